@@ -0,0 +1,191 @@
+//! ツールスキーマの Document 変換のベンチマーク
+//!
+//! 20個のツールを公開するMCPサーバーを想定し、毎ターン行われる
+//! JSON Schema → `Document` 変換のコストを計測する。
+//! あわせて、30個のツールのツール設定（`ToolConfiguration`）を毎ターン構築する場合と、
+//! 構築済みの設定を再利用する場合のコストを比較する。
+//!
+//! 実行方法: `cargo bench -p agent --bench tool_schema_conversion`
+
+use agent::agent::{json_object_to_document, json_to_document};
+use aws_sdk_bedrockruntime::types::{Tool, ToolConfiguration, ToolInputSchema, ToolSpecification};
+use criterion::{Criterion, criterion_group, criterion_main};
+use serde_json::{Map, Value, json};
+use std::hint::black_box;
+
+/// ファイル操作・Git操作系のMCPサーバーを想定したツールスキーマを作成する
+///
+/// 20個を超える場合は、同じ名前に番号を付けたツールを追加する。
+fn realistic_tool_schemas(count: usize) -> Vec<(String, Map<String, Value>)> {
+    let names = [
+        "read_file",
+        "read_multiple_files",
+        "write_file",
+        "edit_file",
+        "create_directory",
+        "list_directory",
+        "directory_tree",
+        "move_file",
+        "search_files",
+        "get_file_info",
+        "git_status",
+        "git_diff",
+        "git_diff_staged",
+        "git_log",
+        "git_show",
+        "git_add",
+        "git_commit",
+        "git_reset",
+        "git_checkout",
+        "git_create_branch",
+    ];
+
+    (0..count)
+        .map(|i| {
+            let name = match i / names.len() {
+                0 => names[i].to_string(),
+                n => format!("{}_{}", names[i % names.len()], n),
+            };
+            let schema = json!({
+                "$schema": "http://json-schema.org/draft-07/schema#",
+                "type": "object",
+                "title": format!("{}_arguments", name),
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "操作対象のファイルまたはディレクトリのパス"
+                    },
+                    "paths": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "minItems": 1,
+                        "description": "複数のパスを指定する場合に使用する"
+                    },
+                    "max_count": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "maximum": 1000,
+                        "default": 10 + i
+                    },
+                    "edits": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "old_text": { "type": "string" },
+                                "new_text": { "type": "string" }
+                            },
+                            "required": ["old_text", "new_text"],
+                            "additionalProperties": false
+                        }
+                    },
+                    "mode": {
+                        "type": "string",
+                        "enum": ["soft", "mixed", "hard"],
+                        "default": "mixed"
+                    },
+                    "dry_run": { "type": "boolean", "default": false },
+                    "exclude_patterns": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "default": []
+                    }
+                },
+                "required": ["path"],
+                "additionalProperties": false
+            });
+            match schema {
+                Value::Object(map) => (name, map),
+                _ => unreachable!(),
+            }
+        })
+        .collect()
+}
+
+/// ツールスキーマからツール設定を構築する（MCPツールの変換と同じ手順）
+fn build_tool_config(schemas: &[(String, Map<String, Value>)]) -> ToolConfiguration {
+    let tools = schemas
+        .iter()
+        .map(|(name, schema)| {
+            let spec = ToolSpecification::builder()
+                .name(name)
+                .description("benchmark tool")
+                .input_schema(ToolInputSchema::Json(
+                    json_object_to_document(schema).unwrap(),
+                ))
+                .build()
+                .unwrap();
+            Tool::ToolSpec(spec)
+        })
+        .collect();
+    ToolConfiguration::builder()
+        .set_tools(Some(tools))
+        .build()
+        .unwrap()
+}
+
+fn bench_tool_schema_conversion(c: &mut Criterion) {
+    let schemas: Vec<Map<String, Value>> = realistic_tool_schemas(20)
+        .into_iter()
+        .map(|(_, schema)| schema)
+        .collect();
+    let mut group = c.benchmark_group("tool_schema_conversion_20_tools");
+
+    // 変換前の方式：スキーマを複製してから所有権付きで変換する
+    group.bench_function("clone_then_json_to_document", |b| {
+        b.iter(|| {
+            for schema in &schemas {
+                black_box(json_to_document(Value::Object(schema.clone())).unwrap());
+            }
+        })
+    });
+
+    // 現在の方式：借用したまま直接変換する
+    group.bench_function("json_object_to_document", |b| {
+        b.iter(|| {
+            for schema in &schemas {
+                black_box(json_object_to_document(schema).unwrap());
+            }
+        })
+    });
+
+    group.finish();
+}
+
+fn bench_tool_config_reuse(c: &mut Criterion) {
+    let schemas = realistic_tool_schemas(30);
+    let cached = build_tool_config(&schemas);
+    let cached_tools = cached.tools().to_vec();
+    let mut group = c.benchmark_group("tool_config_30_tools");
+
+    // キャッシュなし：毎ターン、スキーマの変換からツール設定を構築する
+    group.bench_function("rebuild_each_turn", |b| {
+        b.iter(|| black_box(build_tool_config(&schemas)))
+    });
+
+    // 以前の方式：変換済みのツール定義を複製し、毎ターンツール設定を構築する
+    group.bench_function("clone_tools_then_build", |b| {
+        b.iter(|| {
+            black_box(
+                ToolConfiguration::builder()
+                    .set_tools(Some(cached_tools.clone()))
+                    .build()
+                    .unwrap(),
+            )
+        })
+    });
+
+    // 現在の方式：構築済みのツール設定を複製して再利用する（リクエストが所有権を必要とするため）
+    group.bench_function("reuse_cached_config", |b| {
+        b.iter(|| black_box(cached.clone()))
+    });
+
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_tool_schema_conversion,
+    bench_tool_config_reuse
+);
+criterion_main!(benches);
@@ -0,0 +1,107 @@
+/// キャンセル可能な応答の受け取り
+///
+/// 長い応答の生成中にユーザーが中断できるよう、[`CancellationToken`] がキャンセルされた
+/// 時点でストリームの受信を打ち切る。受信を打ち切った場合も、それまでに受信した内容は
+/// 会話履歴に記録し、次のターンで会話を継続できる状態に保つ。
+use aws_sdk_bedrockruntime::types::ContentBlock;
+use tokio_util::sync::CancellationToken;
+
+use crate::agent::AgentError;
+use crate::channel::EventSource;
+use crate::stream::{StopReason, TurnAccumulator};
+
+/// [`AgentClient::send_message_cancellable`](crate::AgentClient::send_message_cancellable) の結果
+#[derive(Debug, Clone, PartialEq)]
+pub struct CancellableResponse {
+    /// 会話履歴に追加したアシスタントのコンテンツブロック
+    ///
+    /// キャンセルした場合は、それまでに受信したテキストのみ（ツール使用は含めない）。
+    pub content: Vec<ContentBlock>,
+    /// 応答が終了した理由（キャンセルした場合など、受信していない場合は None）
+    pub stop_reason: Option<StopReason>,
+    /// キャンセルにより受信を打ち切ったか
+    pub cancelled: bool,
+}
+
+/// キャンセルされるまでストリームのイベントを受信する
+///
+/// # Arguments
+/// * `source` - ストリームイベントの受信元
+/// * `cancel` - 受信を打ち切るためのトークン
+/// * `on_text` - 応答テキストの差分を受け取る関数
+///
+/// # Returns
+/// * `Ok((TurnAccumulator, bool))` - 受信した内容と、キャンセルにより打ち切ったか
+/// * `Err(AgentError)` - 受信またはツール入力の組み立てに失敗した場合
+pub(crate) async fn receive_until_cancelled(
+    source: &mut impl EventSource,
+    cancel: &CancellationToken,
+    on_text: &mut impl FnMut(&str),
+) -> Result<(TurnAccumulator, bool), AgentError> {
+    let mut accumulator = TurnAccumulator::new();
+    loop {
+        let event = tokio::select! {
+            biased;
+            _ = cancel.cancelled() => return Ok((accumulator, true)),
+            event = source.next_event() => event?,
+        };
+        let Some(event) = event else {
+            return Ok((accumulator, false));
+        };
+        if let Some(text) = accumulator.push_event(event)? {
+            on_text(&text);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aws_sdk_bedrockruntime::types::{
+        ContentBlockDelta, ContentBlockDeltaEvent, ContentBlockStopEvent, ConverseStreamOutput,
+    };
+    use std::collections::VecDeque;
+
+    #[tokio::test]
+    async fn test_receive_stops_when_cancelled() {
+        let text_delta = |text: &str| {
+            ConverseStreamOutput::ContentBlockDelta(
+                ContentBlockDeltaEvent::builder()
+                    .content_block_index(0)
+                    .delta(ContentBlockDelta::Text(text.to_string()))
+                    .build()
+                    .unwrap(),
+            )
+        };
+        let block_stop = ConverseStreamOutput::ContentBlockStop(
+            ContentBlockStopEvent::builder()
+                .content_block_index(0)
+                .build()
+                .unwrap(),
+        );
+
+        // キャンセルしなければ、ストリームの最後まで受信する
+        let mut source: VecDeque<_> = [text_delta("最後"), text_delta("まで"), block_stop].into();
+        let cancel = CancellationToken::new();
+        let (accumulator, cancelled) = receive_until_cancelled(&mut source, &cancel, &mut |_| {})
+            .await
+            .unwrap();
+        assert!(!cancelled);
+        assert_eq!(accumulator.text(), "最後まで");
+
+        // 最初の差分を受信した時点でキャンセルすると、残りのイベントは受信しない
+        let mut source: VecDeque<_> = [text_delta("途中"), text_delta("以降")].into();
+        let mut streamed = Vec::new();
+        let (accumulator, cancelled) = receive_until_cancelled(&mut source, &cancel, &mut |text| {
+            streamed.push(text.to_string());
+            cancel.cancel();
+        })
+        .await
+        .unwrap();
+        assert!(cancelled);
+        assert_eq!(streamed, vec!["途中"]);
+        assert_eq!(source.len(), 1);
+        let content = accumulator.finish();
+        assert_eq!(content[0].as_text().unwrap(), "途中");
+    }
+}
@@ -0,0 +1,193 @@
+/// チャネルによる応答の受け取り
+///
+/// GUIフレームワーク（egui・iced・tauri など）のように、イベントループからストリームを
+/// 待ち受けられない呼び出し側のために、応答の受信を別タスクで行い、受信した内容を
+/// 型付きのイベントとしてチャネルに送る。呼び出し側はイベントループの中で
+/// `try_recv` などを使って、ブロックせずにイベントを取り出せる。
+use std::sync::Arc;
+
+use aws_sdk_bedrockruntime::error::DisplayErrorContext;
+use aws_sdk_bedrockruntime::primitives::event_stream::EventReceiver;
+use aws_sdk_bedrockruntime::types::error::ConverseStreamOutputError;
+use aws_sdk_bedrockruntime::types::{ContentBlock, ConverseStreamOutput, TokenUsage};
+use tokio::sync::{Mutex, mpsc};
+
+use crate::agent::{AgentClient, AgentError};
+use crate::stream::{StreamMetadata, TurnAccumulator};
+
+/// イベントを送るチャネルの容量（受け取りが遅れた場合は、受信タスクが空きを待つ）
+pub const EVENT_CHANNEL_CAPACITY: usize = 64;
+
+/// チャネルに送られる応答のイベント
+#[derive(Debug)]
+pub enum AgentEvent {
+    /// 応答テキストの差分
+    Text(String),
+    /// トークン使用量・レイテンシなどのメタデータ
+    Metadata(StreamMetadata),
+    /// 応答が完了し、会話履歴に追加された
+    ///
+    /// ツール使用を含む場合、ツールの実行とツール結果の追加は呼び出し側が行う。
+    Completed { content: Vec<ContentBlock> },
+    /// 送信または受信に失敗した（送信したユーザーメッセージは会話履歴から取り除かれる）
+    Error(AgentError),
+}
+
+/// ストリームイベントの受信元
+///
+/// テストでは、固定のイベント列に置き換えて受信処理を確認する。
+pub(crate) trait EventSource {
+    /// 次のイベントを受信する（ストリームが終了した場合は None）
+    async fn next_event(&mut self) -> Result<Option<ConverseStreamOutput>, AgentError>;
+}
+
+impl EventSource for EventReceiver<ConverseStreamOutput, ConverseStreamOutputError> {
+    async fn next_event(&mut self) -> Result<Option<ConverseStreamOutput>, AgentError> {
+        self.recv().await.map_err(|e| {
+            AgentError::BedrockError(format!("Stream receive error: {}", DisplayErrorContext(&e)))
+        })
+    }
+}
+
+/// ユーザーのメッセージを送信し、応答のイベントを受け取るチャネルを返す
+///
+/// 送信と受信は別タスクで行い、完了までエージェントのロックを保持する。
+/// 応答が完了すると会話履歴にアシスタントのメッセージを追加してから
+/// [`AgentEvent::Completed`] を送る。受信側を破棄した場合も、会話履歴の整合性を保つため
+/// 応答は最後まで受信する。
+pub(crate) fn spawn_turn(
+    agent: Arc<Mutex<AgentClient>>,
+    user_input: String,
+) -> mpsc::Receiver<AgentEvent> {
+    let (tx, rx) = mpsc::channel(EVENT_CHANNEL_CAPACITY);
+    tokio::spawn(async move {
+        let mut agent = agent.lock().await;
+        // ユーザーメッセージを追加する前に失敗した場合に、以前のメッセージを取り除かないよう
+        // 送信前の件数まで巻き戻す
+        let history_len = agent.history_len();
+        let result = async {
+            let mut response = agent.send_message(&user_input).await?;
+            let (content, usage) = forward_events(&mut response.stream, &tx).await?;
+            if let Some(usage) = &usage {
+                agent.record_usage(usage);
+            }
+            agent.add_assistant_message_with_blocks(content.clone())?;
+            Ok(content)
+        }
+        .await;
+
+        let event = match result {
+            Ok(content) => AgentEvent::Completed { content },
+            Err(e) => {
+                agent.rollback_to(history_len);
+                AgentEvent::Error(e)
+            }
+        };
+        // 受信側が破棄されている場合は送信できないが、会話履歴は更新済みのため無視する
+        let _ = tx.send(event).await;
+    });
+    rx
+}
+
+/// ストリームのイベントを受信し、テキストの差分とメタデータをチャネルに送る
+///
+/// # Returns
+/// * `Ok((Vec<ContentBlock>, Option<TokenUsage>))` - 会話履歴に追加するコンテンツブロックと、
+///   費用の見積もりに使うトークン使用量
+/// * `Err(AgentError)` - 受信またはツール入力の組み立てに失敗した場合
+pub(crate) async fn forward_events(
+    source: &mut impl EventSource,
+    tx: &mpsc::Sender<AgentEvent>,
+) -> Result<(Vec<ContentBlock>, Option<TokenUsage>), AgentError> {
+    let mut accumulator = TurnAccumulator::new();
+    while let Some(event) = source.next_event().await? {
+        if let Some(text) = accumulator.push_event(event)? {
+            // 受信側が破棄されていても、応答は最後まで受信する
+            let _ = tx.send(AgentEvent::Text(text)).await;
+        }
+    }
+    let usage = accumulator
+        .metadata()
+        .and_then(|metadata| metadata.usage.clone());
+    if let Some(metadata) = accumulator.metadata() {
+        let _ = tx.send(AgentEvent::Metadata(metadata.clone())).await;
+    }
+    Ok((accumulator.finish(), usage))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aws_sdk_bedrockruntime::types::{
+        ContentBlockDelta, ContentBlockDeltaEvent, ContentBlockStopEvent,
+        ConverseStreamMetadataEvent,
+    };
+    use std::collections::VecDeque;
+
+    impl EventSource for VecDeque<ConverseStreamOutput> {
+        async fn next_event(&mut self) -> Result<Option<ConverseStreamOutput>, AgentError> {
+            Ok(self.pop_front())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_forward_events_drains_stream_into_channel() {
+        let text_delta = |text: &str| {
+            ConverseStreamOutput::ContentBlockDelta(
+                ContentBlockDeltaEvent::builder()
+                    .content_block_index(0)
+                    .delta(ContentBlockDelta::Text(text.to_string()))
+                    .build()
+                    .unwrap(),
+            )
+        };
+        let usage = TokenUsage::builder()
+            .input_tokens(5)
+            .output_tokens(2)
+            .total_tokens(7)
+            .build()
+            .unwrap();
+        let mut source: VecDeque<_> = [
+            text_delta("こんに"),
+            text_delta("ちは"),
+            ConverseStreamOutput::ContentBlockStop(
+                ContentBlockStopEvent::builder()
+                    .content_block_index(0)
+                    .build()
+                    .unwrap(),
+            ),
+            ConverseStreamOutput::Metadata(
+                ConverseStreamMetadataEvent::builder()
+                    .usage(usage.clone())
+                    .build(),
+            ),
+        ]
+        .into();
+
+        let (tx, mut rx) = mpsc::channel(EVENT_CHANNEL_CAPACITY);
+        let (content, received_usage) = forward_events(&mut source, &tx).await.unwrap();
+        drop(tx);
+
+        let mut texts = Vec::new();
+        let mut metadata = None;
+        while let Some(event) = rx.recv().await {
+            match event {
+                AgentEvent::Text(text) => texts.push(text),
+                AgentEvent::Metadata(received) => metadata = Some(received),
+                other => panic!("unexpected event: {:?}", other),
+            }
+        }
+        assert_eq!(texts, vec!["こんに", "ちは"]);
+        assert_eq!(metadata.unwrap().usage, Some(usage.clone()));
+        assert_eq!(received_usage, Some(usage));
+        assert_eq!(content.len(), 1);
+        assert_eq!(content[0].as_text().unwrap(), "こんにちは");
+
+        // 受信側を破棄しても、応答は最後まで組み立てる
+        let mut source: VecDeque<_> = [text_delta("OK")].into();
+        let (tx, rx) = mpsc::channel(1);
+        drop(rx);
+        let (content, _) = forward_events(&mut source, &tx).await.unwrap();
+        assert_eq!(content[0].as_text().unwrap(), "OK");
+    }
+}
@@ -0,0 +1,81 @@
+/// 読み取り専用の会話（凍結したエージェント）
+///
+/// 会話を分析・表示するツールなどで、誤って会話履歴を変更しないよう、
+/// [`AgentClient::freeze`] で会話履歴の読み取りとトランスクリプトの書き出しだけを
+/// 公開する型に変換する。メッセージの送信や履歴の変更を行うメソッドは持たないため、
+/// 誤った呼び出しはコンパイル時に検出される。
+use std::time::SystemTime;
+
+use crate::agent::{AgentClient, AgentError};
+use crate::history::{HistoryError, Transcript};
+
+/// 会話履歴の変更を受け付けない、読み取り専用のエージェント
+pub struct FrozenAgent {
+    client: AgentClient,
+}
+
+impl FrozenAgent {
+    pub(crate) fn new(client: AgentClient) -> Self {
+        Self { client }
+    }
+
+    /// 会話履歴のメッセージ数を取得する
+    pub fn history_len(&self) -> usize {
+        self.client.history_len()
+    }
+
+    /// 会話履歴の各メッセージを追加した時刻を取得する（古い順、メッセージと同じ件数）
+    pub fn message_timestamps(&self) -> &[SystemTime] {
+        self.client.message_timestamps()
+    }
+
+    /// 会話履歴がBedrockに送信できる形になっているかを検証する
+    pub fn validate_history(&self) -> Result<(), HistoryError> {
+        self.client.validate_history()
+    }
+
+    /// 直近のアシスタントの応答テキストを取得する
+    pub fn last_assistant_text(&self) -> Option<String> {
+        self.client.last_assistant_text()
+    }
+
+    /// 会話履歴をトランスクリプトとして取得する
+    pub fn transcript(&self) -> Result<Transcript, AgentError> {
+        self.client.transcript()
+    }
+
+    /// 会話履歴をJSON文字列として書き出す
+    pub fn export_history(&self) -> Result<String, AgentError> {
+        self.client.export_history()
+    }
+
+    /// 会話の内容を要約した短いタイトルをモデルに生成させる（会話履歴は変更しない）
+    pub async fn generate_title(&self) -> Result<String, AgentError> {
+        self.client.generate_title().await
+    }
+
+    /// 設定されているシステムプロンプトを取得する
+    pub fn system_prompt(&self) -> Option<&str> {
+        self.client.system_prompt()
+    }
+
+    /// 使用するモデルIDを取得する
+    pub fn model_id(&self) -> &str {
+        self.client.model_id()
+    }
+
+    /// 直近の応答を実際に生成したモデルIDを取得する
+    pub fn last_model_id(&self) -> &str {
+        self.client.last_model_id()
+    }
+
+    /// 使用中のリージョン名を取得する
+    pub fn region(&self) -> &str {
+        self.client.region()
+    }
+
+    /// 凍結を解除し、会話を続けられる [`AgentClient`] に戻す
+    pub fn thaw(self) -> AgentClient {
+        self.client
+    }
+}
@@ -0,0 +1,537 @@
+/// 会話履歴のシリアライズ用データ構造
+///
+/// AWS SDK の `Message` は `Serialize` を実装していないため、
+/// 役割とコンテンツブロック（テキスト/ツール使用/ツール結果）を保持する
+/// 独自の中間構造体を定義し、相互変換を提供する。
+use aws_sdk_bedrockruntime::types::{
+    ContentBlock, ConversationRole, Message, ToolResultBlock, ToolResultContentBlock, ToolUseBlock,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use thiserror::Error;
+
+use crate::agent::{AgentError, document_to_json, json_to_document};
+
+/// 会話履歴がBedrockに送信できない状態であることを表すエラー
+///
+/// `index` は問題のあるメッセージの、会話履歴内の位置（0始まり）。
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum HistoryError {
+    #[error("Message {index}: the conversation must start with a user message")]
+    FirstMessageNotUser { index: usize },
+
+    #[error("Message {index}: consecutive {role} messages")]
+    ConsecutiveRole { index: usize, role: String },
+
+    #[error("Message {index}: tool use '{tool_use_id}' has no matching tool result")]
+    MissingToolResult { index: usize, tool_use_id: String },
+
+    #[error("Message {index}: tool result '{tool_use_id}' has no matching tool use")]
+    OrphanedToolResult { index: usize, tool_use_id: String },
+}
+
+/// 保存用の会話記録（トランスクリプト）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Transcript {
+    /// 会話に使用したモデルID
+    pub model_id: String,
+
+    /// 会話に適用していたシステムプロンプト
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_prompt: Option<String>,
+
+    /// メッセージの一覧（古い順）
+    pub messages: Vec<TranscriptMessage>,
+}
+
+/// 保存用のメッセージ
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TranscriptMessage {
+    /// メッセージの送信者
+    pub role: TranscriptRole,
+
+    /// コンテンツブロックの一覧
+    pub content: Vec<TranscriptContent>,
+
+    /// メッセージを追加した時刻（UNIX時間のミリ秒。記録しない設定の場合は None）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp_ms: Option<u64>,
+}
+
+/// メッセージの送信者
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TranscriptRole {
+    User,
+    Assistant,
+}
+
+/// 保存用のコンテンツブロック
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TranscriptContent {
+    /// テキスト
+    Text { text: String },
+
+    /// ツール使用
+    ToolUse {
+        tool_use_id: String,
+        name: String,
+        input: serde_json::Value,
+    },
+
+    /// ツール実行結果
+    ToolResult {
+        tool_use_id: String,
+        content: Vec<TranscriptToolResultContent>,
+    },
+}
+
+/// 保存用のツール実行結果の内容
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TranscriptToolResultContent {
+    /// テキスト形式の結果
+    Text { text: String },
+
+    /// JSON形式の結果
+    Json { json: serde_json::Value },
+}
+
+impl Transcript {
+    /// メッセージをターン単位に分割する
+    ///
+    /// ユーザーのテキスト入力で始まり、続くアシスタントの応答・ツール使用・
+    /// ツール実行結果までを1ターンとする。
+    ///
+    /// # Returns
+    /// ターンごとのメッセージのスライス（古い順）
+    pub fn turns(&self) -> Vec<&[TranscriptMessage]> {
+        let mut turns = Vec::new();
+        let mut start = 0;
+        for (index, message) in self.messages.iter().enumerate() {
+            if index > start && message.is_user_input() {
+                turns.push(&self.messages[start..index]);
+                start = index;
+            }
+        }
+        if start < self.messages.len() {
+            turns.push(&self.messages[start..]);
+        }
+        turns
+    }
+
+    /// ユーザーの入力テキストを取り出す
+    ///
+    /// ツール実行結果を除いた、ユーザーが入力したメッセージのテキストのみを古い順に返す。
+    /// 同じメッセージに複数のテキストブロックがある場合は連結する。
+    pub fn user_inputs(&self) -> Vec<String> {
+        self.messages
+            .iter()
+            .filter(|message| message.is_user_input())
+            .map(|message| {
+                message
+                    .content
+                    .iter()
+                    .filter_map(|content| match content {
+                        TranscriptContent::Text { text } => Some(text.as_str()),
+                        _ => None,
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+impl TranscriptMessage {
+    /// ユーザーの入力（ツール実行結果ではないユーザーメッセージ）かを判定する
+    pub fn is_user_input(&self) -> bool {
+        self.role == TranscriptRole::User
+            && self
+                .content
+                .iter()
+                .any(|content| matches!(content, TranscriptContent::Text { .. }))
+    }
+
+    /// AWS SDK の `Message` から変換する
+    ///
+    /// # Errors
+    /// テキスト/ツール使用/ツール結果以外のコンテンツブロックが含まれる場合
+    pub fn from_message(message: &Message) -> Result<Self, AgentError> {
+        let role = match message.role() {
+            ConversationRole::User => TranscriptRole::User,
+            ConversationRole::Assistant => TranscriptRole::Assistant,
+            other => {
+                return Err(AgentError::MessageBuildError(format!(
+                    "Unsupported message role: {}",
+                    other.as_str()
+                )));
+            }
+        };
+
+        let content = message
+            .content()
+            .iter()
+            .map(TranscriptContent::from_content_block)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            role,
+            content,
+            timestamp_ms: None,
+        })
+    }
+
+    /// AWS SDK の `Message` に変換する
+    ///
+    /// # Errors
+    /// コンテンツが空の場合や、メッセージの構築に失敗した場合
+    pub fn to_message(&self) -> Result<Message, AgentError> {
+        let role = match self.role {
+            TranscriptRole::User => ConversationRole::User,
+            TranscriptRole::Assistant => ConversationRole::Assistant,
+        };
+        let content = self
+            .content
+            .iter()
+            .map(TranscriptContent::to_content_block)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Message::builder()
+            .role(role)
+            .set_content(Some(content))
+            .build()
+            .map_err(|e| AgentError::MessageBuildError(format!("Failed to build message: {}", e)))
+    }
+}
+
+impl TranscriptContent {
+    /// AWS SDK の `ContentBlock` から変換する
+    fn from_content_block(block: &ContentBlock) -> Result<Self, AgentError> {
+        match block {
+            ContentBlock::Text(text) => Ok(Self::Text { text: text.clone() }),
+            ContentBlock::ToolUse(tool_use) => Ok(Self::ToolUse {
+                tool_use_id: tool_use.tool_use_id().to_string(),
+                name: tool_use.name().to_string(),
+                input: document_to_json(tool_use.input().clone())?,
+            }),
+            ContentBlock::ToolResult(tool_result) => Ok(Self::ToolResult {
+                tool_use_id: tool_result.tool_use_id().to_string(),
+                content: tool_result_contents(tool_result)?,
+            }),
+            _ => Err(AgentError::MessageBuildError(
+                "Unsupported content block in history".to_string(),
+            )),
+        }
+    }
+
+    /// AWS SDK の `ContentBlock` に変換する
+    fn to_content_block(&self) -> Result<ContentBlock, AgentError> {
+        let build_error = |e: aws_sdk_bedrockruntime::error::BuildError| {
+            AgentError::MessageBuildError(e.to_string())
+        };
+        match self {
+            Self::Text { text } => Ok(ContentBlock::Text(text.clone())),
+            Self::ToolUse {
+                tool_use_id,
+                name,
+                input,
+            } => Ok(ContentBlock::ToolUse(
+                ToolUseBlock::builder()
+                    .tool_use_id(tool_use_id)
+                    .name(name)
+                    .input(json_to_document(input.clone())?)
+                    .build()
+                    .map_err(build_error)?,
+            )),
+            Self::ToolResult {
+                tool_use_id,
+                content,
+            } => {
+                let content = content
+                    .iter()
+                    .map(|content| match content {
+                        TranscriptToolResultContent::Text { text } => {
+                            Ok(ToolResultContentBlock::Text(text.clone()))
+                        }
+                        TranscriptToolResultContent::Json { json } => Ok(
+                            ToolResultContentBlock::Json(json_to_document(json.clone())?),
+                        ),
+                    })
+                    .collect::<Result<Vec<_>, AgentError>>()?;
+                Ok(ContentBlock::ToolResult(
+                    ToolResultBlock::builder()
+                        .tool_use_id(tool_use_id)
+                        .set_content(Some(content))
+                        .build()
+                        .map_err(build_error)?,
+                ))
+            }
+        }
+    }
+}
+
+/// 会話履歴の末尾にメッセージを追加しても、送信できる形を保てるかを検証する
+///
+/// [`validate_messages`] と同じ条件を、追加するメッセージについてのみ確認する。
+/// ツール結果はツール使用ごとに追加されるため、直前のアシスタントのツール使用の一部にのみ
+/// ツール結果が対応している途中の状態は許容し、ツール結果のないツール使用が残ったまま
+/// ユーザーのテキストやアシスタントのメッセージを追加する場合をエラーとする。
+///
+/// # Arguments
+/// * `messages` - 現在の会話履歴
+/// * `next` - 追加するメッセージ
+pub fn validate_append(messages: &[Message], next: &Message) -> Result<(), HistoryError> {
+    let index = messages.len();
+
+    // 直前のアシスタントのメッセージのツール使用のうち、まだツール結果のないもの
+    let last_assistant = messages
+        .iter()
+        .rposition(|message| matches!(message.role(), ConversationRole::Assistant));
+    let known_tool_uses: Vec<&str> = last_assistant
+        .map(|position| {
+            messages[position]
+                .content()
+                .iter()
+                .filter_map(|block| block.as_tool_use().ok())
+                .map(|tool_use| tool_use.tool_use_id())
+                .collect()
+        })
+        .unwrap_or_default();
+    let resolved: HashSet<&str> = messages[last_assistant.map_or(0, |position| position + 1)..]
+        .iter()
+        .flat_map(|message| message.content())
+        .filter_map(|block| block.as_tool_result().ok())
+        .map(|tool_result| tool_result.tool_use_id())
+        .collect();
+    let mut pending = known_tool_uses.iter().filter(|id| !resolved.contains(*id));
+    let missing = |tool_use_id: &str| HistoryError::MissingToolResult {
+        index: last_assistant.unwrap_or_default(),
+        tool_use_id: tool_use_id.to_string(),
+    };
+
+    match next.role() {
+        ConversationRole::User => {
+            let is_tool_result_only = !next.content().is_empty()
+                && next.content().iter().all(ContentBlock::is_tool_result);
+            if !is_tool_result_only {
+                if messages
+                    .last()
+                    .is_some_and(|last| matches!(last.role(), ConversationRole::User))
+                {
+                    return Err(HistoryError::ConsecutiveRole {
+                        index,
+                        role: "user".to_string(),
+                    });
+                }
+                if let Some(tool_use_id) = pending.next() {
+                    return Err(missing(tool_use_id));
+                }
+            }
+            for block in next.content() {
+                let Ok(tool_result) = block.as_tool_result() else {
+                    continue;
+                };
+                let tool_use_id = tool_result.tool_use_id();
+                if !known_tool_uses.contains(&tool_use_id) {
+                    return Err(HistoryError::OrphanedToolResult {
+                        index,
+                        tool_use_id: tool_use_id.to_string(),
+                    });
+                }
+            }
+        }
+        ConversationRole::Assistant => {
+            let Some(last) = messages.last() else {
+                return Err(HistoryError::FirstMessageNotUser { index });
+            };
+            if matches!(last.role(), ConversationRole::Assistant) {
+                return Err(HistoryError::ConsecutiveRole {
+                    index,
+                    role: "assistant".to_string(),
+                });
+            }
+            if let Some(tool_use_id) = pending.next() {
+                return Err(missing(tool_use_id));
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// 会話履歴がBedrockに送信できる形になっているかを検証する
+///
+/// 次の条件を確認する：
+/// - 最初のメッセージがユーザーのもの
+/// - アシスタントのメッセージが連続しない
+/// - ユーザーのメッセージが連続するのは、ツール結果のみのメッセージが続く場合に限る
+///   （ツール結果はツール使用ごとに個別のメッセージとして追加されるため）
+/// - アシスタントのツール使用に、続くユーザーのメッセージ群でツール結果が対応している
+/// - ツール結果が、直前のアシスタントのメッセージのツール使用に対応している
+///
+/// # Note
+/// 最後のメッセージのツール使用は実行待ちとみなし、ツール結果がなくてもエラーとしない。
+pub fn validate_messages(messages: &[Message]) -> Result<(), HistoryError> {
+    // 直前のアシスタントのメッセージで、まだツール結果を受け取っていないツール使用
+    let mut pending_tool_uses: Vec<(usize, String)> = Vec::new();
+    // 直前のアシスタントのメッセージのツール使用ID
+    let mut known_tool_uses: HashSet<String> = HashSet::new();
+
+    for (index, message) in messages.iter().enumerate() {
+        let is_tool_result_only = !message.content().is_empty()
+            && message.content().iter().all(ContentBlock::is_tool_result);
+
+        match message.role() {
+            ConversationRole::User => {
+                if index > 0
+                    && matches!(messages[index - 1].role(), ConversationRole::User)
+                    && !is_tool_result_only
+                {
+                    return Err(HistoryError::ConsecutiveRole {
+                        index,
+                        role: "user".to_string(),
+                    });
+                }
+                for block in message.content() {
+                    let Ok(tool_result) = block.as_tool_result() else {
+                        continue;
+                    };
+                    let tool_use_id = tool_result.tool_use_id();
+                    if !known_tool_uses.contains(tool_use_id) {
+                        return Err(HistoryError::OrphanedToolResult {
+                            index,
+                            tool_use_id: tool_use_id.to_string(),
+                        });
+                    }
+                    pending_tool_uses.retain(|(_, id)| id != tool_use_id);
+                }
+            }
+            ConversationRole::Assistant => {
+                if index == 0 {
+                    return Err(HistoryError::FirstMessageNotUser { index });
+                }
+                if matches!(messages[index - 1].role(), ConversationRole::Assistant) {
+                    return Err(HistoryError::ConsecutiveRole {
+                        index,
+                        role: "assistant".to_string(),
+                    });
+                }
+                if let Some((index, tool_use_id)) = pending_tool_uses.drain(..).next() {
+                    return Err(HistoryError::MissingToolResult { index, tool_use_id });
+                }
+                known_tool_uses.clear();
+                for block in message.content() {
+                    if let Ok(tool_use) = block.as_tool_use() {
+                        known_tool_uses.insert(tool_use.tool_use_id().to_string());
+                        pending_tool_uses.push((index, tool_use.tool_use_id().to_string()));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // 最後のメッセージのツール使用は実行待ちのため許容する
+    let last_index = messages.len().saturating_sub(1);
+    match pending_tool_uses
+        .into_iter()
+        .find(|(index, _)| *index != last_index)
+    {
+        Some((index, tool_use_id)) => Err(HistoryError::MissingToolResult { index, tool_use_id }),
+        None => Ok(()),
+    }
+}
+
+/// ツール結果ブロックの内容を保存用の形式に変換する
+fn tool_result_contents(
+    tool_result: &ToolResultBlock,
+) -> Result<Vec<TranscriptToolResultContent>, AgentError> {
+    tool_result
+        .content()
+        .iter()
+        .map(|content| match content {
+            ToolResultContentBlock::Text(text) => {
+                Ok(TranscriptToolResultContent::Text { text: text.clone() })
+            }
+            ToolResultContentBlock::Json(doc) => Ok(TranscriptToolResultContent::Json {
+                json: document_to_json(doc.clone())?,
+            }),
+            _ => Err(AgentError::MessageBuildError(
+                "Unsupported tool result content in history".to_string(),
+            )),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_message_serializes_tool_use() {
+        let message = Message::builder()
+            .role(ConversationRole::Assistant)
+            .content(ContentBlock::Text("確認します".to_string()))
+            .content(ContentBlock::ToolUse(
+                ToolUseBlock::builder()
+                    .tool_use_id("tool-1")
+                    .name("git_status")
+                    .input(
+                        crate::agent::json_to_document(serde_json::json!({ "repo_path": "." }))
+                            .unwrap(),
+                    )
+                    .build()
+                    .unwrap(),
+            ))
+            .build()
+            .unwrap();
+
+        let transcript = TranscriptMessage::from_message(&message).unwrap();
+        assert_eq!(
+            serde_json::to_value(&transcript).unwrap(),
+            serde_json::json!({
+                "role": "assistant",
+                "content": [
+                    { "type": "text", "text": "確認します" },
+                    {
+                        "type": "tool_use",
+                        "tool_use_id": "tool-1",
+                        "name": "git_status",
+                        "input": { "repo_path": "." }
+                    }
+                ]
+            })
+        );
+
+        // 保存用の形式から元のメッセージに戻せる
+        assert_eq!(transcript.to_message().unwrap(), message);
+    }
+
+    #[test]
+    fn test_turns_group_tool_results_into_the_same_turn() {
+        let transcript: Transcript = serde_json::from_value(serde_json::json!({
+            "model_id": "model",
+            "messages": [
+                { "role": "user", "content": [{ "type": "text", "text": "状態は？" }] },
+                { "role": "assistant", "content": [{
+                    "type": "tool_use", "tool_use_id": "t1", "name": "git_status", "input": {}
+                }] },
+                { "role": "user", "content": [{
+                    "type": "tool_result", "tool_use_id": "t1",
+                    "content": [{ "type": "text", "text": "clean" }]
+                }] },
+                { "role": "assistant", "content": [{ "type": "text", "text": "クリーンです" }] },
+                { "role": "user", "content": [{ "type": "text", "text": "ありがとう" }] },
+                { "role": "assistant", "content": [{ "type": "text", "text": "どういたしまして" }] }
+            ]
+        }))
+        .unwrap();
+
+        let turns = transcript.turns();
+        assert_eq!(turns.len(), 2);
+        assert_eq!(turns[0].len(), 4);
+        assert_eq!(turns[1].len(), 2);
+        assert_eq!(transcript.user_inputs(), vec!["状態は？", "ありがとう"]);
+    }
+}
@@ -1,4 +1,38 @@
 pub mod agent;
+pub mod cancel;
+pub mod channel;
+pub mod frozen;
+pub mod history;
+pub mod model_target;
+pub mod models;
+pub mod pricing;
+pub mod retry;
+pub mod schema;
+pub mod stream;
+pub mod tool_cache;
+pub mod tool_guard;
+pub mod tool_result_pager;
+pub mod tool_settings;
 
-pub use agent::{AgentClient, AgentError};
+pub use agent::{
+    AgentClient, AgentError, AssumeRoleConfig, CredentialStatus, DEFAULT_APP_NAME,
+    DEFAULT_MAX_TOOL_ITERATIONS, DEFAULT_MCP_SERVER_NAME, DEFAULT_MODEL_ID,
+    MCP_TOOL_NAMESPACE_SEPARATOR, PostResponseHook, PreSendHook, SdkClientConfig,
+    model_supports_tool_use,
+};
 pub use aws_sdk_bedrockruntime::operation::converse_stream::ConverseStreamOutput as ConverseStreamResponse;
+pub use aws_smithy_types::retry::RetryConfig;
+pub use aws_smithy_types::timeout::TimeoutConfig;
+pub use cancel::CancellableResponse;
+pub use channel::AgentEvent;
+pub use frozen::FrozenAgent;
+pub use history::{HistoryError, Transcript};
+pub use model_target::ModelTarget;
+pub use models::{FoundationModelInfo, ModelListFilter};
+pub use retry::RetryPolicy;
+pub use stream::{DeltaKind, StopReason, StreamMetadata, TurnAccumulator};
+pub use tokio_util::sync::CancellationToken;
+pub use tool_cache::{ToolCacheConfig, ToolCallOutcome};
+pub use tool_guard::ToolGuardConfig;
+pub use tool_result_pager::ToolResultPagingConfig;
+pub use tool_settings::ToolExecutionConfig;
@@ -0,0 +1,165 @@
+/// リクエストの送信先（基盤モデル・推論プロファイル）
+///
+/// Converse API の `modelId` には、基盤モデルのIDのほか、リージョンをまたいで
+/// リクエストを振り分けるクロスリージョン推論プロファイルのID（`us.` などの地域接頭辞付き）や、
+/// 推論プロファイルのARNを指定できる。どちらを指定しているかを型で区別し、
+/// 推論プロファイルとして指定された値はその形式を検証する。
+use std::fmt;
+
+use crate::agent::AgentError;
+
+/// 推論プロファイルのIDに付く地域接頭辞
+pub(crate) const INFERENCE_PROFILE_PREFIXES: &[&str] = &[
+    "us.", "eu.", "apac.", "us-gov.", "jp.", "au.", "ca.", "global.",
+];
+
+/// 推論プロファイルのARNのリソースの種類
+const INFERENCE_PROFILE_RESOURCE_TYPES: &[&str] =
+    &["inference-profile/", "application-inference-profile/"];
+
+/// リクエストの送信先
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModelTarget {
+    /// 基盤モデルのID（例: `anthropic.claude-3-5-sonnet-20240620-v1:0`）や、基盤モデルのARN
+    Model(String),
+    /// 推論プロファイルのID（例: `us.anthropic.claude-3-5-sonnet-20240620-v1:0`）またはARN
+    InferenceProfile(String),
+}
+
+impl ModelTarget {
+    /// 文字列の形式から、基盤モデルと推論プロファイルのどちらかを判別する
+    ///
+    /// 推論プロファイルのIDやARNの形式であれば [`ModelTarget::InferenceProfile`]、
+    /// それ以外は [`ModelTarget::Model`] とする。
+    pub fn from_id(id: impl Into<String>) -> Self {
+        let id = id.into();
+        if is_inference_profile(&id) {
+            Self::InferenceProfile(id)
+        } else {
+            Self::Model(id)
+        }
+    }
+
+    /// 推論プロファイルのIDまたはARNを検証して作成する
+    ///
+    /// # Arguments
+    /// * `id` - 推論プロファイルのID（`us.` などの地域接頭辞付き）、または
+    ///   `arn:aws:bedrock:<リージョン>:<アカウントID>:inference-profile/<ID>` 形式のARN
+    ///   （アプリケーション推論プロファイルの `application-inference-profile/` も可）
+    ///
+    /// # Errors
+    /// * `AgentError::ConfigError` - 推論プロファイルのIDまたはARNの形式でない場合
+    pub fn inference_profile(id: impl Into<String>) -> Result<Self, AgentError> {
+        let id = id.into();
+        if is_inference_profile(&id) {
+            Ok(Self::InferenceProfile(id))
+        } else {
+            Err(AgentError::ConfigError(format!(
+                "Invalid inference profile '{}': expected an ID with a geographic prefix (e.g. us.anthropic.claude-3-5-sonnet-20240620-v1:0) or an ARN such as arn:aws:bedrock:us-east-1:123456789012:inference-profile/<id>",
+                id
+            )))
+        }
+    }
+
+    /// リクエストの `modelId` に指定する値
+    pub fn id(&self) -> &str {
+        match self {
+            Self::Model(id) | Self::InferenceProfile(id) => id,
+        }
+    }
+
+    /// 推論プロファイルか
+    pub fn is_inference_profile(&self) -> bool {
+        matches!(self, Self::InferenceProfile(_))
+    }
+}
+
+impl fmt::Display for ModelTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.id())
+    }
+}
+
+/// 推論プロファイルのIDまたはARNの形式か
+fn is_inference_profile(id: &str) -> bool {
+    if id.starts_with("arn:") {
+        is_inference_profile_arn(id)
+    } else {
+        INFERENCE_PROFILE_PREFIXES
+            .iter()
+            .filter_map(|prefix| id.strip_prefix(prefix))
+            .any(|model| model.contains('.') && !model.starts_with('.'))
+    }
+}
+
+/// `arn:<パーティション>:bedrock:<リージョン>:<アカウントID>:<種類>/<ID>` 形式の推論プロファイルのARNか
+fn is_inference_profile_arn(arn: &str) -> bool {
+    let parts: Vec<&str> = arn.splitn(6, ':').collect();
+    let [_, partition, service, region, account, resource] = parts.as_slice() else {
+        return false;
+    };
+    partition.starts_with("aws")
+        && *service == "bedrock"
+        && !region.is_empty()
+        && account.len() == 12
+        && account.chars().all(|c| c.is_ascii_digit())
+        && INFERENCE_PROFILE_RESOURCE_TYPES
+            .iter()
+            .filter_map(|resource_type| resource.strip_prefix(resource_type))
+            .any(|profile_id| !profile_id.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_id_distinguishes_inference_profiles() {
+        assert_eq!(
+            ModelTarget::from_id("anthropic.claude-3-5-sonnet-20240620-v1:0"),
+            ModelTarget::Model("anthropic.claude-3-5-sonnet-20240620-v1:0".to_string())
+        );
+        let profile = ModelTarget::from_id("us.anthropic.claude-3-5-sonnet-20240620-v1:0");
+        assert!(profile.is_inference_profile());
+        assert_eq!(profile.id(), "us.anthropic.claude-3-5-sonnet-20240620-v1:0");
+        assert!(
+            ModelTarget::from_id(
+                "arn:aws:bedrock:us-east-1:123456789012:application-inference-profile/abc123"
+            )
+            .is_inference_profile()
+        );
+        // 基盤モデルのARNは推論プロファイルとして扱わない
+        assert!(
+            !ModelTarget::from_id(
+                "arn:aws:bedrock:us-east-1::foundation-model/anthropic.claude-3-haiku-20240307-v1:0"
+            )
+            .is_inference_profile()
+        );
+    }
+
+    #[test]
+    fn test_inference_profile_validates_format() {
+        assert!(ModelTarget::inference_profile("eu.amazon.nova-pro-v1:0").is_ok());
+        assert!(
+            ModelTarget::inference_profile(
+                "arn:aws:bedrock:us-west-2:123456789012:inference-profile/us.anthropic.claude-3-5-sonnet-20240620-v1:0"
+            )
+            .is_ok()
+        );
+        for invalid in [
+            "anthropic.claude-3-5-sonnet-20240620-v1:0",
+            "us.",
+            "us.claude",
+            "arn:aws:bedrock:us-east-1:123456789012:inference-profile/",
+            "arn:aws:bedrock:us-east-1:1234:inference-profile/us.anthropic.claude",
+            "arn:aws:s3:us-east-1:123456789012:inference-profile/us.anthropic.claude",
+        ] {
+            let result = ModelTarget::inference_profile(invalid);
+            assert!(
+                matches!(result, Err(AgentError::ConfigError(_))),
+                "{} should be rejected",
+                invalid
+            );
+        }
+    }
+}
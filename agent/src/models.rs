@@ -0,0 +1,140 @@
+/// アカウントで利用可能な基盤モデルの一覧
+///
+/// Bedrockのコントロールプレーン（`aws-sdk-bedrock`）の `ListFoundationModels` で取得した
+/// 基盤モデルの概要を保持し、ストリーミング対応・Converse API対応などで絞り込む。
+/// 一覧の取得は [`AgentClient::list_foundation_models`](crate::AgentClient::list_foundation_models) で行う。
+use aws_sdk_bedrock::types::FoundationModelSummary;
+
+/// テキストを表すモダリティ
+const TEXT_MODALITY: &str = "TEXT";
+
+/// 基盤モデルの概要
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FoundationModelInfo {
+    /// モデルID（`--model` に指定する値）
+    pub model_id: String,
+    /// モデルの表示名
+    pub model_name: Option<String>,
+    /// プロバイダ名（例: `Anthropic`）
+    pub provider_name: Option<String>,
+    /// 入力できるモダリティ（例: `TEXT`・`IMAGE`）
+    pub input_modalities: Vec<String>,
+    /// 出力するモダリティ（例: `TEXT`・`EMBEDDING`）
+    pub output_modalities: Vec<String>,
+    /// ストリーミング（ConverseStream）で応答を受け取れるか
+    pub response_streaming_supported: bool,
+    /// 対応する推論の種類（例: `ON_DEMAND`・`PROVISIONED`）
+    pub inference_types: Vec<String>,
+}
+
+impl FoundationModelInfo {
+    /// Converse APIで会話に使用できるか
+    ///
+    /// APIの応答には対応状況が含まれないため、テキストを入力してテキストを出力するモデルを
+    /// 対応しているものとみなす（埋め込みモデルや画像生成モデルは対応しない）。
+    pub fn supports_converse(&self) -> bool {
+        self.input_modalities.iter().any(|m| m == TEXT_MODALITY)
+            && self.output_modalities.iter().any(|m| m == TEXT_MODALITY)
+    }
+}
+
+impl From<&FoundationModelSummary> for FoundationModelInfo {
+    fn from(summary: &FoundationModelSummary) -> Self {
+        Self {
+            model_id: summary.model_id().to_string(),
+            model_name: summary.model_name().map(str::to_string),
+            provider_name: summary.provider_name().map(str::to_string),
+            input_modalities: summary
+                .input_modalities()
+                .iter()
+                .map(|m| m.as_str().to_string())
+                .collect(),
+            output_modalities: summary
+                .output_modalities()
+                .iter()
+                .map(|m| m.as_str().to_string())
+                .collect(),
+            response_streaming_supported: summary.response_streaming_supported().unwrap_or(false),
+            inference_types: summary
+                .inference_types_supported()
+                .iter()
+                .map(|t| t.as_str().to_string())
+                .collect(),
+        }
+    }
+}
+
+/// 基盤モデルの一覧の絞り込み条件
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModelListFilter {
+    /// プロバイダ名（例: `Anthropic`。APIのリクエストで絞り込む）
+    pub provider: Option<String>,
+    /// ストリーミングに対応するモデルのみ
+    pub streaming_only: bool,
+    /// Converse APIに対応するモデルのみ
+    pub converse_only: bool,
+}
+
+impl ModelListFilter {
+    /// モデルが条件に合うか（プロバイダ名はAPIのリクエストで絞り込むため判定しない）
+    pub fn matches(&self, model: &FoundationModelInfo) -> bool {
+        (!self.streaming_only || model.response_streaming_supported)
+            && (!self.converse_only || model.supports_converse())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn model(
+        model_id: &str,
+        input: &[&str],
+        output: &[&str],
+        streaming: bool,
+    ) -> FoundationModelInfo {
+        FoundationModelInfo {
+            model_id: model_id.to_string(),
+            model_name: None,
+            provider_name: None,
+            input_modalities: input.iter().map(|m| m.to_string()).collect(),
+            output_modalities: output.iter().map(|m| m.to_string()).collect(),
+            response_streaming_supported: streaming,
+            inference_types: vec!["ON_DEMAND".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_model_list_filter_matches() {
+        let chat = model(
+            "anthropic.claude-3-haiku-20240307-v1:0",
+            &["TEXT", "IMAGE"],
+            &["TEXT"],
+            true,
+        );
+        let embedding = model(
+            "amazon.titan-embed-text-v2:0",
+            &["TEXT"],
+            &["EMBEDDING"],
+            false,
+        );
+        let image = model("stability.sd3-large-v1:0", &["TEXT"], &["IMAGE"], false);
+        assert!(chat.supports_converse());
+        assert!(!embedding.supports_converse());
+        assert!(!image.supports_converse());
+
+        assert!(ModelListFilter::default().matches(&embedding));
+        let streaming = ModelListFilter {
+            streaming_only: true,
+            ..ModelListFilter::default()
+        };
+        assert!(streaming.matches(&chat));
+        assert!(!streaming.matches(&embedding));
+        let converse = ModelListFilter {
+            converse_only: true,
+            ..ModelListFilter::default()
+        };
+        assert!(converse.matches(&chat));
+        assert!(!converse.matches(&image));
+    }
+}
@@ -0,0 +1,97 @@
+/// モデルの料金表と、トークン使用量からの費用の見積もり
+///
+/// Bedrockのオンデマンド料金（USリージョン、100万トークンあたりのUSD）をもとに、
+/// 応答のトークン使用量からおおよその費用を計算する。料金はリージョンや改定により
+/// 異なる場合があるため、見積もりは目安として扱う。
+use aws_sdk_bedrockruntime::types::TokenUsage;
+
+/// 100万トークンあたりの料金（USD）
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModelPricing {
+    /// 入力トークンの料金
+    pub input_per_million_usd: f64,
+    /// 出力トークンの料金
+    pub output_per_million_usd: f64,
+}
+
+impl ModelPricing {
+    const fn new(input_per_million_usd: f64, output_per_million_usd: f64) -> Self {
+        Self {
+            input_per_million_usd,
+            output_per_million_usd,
+        }
+    }
+
+    /// トークン使用量から費用（USD）を計算する
+    ///
+    /// プロンプトキャッシュの読み書きのトークンは、通常の入力トークンとして計算する。
+    pub fn cost_usd(&self, usage: &TokenUsage) -> f64 {
+        let input_tokens = usage.input_tokens()
+            + usage.cache_read_input_tokens().unwrap_or_default()
+            + usage.cache_write_input_tokens().unwrap_or_default();
+        (f64::from(input_tokens) * self.input_per_million_usd
+            + f64::from(usage.output_tokens()) * self.output_per_million_usd)
+            / 1_000_000.0
+    }
+}
+
+/// モデルIDに含まれる文字列と料金の対応（前から順に照合するため、具体的なものを先に置く）
+const PRICING_TABLE: &[(&str, ModelPricing)] = &[
+    ("anthropic.claude-opus-4-5", ModelPricing::new(5.0, 25.0)),
+    ("anthropic.claude-opus-4", ModelPricing::new(15.0, 75.0)),
+    ("anthropic.claude-sonnet-4", ModelPricing::new(3.0, 15.0)),
+    ("anthropic.claude-haiku-4-5", ModelPricing::new(1.0, 5.0)),
+    ("anthropic.claude-3-7-sonnet", ModelPricing::new(3.0, 15.0)),
+    ("anthropic.claude-3-5-sonnet", ModelPricing::new(3.0, 15.0)),
+    ("anthropic.claude-3-5-haiku", ModelPricing::new(0.8, 4.0)),
+    ("anthropic.claude-3-opus", ModelPricing::new(15.0, 75.0)),
+    ("anthropic.claude-3-sonnet", ModelPricing::new(3.0, 15.0)),
+    ("anthropic.claude-3-haiku", ModelPricing::new(0.25, 1.25)),
+    ("amazon.nova-premier", ModelPricing::new(2.5, 12.5)),
+    ("amazon.nova-pro", ModelPricing::new(0.8, 3.2)),
+    ("amazon.nova-lite", ModelPricing::new(0.06, 0.24)),
+    ("amazon.nova-micro", ModelPricing::new(0.035, 0.14)),
+];
+
+/// モデルIDに対応する料金を取得する
+///
+/// 推論プロファイル（`us.` などの接頭辞付き）やARNのモデルIDにも対応する。
+///
+/// # Returns
+/// 料金表にないモデルの場合は None
+pub fn pricing_for_model(model_id: &str) -> Option<ModelPricing> {
+    PRICING_TABLE
+        .iter()
+        .find(|(pattern, _)| model_id.contains(pattern))
+        .map(|(_, pricing)| *pricing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pricing_for_model_and_cost() {
+        let sonnet = pricing_for_model("us.anthropic.claude-3-5-sonnet-20240620-v1:0").unwrap();
+        assert_eq!(sonnet, ModelPricing::new(3.0, 15.0));
+        assert_eq!(
+            pricing_for_model("anthropic.claude-opus-4-5-20251101-v1:0"),
+            Some(ModelPricing::new(5.0, 25.0))
+        );
+        assert_eq!(
+            pricing_for_model("anthropic.claude-opus-4-1-20250805-v1:0"),
+            Some(ModelPricing::new(15.0, 75.0))
+        );
+        assert_eq!(pricing_for_model("unknown.model-v1"), None);
+
+        let usage = TokenUsage::builder()
+            .input_tokens(1_000)
+            .output_tokens(2_000)
+            .total_tokens(3_000)
+            .cache_read_input_tokens(1_000)
+            .build()
+            .unwrap();
+        // (2,000 * 3 + 2,000 * 15) / 1,000,000
+        assert!((sonnet.cost_usd(&usage) - 0.036).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,106 @@
+/// Bedrockへのリクエストの再試行（指数バックオフ）
+///
+/// スロットリングや一時的な提供停止でリクエストが失敗した場合に、待機時間を倍々に
+/// 伸ばしながら同じリクエストを再送信する。複数のクライアントが同時に再試行して
+/// 再び集中しないよう、待機時間にはランダムな揺らぎ（ジッター）を加える。
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+/// 再試行の最大回数のデフォルト
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+
+/// 最初の再試行までの待機時間のデフォルト
+pub const DEFAULT_INITIAL_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// 再試行までの最大待機時間のデフォルト
+pub const DEFAULT_MAX_RETRY_DELAY: Duration = Duration::from_secs(20);
+
+/// リクエストの再試行の設定
+///
+/// [`AgentError::is_retryable`](crate::AgentError::is_retryable) が true のエラーのみ再試行し、
+/// 入力の検証エラー（`ValidationException`）などはすぐに返す。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// 再試行の最大回数（0 の場合は再試行しない）
+    pub max_retries: u32,
+    /// 最初の再試行までの待機時間（再試行のたびに倍にする）
+    pub initial_delay: Duration,
+    /// 再試行までの最大待機時間
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: DEFAULT_MAX_RETRIES,
+            initial_delay: DEFAULT_INITIAL_RETRY_DELAY,
+            max_delay: DEFAULT_MAX_RETRY_DELAY,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// 再試行しない設定
+    pub fn disabled() -> Self {
+        Self {
+            max_retries: 0,
+            ..Self::default()
+        }
+    }
+
+    /// 再試行までの待機時間を計算する
+    ///
+    /// # Arguments
+    /// * `attempt` - これまでに再試行した回数（最初の再試行は 0）
+    pub fn delay_for(&self, attempt: u32) -> Duration {
+        self.backoff_delay(attempt, random_fraction())
+    }
+
+    /// 待機時間の上限（指数バックオフ）の半分に、揺らぎの割合に応じた残りの半分を加える
+    fn backoff_delay(&self, attempt: u32, fraction: f64) -> Duration {
+        let ceiling = self
+            .initial_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay);
+        ceiling / 2 + (ceiling / 2).mul_f64(fraction.clamp(0.0, 1.0))
+    }
+}
+
+/// 0.0 以上 1.0 未満のランダムな値を返す
+///
+/// 揺らぎの用途には十分なため、乱数クレートの代わりに `RandomState` のランダムなキーを使う。
+fn random_fraction() -> f64 {
+    let random = RandomState::new().build_hasher().finish();
+    (random >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_delay_grows_and_is_capped() {
+        let policy = RetryPolicy {
+            max_retries: 5,
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(5),
+        };
+        assert_eq!(policy.backoff_delay(0, 0.0), Duration::from_millis(500));
+        assert_eq!(policy.backoff_delay(0, 1.0), Duration::from_secs(1));
+        assert_eq!(policy.backoff_delay(2, 1.0), Duration::from_secs(4));
+        // 上限を超えず、回数が大きくてもオーバーフローしない
+        assert_eq!(policy.backoff_delay(3, 1.0), Duration::from_secs(5));
+        assert_eq!(
+            policy.backoff_delay(u32::MAX, 0.0),
+            Duration::from_millis(2500)
+        );
+
+        for attempt in 0..5 {
+            let delay = policy.delay_for(attempt);
+            assert!(delay >= policy.backoff_delay(attempt, 0.0));
+            assert!(delay <= policy.backoff_delay(attempt, 1.0));
+        }
+        assert_eq!(RetryPolicy::disabled().max_retries, 0);
+    }
+}
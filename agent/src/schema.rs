@@ -0,0 +1,438 @@
+/// JSON Schema の簡易バリデーション
+///
+/// 構造化出力（`send_expecting_json`）でモデルが返したJSONを検証するための、
+/// 外部クレートに依存しない最小限のJSON Schemaサブセット実装。
+///
+/// サポートするキーワード：
+/// - `type`（文字列または文字列の配列）
+/// - `properties` / `required` / `additionalProperties: false`
+/// - `items`
+/// - `enum`
+///
+/// あわせて、MCPツールの入力スキーマをBedrockが受け付ける形に補正する正規化処理を提供する。
+use serde_json::{Map, Value, json};
+
+/// Bedrockのツール入力スキーマで受け付けられないため取り除くキーワード
+const UNSUPPORTED_TOOL_SCHEMA_KEYWORDS: &[&str] = &["$schema", "$id", "$comment"];
+
+/// ルートがオブジェクト型でないスキーマを包むプロパティ名
+const WRAPPED_INPUT_PROPERTY: &str = "input";
+
+/// JSON値をスキーマに照らして検証する
+///
+/// # Arguments
+/// * `value` - 検証対象のJSON値
+/// * `schema` - JSON Schema
+///
+/// # Returns
+/// * `Ok(())` - スキーマに適合する場合
+/// * `Err(Vec<String>)` - 違反内容（JSON Pointer形式のパス付き）の一覧
+pub fn validate(value: &Value, schema: &Value) -> Result<(), Vec<String>> {
+    let mut errors = Vec::new();
+    validate_at(value, schema, "", &mut errors);
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// 指定パスの値を再帰的に検証し、違反を `errors` に追加する
+fn validate_at(value: &Value, schema: &Value, path: &str, errors: &mut Vec<String>) {
+    let Some(schema) = schema.as_object() else {
+        // `true` や空オブジェクトなど、制約のないスキーマは常に適合
+        return;
+    };
+    let display_path = if path.is_empty() { "/" } else { path };
+
+    if let Some(expected) = schema.get("type") {
+        let allowed: Vec<&str> = match expected {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !allowed.is_empty() && !allowed.iter().any(|t| matches_type(value, t)) {
+            errors.push(format!(
+                "{}: expected type {}, got {}",
+                display_path,
+                allowed.join(" | "),
+                type_name(value)
+            ));
+            // 型が異なる場合、以降のキーワードは意味を持たない
+            return;
+        }
+    }
+
+    if let Some(Value::Array(candidates)) = schema.get("enum")
+        && !candidates.contains(value)
+    {
+        errors.push(format!(
+            "{}: value is not one of the allowed enum values",
+            display_path
+        ));
+    }
+
+    if let Value::Object(map) = value {
+        let properties = schema.get("properties").and_then(Value::as_object);
+
+        if let Some(Value::Array(required)) = schema.get("required") {
+            for key in required.iter().filter_map(Value::as_str) {
+                if !map.contains_key(key) {
+                    errors.push(format!(
+                        "{}: missing required property '{}'",
+                        display_path, key
+                    ));
+                }
+            }
+        }
+
+        if let Some(properties) = properties {
+            for (key, sub_schema) in properties {
+                if let Some(sub_value) = map.get(key) {
+                    validate_at(sub_value, sub_schema, &format!("{}/{}", path, key), errors);
+                }
+            }
+        }
+
+        if matches!(schema.get("additionalProperties"), Some(Value::Bool(false))) {
+            for key in map.keys() {
+                if !properties.is_some_and(|p| p.contains_key(key)) {
+                    errors.push(format!("{}: unexpected property '{}'", display_path, key));
+                }
+            }
+        }
+    }
+
+    if let (Value::Array(items), Some(item_schema)) = (value, schema.get("items")) {
+        for (index, item) in items.iter().enumerate() {
+            validate_at(item, item_schema, &format!("{}/{}", path, index), errors);
+        }
+    }
+}
+
+/// MCPツールの入力スキーマを、Bedrockが受け付ける形に正規化する
+///
+/// 実際のMCPサーバーが出力するスキーマのうち、Bedrockに拒否される次のパターンを補正する：
+/// - ルートに `type` がない場合は `"object"` を補い、オブジェクト以外の型の場合は
+///   `input` プロパティに包んだオブジェクトのスキーマにする
+/// - `$schema` などの未対応キーワードと、真偽値以外の `additionalProperties` を取り除く
+/// - `$defs` / `definitions` を参照する `$ref` を展開し、定義自体は取り除く
+///   （循環参照や解決できない参照は、制約のないスキーマに置き換える）
+///
+/// # Arguments
+/// * `schema` - MCPツールの入力スキーマ
+///
+/// # Returns
+/// * 正規化したスキーマと、行った補正の説明（補正がなければ空）
+///
+/// # Note
+/// MCPのツール引数は常にオブジェクトのため、`input` に包んだスキーマのツールは
+/// モデルが生成した `{"input": ...}` をそのままサーバーに渡す。
+pub fn normalize_tool_schema(schema: &Map<String, Value>) -> (Map<String, Value>, Vec<String>) {
+    let mut coercions = Vec::new();
+    let mut root = schema.clone();
+
+    let mut definitions = Map::new();
+    for keyword in ["$defs", "definitions"] {
+        if let Some(Value::Object(defs)) = root.remove(keyword) {
+            coercions.push(format!(
+                "/: removed '{}' after inlining references",
+                keyword
+            ));
+            definitions.extend(defs);
+        }
+    }
+
+    let mut root_value = Value::Object(root);
+    normalize_at(
+        &mut root_value,
+        "",
+        &definitions,
+        &mut Vec::new(),
+        &mut coercions,
+    );
+    let Value::Object(mut root) = root_value else {
+        unreachable!("normalizing an object schema always yields an object");
+    };
+
+    match root.get("type") {
+        None => {
+            root.insert("type".to_string(), json!("object"));
+            coercions.push("/: added missing type 'object'".to_string());
+        }
+        Some(Value::String(type_name)) if type_name == "object" => {}
+        Some(other) => {
+            coercions.push(format!(
+                "/: wrapped non-object root (type {}) in property '{}'",
+                other, WRAPPED_INPUT_PROPERTY
+            ));
+            let Value::Object(wrapped) = json!({
+                "type": "object",
+                "properties": { WRAPPED_INPUT_PROPERTY: Value::Object(root) },
+                "required": [WRAPPED_INPUT_PROPERTY]
+            }) else {
+                unreachable!("json! object literal always yields an object");
+            };
+            root = wrapped;
+        }
+    }
+
+    (root, coercions)
+}
+
+/// 指定パスのスキーマを再帰的に正規化し、行った補正を `coercions` に追加する
+///
+/// `expanding` は展開中の `$ref` 定義名の一覧で、循環参照の検出に使う。
+fn normalize_at(
+    schema: &mut Value,
+    path: &str,
+    definitions: &Map<String, Value>,
+    expanding: &mut Vec<String>,
+    coercions: &mut Vec<String>,
+) {
+    let display_path = if path.is_empty() { "/" } else { path };
+    let Value::Object(obj) = schema else {
+        return;
+    };
+
+    if let Some(reference) = obj.remove("$ref") {
+        let reference = reference.as_str().unwrap_or_default().to_string();
+        let name = reference
+            .strip_prefix("#/$defs/")
+            .or_else(|| reference.strip_prefix("#/definitions/"));
+        match name.and_then(|name| definitions.get(name).map(|def| (name, def))) {
+            Some((name, _)) if expanding.iter().any(|expanded| expanded == name) => {
+                coercions.push(format!(
+                    "{}: replaced recursive $ref '{}' with an unconstrained schema",
+                    display_path, reference
+                ));
+            }
+            Some((name, definition)) => {
+                coercions.push(format!("{}: inlined $ref '{}'", display_path, reference));
+                // `$ref` と並ぶキーワード（`description` など）は展開した定義より優先する
+                let mut inlined = definition.clone();
+                if let Value::Object(inlined_obj) = &mut inlined {
+                    inlined_obj.extend(std::mem::take(obj));
+                }
+                expanding.push(name.to_string());
+                normalize_at(&mut inlined, path, definitions, expanding, coercions);
+                expanding.pop();
+                *schema = inlined;
+                return;
+            }
+            None => {
+                coercions.push(format!(
+                    "{}: replaced unresolvable $ref '{}' with an unconstrained schema",
+                    display_path, reference
+                ));
+            }
+        }
+    }
+
+    for keyword in UNSUPPORTED_TOOL_SCHEMA_KEYWORDS {
+        if obj.remove(*keyword).is_some() {
+            coercions.push(format!(
+                "{}: removed unsupported '{}'",
+                display_path, keyword
+            ));
+        }
+    }
+    if obj
+        .get("additionalProperties")
+        .is_some_and(|value| !value.is_boolean())
+    {
+        obj.remove("additionalProperties");
+        coercions.push(format!(
+            "{}: removed non-boolean 'additionalProperties'",
+            display_path
+        ));
+    }
+
+    // サブスキーマを持つキーワードを再帰的に正規化する
+    for keyword in ["properties", "patternProperties"] {
+        if let Some(Value::Object(children)) = obj.get_mut(keyword) {
+            for (name, child) in children.iter_mut() {
+                let child_path = format!("{}/{}/{}", path, keyword, name);
+                normalize_at(child, &child_path, definitions, expanding, coercions);
+            }
+        }
+    }
+    for keyword in ["items", "prefixItems", "anyOf", "oneOf", "allOf", "not"] {
+        match obj.get_mut(keyword) {
+            Some(Value::Array(children)) => {
+                for (index, child) in children.iter_mut().enumerate() {
+                    let child_path = format!("{}/{}/{}", path, keyword, index);
+                    normalize_at(child, &child_path, definitions, expanding, coercions);
+                }
+            }
+            Some(child) => {
+                let child_path = format!("{}/{}", path, keyword);
+                normalize_at(child, &child_path, definitions, expanding, coercions);
+            }
+            None => {}
+        }
+    }
+}
+
+/// JSON値がスキーマの型名に一致するかを判定する
+fn matches_type(value: &Value, type_name: &str) -> bool {
+    match type_name {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64(),
+        // 未知の型名は検証対象外とする
+        _ => true,
+    }
+}
+
+/// エラーメッセージ用にJSON値の型名を返す
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn person_schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "name": { "type": "string" },
+                "age": { "type": "integer" },
+                "tags": { "type": "array", "items": { "type": "string" } },
+                "role": { "enum": ["admin", "user"] }
+            },
+            "required": ["name", "age"],
+            "additionalProperties": false
+        })
+    }
+
+    #[test]
+    fn test_validate_accepts_matching_value() {
+        let value = json!({ "name": "Alice", "age": 30, "tags": ["a"], "role": "admin" });
+        assert!(validate(&value, &person_schema()).is_ok());
+    }
+
+    #[test]
+    fn test_validate_reports_all_violations() {
+        let value = json!({ "age": "thirty", "tags": [1], "role": "guest", "extra": true });
+        let errors = validate(&value, &person_schema()).unwrap_err();
+
+        assert!(
+            errors
+                .iter()
+                .any(|e| e.contains("missing required property 'name'"))
+        );
+        assert!(
+            errors
+                .iter()
+                .any(|e| e.starts_with("/age: expected type integer"))
+        );
+        assert!(
+            errors
+                .iter()
+                .any(|e| e.starts_with("/tags/0: expected type string"))
+        );
+        assert!(
+            errors
+                .iter()
+                .any(|e| e.starts_with("/role: value is not one of"))
+        );
+        assert!(
+            errors
+                .iter()
+                .any(|e| e.contains("unexpected property 'extra'"))
+        );
+    }
+
+    #[test]
+    fn test_normalize_tool_schema_keeps_valid_schema() {
+        let schema = person_schema().as_object().unwrap().clone();
+        let (normalized, coercions) = normalize_tool_schema(&schema);
+        assert_eq!(normalized, schema);
+        assert!(coercions.is_empty());
+    }
+
+    #[test]
+    fn test_normalize_tool_schema_fixes_root_type() {
+        let schema = json!({ "properties": { "path": { "type": "string" } } });
+        let (normalized, coercions) = normalize_tool_schema(schema.as_object().unwrap());
+        assert_eq!(normalized["type"], "object");
+        assert_eq!(coercions.len(), 1);
+
+        let schema = json!({ "type": "string", "description": "検索語" });
+        let (normalized, _) = normalize_tool_schema(schema.as_object().unwrap());
+        assert_eq!(
+            Value::Object(normalized),
+            json!({
+                "type": "object",
+                "properties": { "input": { "type": "string", "description": "検索語" } },
+                "required": ["input"]
+            })
+        );
+    }
+
+    #[test]
+    fn test_normalize_tool_schema_strips_and_inlines() {
+        let schema = json!({
+            "$schema": "http://json-schema.org/draft-07/schema#",
+            "type": "object",
+            "properties": {
+                "filter": { "$ref": "#/$defs/Filter", "description": "絞り込み条件" },
+                "node": { "$ref": "#/definitions/Node" },
+                "missing": { "$ref": "#/$defs/Missing" }
+            },
+            "additionalProperties": { "type": "string" },
+            "$defs": {
+                "Filter": { "type": "object", "properties": { "tag": { "type": "string" } } }
+            },
+            "definitions": {
+                "Node": { "type": "object", "properties": { "child": { "$ref": "#/definitions/Node" } } }
+            }
+        });
+        let (normalized, coercions) = normalize_tool_schema(schema.as_object().unwrap());
+
+        assert_eq!(
+            Value::Object(normalized),
+            json!({
+                "type": "object",
+                "properties": {
+                    "filter": {
+                        "type": "object",
+                        "properties": { "tag": { "type": "string" } },
+                        "description": "絞り込み条件"
+                    },
+                    "node": { "type": "object", "properties": { "child": {} } },
+                    "missing": {}
+                }
+            })
+        );
+        assert!(
+            coercions
+                .iter()
+                .any(|c| c.starts_with("/properties/node/properties/child: replaced recursive"))
+        );
+    }
+
+    #[test]
+    fn test_validate_root_type_mismatch() {
+        let errors = validate(&json!([1, 2]), &person_schema()).unwrap_err();
+        assert_eq!(
+            errors,
+            vec!["/: expected type object, got array".to_string()]
+        );
+    }
+}
@@ -0,0 +1,650 @@
+/// ストリーミングレスポンスの組み立て
+///
+/// `ConverseStream` のイベントを1つずつ受け取り、表示用のテキスト差分を返しつつ、
+/// 会話履歴に追加するためのコンテンツブロック（テキスト/ツール使用）を組み立てる。
+/// UI出力は行わないため、呼び出し側が任意の出力先に描画できる。
+use std::collections::BTreeMap;
+
+use aws_sdk_bedrockruntime::types::{
+    ContentBlock, ContentBlockDelta, ConverseStreamMetadataEvent, ConverseStreamOutput,
+    GuardrailTraceAssessment, StopReason as SdkStopReason, TokenUsage, ToolUseBlock,
+};
+
+use crate::agent::{AgentError, json_to_document};
+
+/// コンテンツブロック差分（`ContentBlockDelta`）の種類
+///
+/// SDK の列挙型は今後も値が追加されうるため、クレート内で扱う種類に分類してから処理する。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeltaKind<'a> {
+    /// 応答テキストの差分
+    Text(&'a str),
+    /// ツール使用の入力（JSON文字列）の差分
+    ToolUseInput(&'a str),
+    /// 推論（reasoning）内容の差分。署名や秘匿化された内容の場合、テキストは None
+    Reasoning(Option<&'a str>),
+    /// このクレートが認識しない種類の差分
+    Unknown,
+}
+
+impl<'a> DeltaKind<'a> {
+    /// `ContentBlockDelta` を分類する
+    pub fn classify(delta: &'a ContentBlockDelta) -> Self {
+        match delta {
+            ContentBlockDelta::Text(text) => Self::Text(text),
+            ContentBlockDelta::ToolUse(tool_use) => Self::ToolUseInput(tool_use.input()),
+            ContentBlockDelta::ReasoningContent(reasoning) => {
+                Self::Reasoning(reasoning.as_text().ok().map(String::as_str))
+            }
+            _ => Self::Unknown,
+        }
+    }
+}
+
+/// 応答が終了した理由（`MessageStop` イベントの `stopReason`）
+///
+/// SDK の列挙型は今後も値が追加されうるため、クレート内で扱う種類に分類してから処理する。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StopReason {
+    /// 応答が完了した
+    EndTurn,
+    /// モデルがツールの使用を要求した
+    ToolUse,
+    /// 最大トークン数に達し、応答が途中で打ち切られた
+    MaxTokens,
+    /// 停止シーケンスに達した
+    StopSequence,
+    /// ガードレールが介入した
+    GuardrailIntervened,
+    /// コンテンツフィルターにより打ち切られた
+    ContentFiltered,
+    /// このクレートが認識しない理由（Bedrockが返した値）
+    Other(String),
+}
+
+impl From<&SdkStopReason> for StopReason {
+    fn from(reason: &SdkStopReason) -> Self {
+        match reason {
+            SdkStopReason::EndTurn => Self::EndTurn,
+            SdkStopReason::ToolUse => Self::ToolUse,
+            SdkStopReason::MaxTokens => Self::MaxTokens,
+            SdkStopReason::StopSequence => Self::StopSequence,
+            SdkStopReason::GuardrailIntervened => Self::GuardrailIntervened,
+            SdkStopReason::ContentFiltered => Self::ContentFiltered,
+            other => Self::Other(other.as_str().to_string()),
+        }
+    }
+}
+
+/// ストリームの最後に届くメタデータ（`ConverseStreamOutput::Metadata`）
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StreamMetadata {
+    /// トークン使用量
+    pub usage: Option<TokenUsage>,
+    /// リクエストの処理にかかった時間（ミリ秒）
+    pub latency_ms: Option<i64>,
+    /// ガードレールの評価結果（ガードレールのトレースを有効にした場合のみ）
+    pub guardrail_trace: Option<GuardrailTraceAssessment>,
+}
+
+impl From<ConverseStreamMetadataEvent> for StreamMetadata {
+    fn from(event: ConverseStreamMetadataEvent) -> Self {
+        Self {
+            usage: event.usage().cloned(),
+            latency_ms: event.metrics().map(|metrics| metrics.latency_ms()),
+            guardrail_trace: event.trace().and_then(|trace| trace.guardrail()).cloned(),
+        }
+    }
+}
+
+/// ブロックインデックスごとのコンテンツブロックの受信状態
+#[derive(Debug)]
+enum BlockState {
+    /// 受信中または受信済みのテキスト
+    Text(String),
+    /// 受信中のツール使用
+    ToolUse {
+        tool_use_id: String,
+        name: String,
+        input: String,
+    },
+    /// 組み立て済みのブロック
+    Complete(ContentBlock),
+}
+
+/// 1ターン分のストリームイベントを蓄積する構造体
+///
+/// テキストとツール使用のブロックが並行して届く場合に差分を取り違えないよう、
+/// イベントの `content_block_index` ごとに個別に組み立て、インデックス順に並べて返す。
+#[derive(Debug, Default)]
+pub struct TurnAccumulator {
+    /// ブロックインデックスごとの受信状態
+    blocks: BTreeMap<i32, BlockState>,
+    /// ストリームのメタデータ（受信していない場合は None）
+    metadata: Option<StreamMetadata>,
+    /// 応答が終了した理由（`MessageStop` を受信していない場合は None）
+    stop_reason: Option<StopReason>,
+    /// 未知の差分について警告を出力済みか（1ターンにつき1回のみ出力する）
+    warned_unknown_delta: bool,
+    /// 受け付ける応答テキストの最大文字数（無制限の場合は None）
+    max_text_chars: Option<usize>,
+    /// これまでに受け付けた応答テキストの文字数
+    text_chars: usize,
+    /// 最大文字数に達し、以降のテキストを破棄しているか
+    truncated: bool,
+}
+
+impl TurnAccumulator {
+    /// 空の `TurnAccumulator` を作成する
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 受け付ける応答テキストの最大文字数を設定する
+    ///
+    /// 最大文字数を超えたテキスト差分は上限までで切り詰め、以降のテキストは破棄する。
+    /// モデル側のトークン数上限とは別に、同じ内容を繰り返し続けるような暴走した応答を
+    /// クライアント側で打ち切るために使用する。
+    ///
+    /// # Arguments
+    /// * `max_chars` - 最大文字数（Unicodeのスカラー値単位）
+    pub fn with_max_text_chars(mut self, max_chars: usize) -> Self {
+        self.max_text_chars = Some(max_chars);
+        self
+    }
+
+    /// 応答テキストが最大文字数に達し、切り詰められたかを確認する
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// ストリームイベントを1つ処理する
+    ///
+    /// # Arguments
+    /// * `event` - Bedrockから受信したストリームイベント
+    ///
+    /// # Returns
+    /// * `Ok(Some(String))` - 表示すべきテキスト差分
+    /// * `Ok(None)` - 表示すべきテキストがないイベント
+    /// * `Err(AgentError)` - ツール入力のパースなどに失敗した場合
+    pub fn push_event(
+        &mut self,
+        event: ConverseStreamOutput,
+    ) -> Result<Option<String>, AgentError> {
+        match event {
+            // コンテンツブロックの差分
+            ConverseStreamOutput::ContentBlockDelta(delta) => {
+                if let Some(delta_block) = &delta.delta {
+                    return Ok(self.push_delta(delta.content_block_index, delta_block));
+                }
+            }
+            // コンテンツブロック開始
+            ConverseStreamOutput::ContentBlockStart(start) => {
+                if let Some(start_block) = &start.start
+                    && let Ok(tool_use) = start_block.as_tool_use()
+                {
+                    // ツール使用開始
+                    self.blocks.insert(
+                        start.content_block_index,
+                        BlockState::ToolUse {
+                            tool_use_id: tool_use.tool_use_id().to_string(),
+                            name: tool_use.name().to_string(),
+                            input: String::new(),
+                        },
+                    );
+                }
+            }
+            // コンテンツブロック終了
+            ConverseStreamOutput::ContentBlockStop(stop) => {
+                self.finish_block(stop.content_block_index)?;
+            }
+            // メッセージ終了（応答が終了した理由）
+            ConverseStreamOutput::MessageStop(stop) => {
+                self.stop_reason = Some(StopReason::from(&stop.stop_reason));
+            }
+            // トークン使用量・レイテンシ・トレース
+            ConverseStreamOutput::Metadata(metadata) => {
+                self.metadata = Some(metadata.into());
+            }
+            _ => {}
+        }
+        Ok(None)
+    }
+
+    /// コンテンツブロックの差分を種類に応じて処理する
+    ///
+    /// # Arguments
+    /// * `index` - 差分が属するコンテンツブロックのインデックス
+    /// * `delta` - コンテンツブロックの差分
+    ///
+    /// # Returns
+    /// 表示すべきテキスト差分（テキスト以外の差分では None）
+    fn push_delta(&mut self, index: i32, delta: &ContentBlockDelta) -> Option<String> {
+        match DeltaKind::classify(delta) {
+            DeltaKind::Text(text) => {
+                let text = self.limit_text(text)?;
+                let state = self
+                    .blocks
+                    .entry(index)
+                    .or_insert_with(|| BlockState::Text(String::new()));
+                if let BlockState::Text(current) = state {
+                    current.push_str(text);
+                }
+                return Some(text.to_string());
+            }
+            DeltaKind::ToolUseInput(input_delta) => {
+                // ツール使用のinputが段階的に来る
+                if let Some(BlockState::ToolUse { input, .. }) = self.blocks.get_mut(&index) {
+                    input.push_str(input_delta);
+                }
+            }
+            DeltaKind::Reasoning(_) => {
+                // 推論内容は現時点では表示・履歴保存の対象外
+            }
+            DeltaKind::Unknown => {
+                if !self.warned_unknown_delta {
+                    tracing::warn!("Ignoring unsupported content block delta: {:?}", delta);
+                    self.warned_unknown_delta = true;
+                }
+            }
+        }
+        None
+    }
+
+    /// 最大文字数を超えないよう、受け付けるテキスト差分を切り詰める
+    ///
+    /// # Returns
+    /// 受け付けるテキスト（受け付けるテキストがない場合は None）
+    fn limit_text<'a>(&mut self, text: &'a str) -> Option<&'a str> {
+        if self.truncated {
+            return None;
+        }
+        let Some(max_chars) = self.max_text_chars else {
+            return Some(text);
+        };
+        let remaining = max_chars.saturating_sub(self.text_chars);
+        match text.char_indices().nth(remaining) {
+            Some((end, _)) => {
+                self.truncated = true;
+                self.text_chars = max_chars;
+                (end > 0).then(|| &text[..end])
+            }
+            None => {
+                self.text_chars += text.chars().count();
+                Some(text)
+            }
+        }
+    }
+
+    /// これまでに受信したテキストをブロックの順に連結して返す
+    pub fn text(&self) -> String {
+        self.blocks
+            .values()
+            .filter_map(|state| match state {
+                BlockState::Text(text) => Some(text.as_str()),
+                BlockState::Complete(block) => block.as_text().ok().map(String::as_str),
+                BlockState::ToolUse { .. } => None,
+            })
+            .collect()
+    }
+
+    /// 受信したストリームのメタデータを取得する
+    ///
+    /// メタデータはストリームの最後に届くため、ストリームの受信が完了するまでは None。
+    pub fn metadata(&self) -> Option<&StreamMetadata> {
+        self.metadata.as_ref()
+    }
+
+    /// 応答が終了した理由を取得する
+    ///
+    /// `MessageStop` イベントを受信するまで（最大文字数で受信を打ち切った場合を含む）は None。
+    pub fn stop_reason(&self) -> Option<&StopReason> {
+        self.stop_reason.as_ref()
+    }
+
+    /// 蓄積を終了し、会話履歴に追加するコンテンツブロックをインデックス順に返す
+    ///
+    /// 終了イベントを受信していないテキストもブロックとして確定する。
+    /// 入力が揃っていない（終了していない）ツール使用は含めない。
+    pub fn finish(self) -> Vec<ContentBlock> {
+        self.blocks
+            .into_values()
+            .filter_map(|state| match state {
+                BlockState::Text(text) if !text.is_empty() => Some(ContentBlock::Text(text)),
+                BlockState::Complete(block) => Some(block),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// 指定したインデックスのブロックを確定する
+    fn finish_block(&mut self, index: i32) -> Result<(), AgentError> {
+        let Some(state) = self.blocks.remove(&index) else {
+            return Ok(());
+        };
+        let block = match state {
+            BlockState::Text(text) if text.is_empty() => return Ok(()),
+            BlockState::Text(text) => ContentBlock::Text(text),
+            BlockState::ToolUse {
+                tool_use_id,
+                name,
+                input,
+            } => build_tool_use_block(tool_use_id, name, &input)?,
+            BlockState::Complete(block) => block,
+        };
+        self.blocks.insert(index, BlockState::Complete(block));
+        Ok(())
+    }
+}
+
+/// 受信したツール使用情報から `ToolUseBlock` を構築する
+///
+/// # Note
+/// 引数のないツールでは、モデルが空（または空白のみ）のinputを返すことがあるため、
+/// 空のJSONオブジェクトとして扱う。必須の引数が欠けているかどうかはツール実行時に検証する。
+fn build_tool_use_block(
+    tool_use_id: String,
+    name: String,
+    input: &str,
+) -> Result<ContentBlock, AgentError> {
+    // JSON形式のinputをパース
+    let input_json: serde_json::Value = if input.trim().is_empty() {
+        serde_json::Value::Object(serde_json::Map::new())
+    } else {
+        serde_json::from_str(input).map_err(|e| {
+            AgentError::MessageBuildError(format!("Failed to parse tool use input as JSON: {}", e))
+        })?
+    };
+
+    let tool_use_block = ToolUseBlock::builder()
+        .tool_use_id(tool_use_id)
+        .name(name)
+        .input(json_to_document(input_json)?)
+        .build()
+        .map_err(|e| {
+            AgentError::MessageBuildError(format!("Failed to build ToolUseBlock: {}", e))
+        })?;
+
+    Ok(ContentBlock::ToolUse(tool_use_block))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aws_sdk_bedrockruntime::types::{
+        ContentBlockDeltaEvent, ContentBlockStart, ContentBlockStartEvent, ContentBlockStopEvent,
+        ConverseStreamMetrics, MessageStopEvent, ReasoningContentBlockDelta, ToolUseBlockDelta,
+        ToolUseBlockStart,
+    };
+
+    fn text_delta(index: i32, text: &str) -> ConverseStreamOutput {
+        ConverseStreamOutput::ContentBlockDelta(
+            ContentBlockDeltaEvent::builder()
+                .content_block_index(index)
+                .delta(ContentBlockDelta::Text(text.to_string()))
+                .build()
+                .unwrap(),
+        )
+    }
+
+    fn block_stop(index: i32) -> ConverseStreamOutput {
+        ConverseStreamOutput::ContentBlockStop(
+            ContentBlockStopEvent::builder()
+                .content_block_index(index)
+                .build()
+                .unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_text_deltas_are_returned_and_accumulated() {
+        let mut acc = TurnAccumulator::new();
+        let mut streamed = String::new();
+
+        for event in [
+            text_delta(0, "Hello, "),
+            text_delta(0, "world"),
+            block_stop(0),
+        ] {
+            if let Some(text) = acc.push_event(event).unwrap() {
+                streamed.push_str(&text);
+            }
+        }
+
+        assert_eq!(streamed, "Hello, world");
+        assert_eq!(acc.text(), "Hello, world");
+        let blocks = acc.finish();
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].as_text().unwrap(), "Hello, world");
+    }
+
+    #[test]
+    fn test_text_is_truncated_at_max_chars() {
+        let mut acc = TurnAccumulator::new().with_max_text_chars(6);
+        let mut streamed = String::new();
+
+        for event in [
+            text_delta(0, "繰り返し"),
+            text_delta(0, "繰り返し"),
+            text_delta(0, "繰り返し"),
+            block_stop(0),
+        ] {
+            if let Some(text) = acc.push_event(event).unwrap() {
+                streamed.push_str(&text);
+            }
+            if acc.is_truncated() {
+                break;
+            }
+        }
+
+        assert!(acc.is_truncated());
+        assert_eq!(streamed, "繰り返し繰り");
+        // 終了イベントを受信する前に打ち切っても、途中までのテキストは履歴用に確定する
+        let blocks = acc.finish();
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].as_text().unwrap(), "繰り返し繰り");
+    }
+
+    #[test]
+    fn test_reasoning_deltas_are_classified_and_not_streamed() {
+        let reasoning = ContentBlockDelta::ReasoningContent(ReasoningContentBlockDelta::Text(
+            "考え中".to_string(),
+        ));
+        assert_eq!(
+            DeltaKind::classify(&reasoning),
+            DeltaKind::Reasoning(Some("考え中"))
+        );
+
+        let mut acc = TurnAccumulator::new();
+        for delta in [
+            reasoning,
+            ContentBlockDelta::ReasoningContent(ReasoningContentBlockDelta::Signature(
+                "sig".to_string(),
+            )),
+        ] {
+            let event = ConverseStreamOutput::ContentBlockDelta(
+                ContentBlockDeltaEvent::builder()
+                    .content_block_index(0)
+                    .delta(delta)
+                    .build()
+                    .unwrap(),
+            );
+            assert!(acc.push_event(event).unwrap().is_none());
+        }
+        assert!(acc.finish().is_empty());
+    }
+
+    fn tool_use_start(index: i32, tool_use_id: &str, name: &str) -> ConverseStreamOutput {
+        ConverseStreamOutput::ContentBlockStart(
+            ContentBlockStartEvent::builder()
+                .content_block_index(index)
+                .start(ContentBlockStart::ToolUse(
+                    ToolUseBlockStart::builder()
+                        .tool_use_id(tool_use_id)
+                        .name(name)
+                        .build()
+                        .unwrap(),
+                ))
+                .build()
+                .unwrap(),
+        )
+    }
+
+    fn tool_input_delta(index: i32, input: &str) -> ConverseStreamOutput {
+        ConverseStreamOutput::ContentBlockDelta(
+            ContentBlockDeltaEvent::builder()
+                .content_block_index(index)
+                .delta(ContentBlockDelta::ToolUse(
+                    ToolUseBlockDelta::builder().input(input).build().unwrap(),
+                ))
+                .build()
+                .unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_tool_use_block_is_assembled() {
+        let mut acc = TurnAccumulator::new();
+        let input_delta = |input: &str| tool_input_delta(1, input);
+
+        for event in [
+            tool_use_start(1, "tool-1", "git_status"),
+            input_delta("{\"repo_path\":"),
+            input_delta("\".\"}"),
+            block_stop(1),
+        ] {
+            assert!(acc.push_event(event).unwrap().is_none());
+        }
+
+        let blocks = acc.finish();
+        let tool_use = blocks[0].as_tool_use().unwrap();
+        assert_eq!(tool_use.tool_use_id(), "tool-1");
+        assert_eq!(tool_use.name(), "git_status");
+        assert_eq!(
+            crate::agent::document_to_json(tool_use.input().clone()).unwrap(),
+            serde_json::json!({ "repo_path": "." })
+        );
+    }
+
+    #[test]
+    fn test_empty_tool_input_is_treated_as_empty_object() {
+        let mut acc = TurnAccumulator::new();
+        for event in [
+            tool_use_start(0, "tool-1", "get_current_time"),
+            tool_input_delta(0, "  "),
+            block_stop(0),
+            tool_use_start(1, "tool-2", "get_current_time"),
+            block_stop(1),
+        ] {
+            assert!(acc.push_event(event).unwrap().is_none());
+        }
+
+        let blocks = acc.finish();
+        assert_eq!(blocks.len(), 2);
+        for block in &blocks {
+            let tool_use = block.as_tool_use().unwrap();
+            assert_eq!(
+                crate::agent::document_to_json(tool_use.input().clone()).unwrap(),
+                serde_json::json!({})
+            );
+        }
+    }
+
+    #[test]
+    fn test_interleaved_blocks_are_assembled_by_index() {
+        let mut acc = TurnAccumulator::new();
+        let mut streamed = String::new();
+
+        // テキスト（0）の受信中にツール使用（1）が始まり、差分が交互に届く
+        for event in [
+            text_delta(0, "確認"),
+            tool_use_start(1, "tool-1", "git_status"),
+            tool_input_delta(1, "{\"repo_path\":"),
+            text_delta(0, "します。"),
+            tool_input_delta(1, "\".\"}"),
+            block_stop(1),
+            block_stop(0),
+        ] {
+            if let Some(text) = acc.push_event(event).unwrap() {
+                streamed.push_str(&text);
+            }
+        }
+
+        assert_eq!(streamed, "確認します。");
+        assert_eq!(acc.text(), "確認します。");
+        let blocks = acc.finish();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].as_text().unwrap(), "確認します。");
+        let tool_use = blocks[1].as_tool_use().unwrap();
+        assert_eq!(tool_use.tool_use_id(), "tool-1");
+        assert_eq!(
+            crate::agent::document_to_json(tool_use.input().clone()).unwrap(),
+            serde_json::json!({ "repo_path": "." })
+        );
+    }
+
+    #[test]
+    fn test_metadata_event_is_recorded() {
+        let mut acc = TurnAccumulator::new();
+        assert!(acc.metadata().is_none());
+
+        let usage = TokenUsage::builder()
+            .input_tokens(12)
+            .output_tokens(34)
+            .total_tokens(46)
+            .build()
+            .unwrap();
+        let event = ConverseStreamOutput::Metadata(
+            ConverseStreamMetadataEvent::builder()
+                .usage(usage.clone())
+                .metrics(
+                    ConverseStreamMetrics::builder()
+                        .latency_ms(850)
+                        .build()
+                        .unwrap(),
+                )
+                .build(),
+        );
+        for event in [text_delta(0, "OK"), block_stop(0), event] {
+            acc.push_event(event).unwrap();
+        }
+
+        let metadata = acc.metadata().unwrap();
+        assert_eq!(metadata.usage.as_ref(), Some(&usage));
+        assert_eq!(metadata.latency_ms, Some(850));
+        assert!(metadata.guardrail_trace.is_none());
+        assert_eq!(acc.finish().len(), 1);
+    }
+
+    #[test]
+    fn test_stop_reason_is_recorded() {
+        let message_stop = |reason: &str| {
+            ConverseStreamOutput::MessageStop(
+                MessageStopEvent::builder()
+                    .stop_reason(SdkStopReason::from(reason))
+                    .build()
+                    .unwrap(),
+            )
+        };
+
+        let mut acc = TurnAccumulator::new();
+        for event in [text_delta(0, "途中"), block_stop(0)] {
+            acc.push_event(event).unwrap();
+        }
+        assert!(acc.stop_reason().is_none());
+        acc.push_event(message_stop("max_tokens")).unwrap();
+        assert_eq!(acc.stop_reason(), Some(&StopReason::MaxTokens));
+
+        let mut acc = TurnAccumulator::new();
+        acc.push_event(message_stop("tool_use")).unwrap();
+        assert_eq!(acc.stop_reason(), Some(&StopReason::ToolUse));
+        acc.push_event(message_stop("model_context_window_exceeded"))
+            .unwrap();
+        assert_eq!(
+            acc.stop_reason(),
+            Some(&StopReason::Other(
+                "model_context_window_exceeded".to_string()
+            ))
+        );
+    }
+}
@@ -0,0 +1,297 @@
+/// MCPツール実行結果のキャッシュ
+///
+/// 同一セッション内でモデルが同じツールを同じ引数で繰り返し呼び出した場合に、
+/// 前回の結果を再利用して再実行を避ける。書き込み系のツールを誤って抑止しないよう、
+/// 読み取り系と判断できるツール、またはユーザーが明示したツールのみをキャッシュ対象とする。
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+
+/// デフォルトのキャッシュ有効期間
+pub const DEFAULT_TOOL_CACHE_TTL: Duration = Duration::from_secs(300);
+
+/// デフォルトの最大エントリ数
+pub const DEFAULT_TOOL_CACHE_MAX_ENTRIES: usize = 128;
+
+/// 読み取り系とみなすツール名の接頭辞
+const READ_LIKE_PREFIXES: &[&str] = &[
+    "get", "list", "read", "search", "find", "show", "describe", "fetch", "query", "view",
+];
+
+/// 読み取り系とみなすツール名（接頭辞を除いた語）
+const READ_LIKE_WORDS: &[&str] = &["status", "diff", "log"];
+
+/// ツール結果キャッシュの設定
+#[derive(Debug, Clone)]
+pub struct ToolCacheConfig {
+    /// エントリの有効期間
+    pub ttl: Duration,
+    /// 保持する最大エントリ数（超過時は古いものから破棄）
+    pub max_entries: usize,
+    /// 明示的にキャッシュ対象とするツール名
+    pub cacheable_tools: HashSet<String>,
+    /// ツール名から読み取り系と判断できるツールもキャッシュ対象とするか
+    pub cache_read_like_tools: bool,
+}
+
+impl Default for ToolCacheConfig {
+    fn default() -> Self {
+        Self {
+            ttl: DEFAULT_TOOL_CACHE_TTL,
+            max_entries: DEFAULT_TOOL_CACHE_MAX_ENTRIES,
+            cacheable_tools: HashSet::new(),
+            cache_read_like_tools: true,
+        }
+    }
+}
+
+/// キャッシュを考慮したツール実行の結果
+#[derive(Debug, Clone)]
+pub struct ToolCallOutcome {
+    /// ツールの実行結果
+    pub result: Value,
+    /// キャッシュから取得した結果かどうか
+    pub from_cache: bool,
+}
+
+/// キャッシュのキー（ツール名, 正規化した引数）
+type CacheKey = (String, String);
+
+/// キャッシュエントリ
+#[derive(Debug)]
+struct CacheEntry {
+    result: Value,
+    stored_at: Instant,
+}
+
+/// ツール実行結果のキャッシュ
+#[derive(Debug)]
+pub struct ToolCache {
+    config: ToolCacheConfig,
+    entries: HashMap<CacheKey, CacheEntry>,
+    /// 挿入順（古い順）のキー。最大エントリ数を超えた場合の破棄に使用する
+    order: VecDeque<CacheKey>,
+}
+
+impl ToolCache {
+    /// 指定した設定でキャッシュを作成する
+    pub fn new(config: ToolCacheConfig) -> Self {
+        Self {
+            config,
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    /// キャッシュの設定を取得する
+    pub fn config(&self) -> &ToolCacheConfig {
+        &self.config
+    }
+
+    /// 保持しているエントリ数を取得する
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// エントリが空かを確認する
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// ツールがキャッシュ対象かを判定する
+    ///
+    /// # Note
+    /// 明示的に指定されたツールに加え、`cache_read_like_tools` が有効な場合は
+    /// ツール名が読み取り系（`get_`/`list_`/`read_` などで始まる、
+    /// または `status`/`diff`/`log` を含む）のものを対象とする。
+    pub fn is_cacheable(&self, tool_name: &str) -> bool {
+        self.config.cacheable_tools.contains(tool_name)
+            || (self.config.cache_read_like_tools && is_read_like_tool(tool_name))
+    }
+
+    /// キャッシュされた結果を取得する
+    ///
+    /// 有効期間を過ぎたエントリは破棄し、`None` を返す。
+    pub fn get(&mut self, tool_name: &str, arguments: Option<&Value>) -> Option<Value> {
+        if !self.is_cacheable(tool_name) {
+            return None;
+        }
+
+        let key = cache_key(tool_name, arguments);
+        let expired = self
+            .entries
+            .get(&key)
+            .map(|entry| entry.stored_at.elapsed() >= self.config.ttl)?;
+
+        if expired {
+            self.remove(&key);
+            None
+        } else {
+            self.entries.get(&key).map(|entry| entry.result.clone())
+        }
+    }
+
+    /// ツールの実行結果を保存する
+    ///
+    /// キャッシュ対象外のツールは保存しない。
+    pub fn insert(&mut self, tool_name: &str, arguments: Option<&Value>, result: Value) {
+        if !self.is_cacheable(tool_name) || self.config.max_entries == 0 {
+            return;
+        }
+
+        let key = cache_key(tool_name, arguments);
+        self.remove(&key);
+
+        while self.entries.len() >= self.config.max_entries {
+            match self.order.pop_front() {
+                Some(oldest) => {
+                    self.entries.remove(&oldest);
+                }
+                None => break,
+            }
+        }
+
+        self.order.push_back(key.clone());
+        self.entries.insert(
+            key,
+            CacheEntry {
+                result,
+                stored_at: Instant::now(),
+            },
+        );
+    }
+
+    /// すべてのエントリを破棄する
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+
+    /// 指定したキーのエントリを破棄する
+    fn remove(&mut self, key: &CacheKey) {
+        if self.entries.remove(key).is_some() {
+            self.order.retain(|k| k != key);
+        }
+    }
+}
+
+/// ツール名から読み取り系のツールかを判定する
+pub(crate) fn is_read_like_tool(tool_name: &str) -> bool {
+    let name = tool_name.to_ascii_lowercase();
+    let words: Vec<&str> = name
+        .split(['_', '-', '.'])
+        .filter(|w| !w.is_empty())
+        .collect();
+
+    words
+        .first()
+        .is_some_and(|first| READ_LIKE_PREFIXES.contains(first))
+        || words.iter().any(|w| READ_LIKE_WORDS.contains(w))
+}
+
+/// キャッシュのキーを作成する
+fn cache_key(tool_name: &str, arguments: Option<&Value>) -> CacheKey {
+    let canonical = arguments.map(canonicalize).unwrap_or_default();
+    (tool_name.to_string(), canonical)
+}
+
+/// オブジェクトのキー順序に依存しない形でJSON値を文字列化する
+fn canonicalize(value: &Value) -> String {
+    match value {
+        Value::Object(map) => {
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+            let fields: Vec<String> = keys
+                .into_iter()
+                .map(|key| format!("{}:{}", Value::String(key.clone()), canonicalize(&map[key])))
+                .collect();
+            format!("{{{}}}", fields.join(","))
+        }
+        Value::Array(items) => {
+            let items: Vec<String> = items.iter().map(canonicalize).collect();
+            format!("[{}]", items.join(","))
+        }
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_cache_hit_ignores_argument_order() {
+        let mut cache = ToolCache::new(ToolCacheConfig::default());
+        let args = json!({ "repo_path": ".", "max_count": 5 });
+        cache.insert("git_log", Some(&args), json!("log output"));
+
+        let reordered = json!({ "max_count": 5, "repo_path": "." });
+        assert_eq!(
+            cache.get("git_log", Some(&reordered)),
+            Some(json!("log output"))
+        );
+        assert_eq!(
+            cache.get("git_log", Some(&json!({ "repo_path": "other" }))),
+            None
+        );
+    }
+
+    #[test]
+    fn test_write_like_tools_are_not_cached_by_default() {
+        let mut cache = ToolCache::new(ToolCacheConfig::default());
+        let args = json!({ "message": "fix" });
+        cache.insert("git_commit", Some(&args), json!("committed"));
+        assert_eq!(cache.get("git_commit", Some(&args)), None);
+        assert!(cache.is_empty());
+
+        let mut config = ToolCacheConfig::default();
+        config.cacheable_tools.insert("git_commit".to_string());
+        let mut cache = ToolCache::new(config);
+        cache.insert("git_commit", Some(&args), json!("committed"));
+        assert_eq!(
+            cache.get("git_commit", Some(&args)),
+            Some(json!("committed"))
+        );
+    }
+
+    #[test]
+    fn test_expired_entries_are_discarded() {
+        let mut cache = ToolCache::new(ToolCacheConfig {
+            ttl: Duration::ZERO,
+            ..ToolCacheConfig::default()
+        });
+        cache.insert("read_file", None, json!("content"));
+        assert_eq!(cache.get("read_file", None), None);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_oldest_entry_is_evicted_when_full() {
+        let mut cache = ToolCache::new(ToolCacheConfig {
+            max_entries: 2,
+            ..ToolCacheConfig::default()
+        });
+        for path in ["a", "b", "c"] {
+            cache.insert("read_file", Some(&json!({ "path": path })), json!(path));
+        }
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get("read_file", Some(&json!({ "path": "a" }))), None);
+        assert_eq!(
+            cache.get("read_file", Some(&json!({ "path": "c" }))),
+            Some(json!("c"))
+        );
+    }
+
+    #[test]
+    fn test_is_read_like_tool() {
+        assert!(is_read_like_tool("git_status"));
+        assert!(is_read_like_tool("list_directory"));
+        assert!(is_read_like_tool("read-file"));
+        assert!(!is_read_like_tool("git_commit"));
+        assert!(!is_read_like_tool("write_file"));
+        assert!(!is_read_like_tool("delete_branch"));
+    }
+}
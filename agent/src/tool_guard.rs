@@ -0,0 +1,380 @@
+/// 危険なツール呼び出しの自動拒否（ツールガード）
+///
+/// 設定した拒否ルールに一致するツール呼び出しを、ユーザーに確認することなく拒否する。
+/// ツール名のパターンによる拒否に加え、許可したルートディレクトリの外を指すパスを
+/// 書き込み系のツールに渡す呼び出しも拒否できる。サンドボックスを有効にすると、
+/// 読み取り系を含むすべてのツールのパス引数をルートディレクトリ基準で解決し、
+/// ルートの外を指す呼び出しを拒否する。
+use std::path::{Component, Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::agent::AgentError;
+use crate::tool_cache::is_read_like_tool;
+
+/// セーフモードで拒否するツール名のパターン
+const SAFE_MODE_DENY_TOOLS: &[&str] = &[
+    "*delete*", "*remove*", "*exec*", "*shell*", "*kill*", "*drop*",
+];
+
+/// ファイルパスとみなす引数名
+const PATH_ARGUMENT_NAMES: &[&str] = &[
+    "path",
+    "file",
+    "file_path",
+    "filepath",
+    "filename",
+    "dir",
+    "directory",
+    "source",
+    "destination",
+    "target",
+    "repo_path",
+    "paths",
+];
+
+/// 作業ディレクトリとみなす引数名
+const WORKING_DIRECTORY_ARGUMENT_NAMES: &[&str] =
+    &["cwd", "workdir", "working_dir", "working_directory"];
+
+/// ツールガードの設定
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolGuardConfig {
+    /// 実行を拒否するツール名のパターン（`*` は任意の文字列に一致し、大文字小文字は区別しない）
+    #[serde(default)]
+    pub deny_tools: Vec<String>,
+
+    /// 書き込み系のツールに渡せるパスのルートディレクトリ（None の場合は制限しない）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_root: Option<PathBuf>,
+
+    /// すべてのツールのパス引数をルートディレクトリ内に制限する（`allowed_root` が必要）
+    #[serde(default)]
+    pub sandbox: bool,
+}
+
+impl ToolGuardConfig {
+    /// JSON形式の設定ファイルを読み込む
+    ///
+    /// # Errors
+    /// ファイルの読み込みやパースに失敗した場合
+    pub fn load_from_file(path: impl AsRef<Path>) -> Result<Self, AgentError> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            AgentError::ConfigError(format!(
+                "Failed to read tool guard config {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        serde_json::from_str(&content).map_err(|e| {
+            AgentError::ConfigError(format!(
+                "Failed to parse tool guard config {}: {}",
+                path.display(),
+                e
+            ))
+        })
+    }
+
+    /// セーフモードの拒否ルールを追加する
+    ///
+    /// 削除・コマンド実行などのツールを拒否し、ルートディレクトリが未設定の場合は
+    /// `root` の外への書き込みを拒否する。
+    pub fn apply_safe_mode(&mut self, root: PathBuf) {
+        for pattern in SAFE_MODE_DENY_TOOLS {
+            if !self.deny_tools.iter().any(|p| p == pattern) {
+                self.deny_tools.push(pattern.to_string());
+            }
+        }
+        if self.allowed_root.is_none() {
+            self.allowed_root = Some(root);
+        }
+    }
+
+    /// サンドボックスを有効にする
+    ///
+    /// ルートディレクトリが未設定の場合は `root` をルートとする。
+    pub fn apply_sandbox(&mut self, root: PathBuf) {
+        self.sandbox = true;
+        if self.allowed_root.is_none() {
+            self.allowed_root = Some(root);
+        }
+    }
+}
+
+/// 拒否ルールに従ってツール呼び出しを検査する
+#[derive(Debug, Clone)]
+pub struct ToolGuard {
+    config: ToolGuardConfig,
+}
+
+impl ToolGuard {
+    /// 指定した設定でツールガードを作成する
+    pub fn new(config: ToolGuardConfig) -> Self {
+        Self { config }
+    }
+
+    /// ツールガードの設定を取得する
+    pub fn config(&self) -> &ToolGuardConfig {
+        &self.config
+    }
+
+    /// ツール呼び出しを検査する
+    ///
+    /// # Returns
+    /// * `Ok(())` - 実行を許可する場合
+    /// * `Err(String)` - 拒否する場合（拒否の理由）
+    pub fn check(
+        &self,
+        tool_name: &str,
+        arguments: Option<&Map<String, Value>>,
+    ) -> Result<(), String> {
+        if let Some(pattern) = self
+            .config
+            .deny_tools
+            .iter()
+            .find(|pattern| matches_pattern(pattern, tool_name))
+        {
+            return Err(format!("tool name matches deny rule '{}'", pattern));
+        }
+
+        let (Some(root), Some(arguments)) = (&self.config.allowed_root, arguments) else {
+            return Ok(());
+        };
+        if is_read_like_tool(tool_name) {
+            return Ok(());
+        }
+
+        let base = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("/"));
+        let root = normalize_path(root, &base);
+        for (key, value) in arguments {
+            if !PATH_ARGUMENT_NAMES.contains(&key.to_ascii_lowercase().as_str()) {
+                continue;
+            }
+            let Some(path) = value.as_str() else {
+                continue;
+            };
+            if !normalize_path(Path::new(path), &base).starts_with(&root) {
+                return Err(format!(
+                    "argument '{}' ({}) is outside the allowed root {}",
+                    key,
+                    path,
+                    root.display()
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// サンドボックスが有効な場合に、ツールの引数をルートディレクトリ内に制限する
+    ///
+    /// パス引数（作業ディレクトリを含む）の相対パスはプロセスのカレントディレクトリではなく
+    /// ルートディレクトリを基準に解決し、正規化した絶対パスに書き換える。
+    /// ツールのスキーマが作業ディレクトリの引数を受け付け、呼び出しで指定されていない場合は
+    /// ルートディレクトリを指定する。
+    ///
+    /// # Arguments
+    /// * `arguments` - ツールに渡す引数（書き換える）
+    /// * `input_schema` - ツールの入力スキーマ
+    ///
+    /// # Returns
+    /// * `Ok(())` - 実行を許可する場合（サンドボックスが無効な場合も含む）
+    /// * `Err(String)` - ルートの外を指すパスがあり拒否する場合（拒否の理由）
+    pub fn sandbox_arguments(
+        &self,
+        arguments: &mut Map<String, Value>,
+        input_schema: &Map<String, Value>,
+    ) -> Result<(), String> {
+        let (true, Some(root)) = (self.config.sandbox, &self.config.allowed_root) else {
+            return Ok(());
+        };
+        let base = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("/"));
+        let root = normalize_path(root, &base);
+
+        for (key, value) in arguments.iter_mut() {
+            let name = key.to_ascii_lowercase();
+            if !PATH_ARGUMENT_NAMES.contains(&name.as_str())
+                && !WORKING_DIRECTORY_ARGUMENT_NAMES.contains(&name.as_str())
+            {
+                continue;
+            }
+            let paths: Vec<&mut Value> = match value {
+                Value::Array(items) => items.iter_mut().collect(),
+                other => vec![other],
+            };
+            for path_value in paths {
+                let Some(path) = path_value.as_str() else {
+                    continue;
+                };
+                let clamped = normalize_path(Path::new(path), &root);
+                if !clamped.starts_with(&root) {
+                    return Err(format!(
+                        "argument '{}' ({}) escapes the sandbox root {}",
+                        key,
+                        path,
+                        root.display()
+                    ));
+                }
+                *path_value = Value::String(clamped.to_string_lossy().into_owned());
+            }
+        }
+
+        // スキーマが受け付ける作業ディレクトリの引数が未指定であれば、ルートを指定する
+        let properties = input_schema.get("properties").and_then(Value::as_object);
+        if let Some(name) = properties.and_then(|properties| {
+            WORKING_DIRECTORY_ARGUMENT_NAMES
+                .iter()
+                .find(|name| properties.contains_key(**name))
+        }) && !arguments.contains_key(*name)
+        {
+            arguments.insert(
+                name.to_string(),
+                Value::String(root.to_string_lossy().into_owned()),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// `*` をワイルドカードとしてツール名がパターンに一致するかを判定する（大文字小文字を区別しない）
+fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.to_ascii_lowercase();
+    let name = name.to_ascii_lowercase();
+    let parts: Vec<&str> = pattern.split('*').collect();
+    let (first, rest) = parts.split_first().expect("split yields at least one part");
+    let Some(mut remaining) = name.strip_prefix(first) else {
+        return false;
+    };
+
+    let Some((last, middle)) = rest.split_last() else {
+        // ワイルドカードを含まない場合は完全一致
+        return remaining.is_empty();
+    };
+    for part in middle {
+        match remaining.find(part) {
+            Some(index) => remaining = &remaining[index + part.len()..],
+            None => return false,
+        }
+    }
+    remaining.ends_with(last)
+}
+
+/// ファイルシステムにアクセスせずに `.` や `..` を解決した絶対パスを作成する
+///
+/// # Arguments
+/// * `path` - 対象のパス
+/// * `base` - 相対パスの基準ディレクトリ
+fn normalize_path(path: &Path, base: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    let joined = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        base.join(path)
+    };
+    for component in joined.components() {
+        match component {
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::CurDir => {}
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_matches_pattern() {
+        assert!(matches_pattern("*delete*", "git_delete_branch"));
+        assert!(matches_pattern("*delete*", "DELETE"));
+        assert!(matches_pattern("git_*", "git_status"));
+        assert!(matches_pattern("*_exec", "shell_exec"));
+        assert!(matches_pattern("run_command", "run_command"));
+        assert!(!matches_pattern("run_command", "run_commands"));
+        assert!(!matches_pattern("*exec*", "git_status"));
+    }
+
+    #[test]
+    fn test_deny_rules_block_matching_tools() {
+        let mut config = ToolGuardConfig::default();
+        config.apply_safe_mode(PathBuf::from("/work"));
+        let guard = ToolGuard::new(config);
+
+        assert!(guard.check("git_delete_branch", None).is_err());
+        assert!(guard.check("shell_exec", None).is_err());
+        assert!(guard.check("git_status", None).is_ok());
+    }
+
+    #[test]
+    fn test_writes_outside_root_are_blocked() {
+        let guard = ToolGuard::new(ToolGuardConfig {
+            deny_tools: Vec::new(),
+            allowed_root: Some(PathBuf::from("/work/project")),
+            sandbox: false,
+        });
+        let args = |path: &str| json!({ "path": path, "content": "x" });
+
+        let inside = args("/work/project/src/main.rs");
+        assert!(guard.check("write_file", inside.as_object()).is_ok());
+
+        let escaped = args("/work/project/../secret.txt");
+        assert!(guard.check("write_file", escaped.as_object()).is_err());
+
+        // 読み取り系のツールはルート外でも許可する
+        let outside = args("/etc/hosts");
+        assert!(guard.check("read_file", outside.as_object()).is_ok());
+        assert!(guard.check("write_file", outside.as_object()).is_err());
+    }
+
+    #[test]
+    fn test_sandbox_arguments_clamps_paths_to_root() {
+        let mut config = ToolGuardConfig::default();
+        config.apply_sandbox(PathBuf::from("/work/project"));
+        let guard = ToolGuard::new(config);
+        let schema = json!({
+            "type": "object",
+            "properties": { "path": { "type": "string" }, "cwd": { "type": "string" } }
+        });
+        let schema = schema.as_object().unwrap();
+
+        // 相対パスはルート基準で解決し、作業ディレクトリにはルートを指定する
+        let mut args = json!({ "path": "src/../README.md", "paths": ["a.txt", "/work/project/b"] })
+            .as_object()
+            .unwrap()
+            .clone();
+        assert!(guard.sandbox_arguments(&mut args, schema).is_ok());
+        assert_eq!(args["path"], "/work/project/README.md");
+        assert_eq!(
+            args["paths"],
+            json!(["/work/project/a.txt", "/work/project/b"])
+        );
+        assert_eq!(args["cwd"], "/work/project");
+
+        // ルートの外を指すパスは、読み取り系のツールでも拒否する
+        for escaped in ["../secret.txt", "/etc/hosts"] {
+            let mut args = json!({ "path": escaped }).as_object().unwrap().clone();
+            assert!(guard.sandbox_arguments(&mut args, schema).is_err());
+        }
+        let mut args = json!({ "cwd": "/" }).as_object().unwrap().clone();
+        assert!(guard.sandbox_arguments(&mut args, schema).is_err());
+
+        // サンドボックスが無効な場合は変更しない
+        let guard = ToolGuard::new(ToolGuardConfig {
+            allowed_root: Some(PathBuf::from("/work/project")),
+            ..ToolGuardConfig::default()
+        });
+        let mut args = json!({ "path": "../secret.txt" })
+            .as_object()
+            .unwrap()
+            .clone();
+        assert!(guard.sandbox_arguments(&mut args, schema).is_ok());
+        assert_eq!(args["path"], "../secret.txt");
+    }
+}
@@ -0,0 +1,179 @@
+/// 大きなツール実行結果のページ分割
+///
+/// 1つのツール結果として送るには大きすぎる結果を、切り捨てずにページに分割する。
+/// 最初のページだけをツール結果としてモデルに返し、続きはモデルがカーソルを指定して
+/// 専用のツール（[`NEXT_PAGE_TOOL_NAME`]）を呼び出すことで取得できるようにする。
+use std::collections::{HashMap, VecDeque};
+
+use serde_json::Value;
+
+/// 続きのページを取得するためにモデルへ提供するツールの名前
+pub const NEXT_PAGE_TOOL_NAME: &str = "tool_result_next_page";
+
+/// 続きのページを取得するツールの説明
+pub const NEXT_PAGE_TOOL_DESCRIPTION: &str = "Read the next page of a tool result that was split into pages because it was too large. Pass the cursor shown at the end of the previous page.";
+
+/// デフォルトの1ページあたりの最大文字数
+pub const DEFAULT_TOOL_RESULT_PAGE_CHARS: usize = 8000;
+
+/// 保持する分割済みの結果の最大件数（超過時は古いものから破棄）
+const MAX_STORED_RESULTS: usize = 16;
+
+/// ツール結果のページ分割の設定
+#[derive(Debug, Clone)]
+pub struct ToolResultPagingConfig {
+    /// 1ページあたりの最大文字数
+    pub page_chars: usize,
+}
+
+impl Default for ToolResultPagingConfig {
+    fn default() -> Self {
+        Self {
+            page_chars: DEFAULT_TOOL_RESULT_PAGE_CHARS,
+        }
+    }
+}
+
+/// 分割したツール結果を保持し、カーソルで指定されたページを返す
+#[derive(Debug)]
+pub struct ToolResultPager {
+    config: ToolResultPagingConfig,
+    /// 結果ID → 分割前の結果テキスト
+    results: HashMap<String, String>,
+    /// 保存順（古い順）の結果ID
+    order: VecDeque<String>,
+    /// 次に割り当てる結果ID の番号
+    next_id: u64,
+}
+
+impl ToolResultPager {
+    /// 指定した設定でページ分割を作成する
+    pub fn new(config: ToolResultPagingConfig) -> Self {
+        Self {
+            config,
+            results: HashMap::new(),
+            order: VecDeque::new(),
+            next_id: 1,
+        }
+    }
+
+    /// ページ分割の設定を取得する
+    pub fn config(&self) -> &ToolResultPagingConfig {
+        &self.config
+    }
+
+    /// ツール結果が1ページに収まらない場合に、最初のページに置き換える
+    ///
+    /// # Arguments
+    /// * `result` - ツールの実行結果
+    ///
+    /// # Returns
+    /// 1ページに収まる場合は `result` をそのまま、収まらない場合は最初のページと
+    /// 続きを取得するためのカーソルの案内を含む文字列を返す。
+    pub fn paginate(&mut self, result: Value) -> Value {
+        let text = result.to_string();
+        if text.chars().count() <= self.config.page_chars {
+            return result;
+        }
+
+        let id = format!("r{}", self.next_id);
+        self.next_id += 1;
+        self.results.insert(id.clone(), text);
+        self.order.push_back(id.clone());
+        if self.order.len() > MAX_STORED_RESULTS
+            && let Some(oldest) = self.order.pop_front()
+        {
+            self.results.remove(&oldest);
+        }
+
+        Value::String(self.render_page(&id, 0))
+    }
+
+    /// カーソルで指定されたページを取得する
+    ///
+    /// # Arguments
+    /// * `cursor` - 前のページの末尾に示したカーソル（`<結果ID>:<開始位置>`）
+    ///
+    /// # Returns
+    /// * `Ok(String)` - ページのテキスト（続きがある場合は次のカーソルの案内を含む）
+    /// * `Err(String)` - カーソルが不正、または結果が破棄済みの場合（理由）
+    pub fn page(&self, cursor: &str) -> Result<String, String> {
+        let (id, offset) = cursor
+            .split_once(':')
+            .and_then(|(id, offset)| Some((id, offset.parse::<usize>().ok()?)))
+            .ok_or_else(|| format!("invalid cursor '{}'", cursor))?;
+        let text = self
+            .results
+            .get(id)
+            .ok_or_else(|| format!("result for cursor '{}' is no longer available", cursor))?;
+        if offset >= text.chars().count() {
+            return Err(format!("cursor '{}' is past the end of the result", cursor));
+        }
+        Ok(self.render_page(id, offset))
+    }
+
+    /// 結果の `offset` 文字目から1ページ分を取り出し、位置と続きの案内を付ける
+    fn render_page(&self, id: &str, offset: usize) -> String {
+        let text = &self.results[id];
+        let total = text.chars().count();
+        let page: String = text
+            .chars()
+            .skip(offset)
+            .take(self.config.page_chars)
+            .collect();
+        let end = offset + page.chars().count();
+
+        let note = if end < total {
+            format!(
+                "[Tool result split into pages: showing characters {}-{} of {}. Call the `{}` tool with cursor \"{}:{}\" to read the next page.]",
+                offset + 1,
+                end,
+                total,
+                NEXT_PAGE_TOOL_NAME,
+                id,
+                end
+            )
+        } else {
+            format!(
+                "[Tool result split into pages: showing characters {}-{} of {} (last page).]",
+                offset + 1,
+                end,
+                total
+            )
+        };
+        format!("{}\n\n{}", page, note)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_paginate_splits_large_results() {
+        let mut pager = ToolResultPager::new(ToolResultPagingConfig { page_chars: 10 });
+
+        // 1ページに収まる結果はそのまま返す
+        assert_eq!(pager.paginate(json!("short")), json!("short"));
+
+        // "\"abcdefghijklmnopqrstuvwxy\"" の27文字を10文字ずつに分割する
+        let first = pager.paginate(json!("abcdefghijklmnopqrstuvwxy"));
+        let first = first.as_str().unwrap();
+        assert!(first.starts_with("\"abcdefghi\n\n"));
+        assert!(first.contains("characters 1-10 of 27"));
+        assert!(first.contains("cursor \"r1:10\""));
+
+        let second = pager.page("r1:10").unwrap();
+        assert!(second.starts_with("jklmnopqrs\n\n"));
+        assert!(second.contains("cursor \"r1:20\""));
+
+        let last = pager.page("r1:20").unwrap();
+        assert!(last.starts_with("tuvwxy\"\n\n"));
+        assert!(last.contains("characters 21-27 of 27 (last page)"));
+
+        assert!(pager.page("r1:27").is_err());
+        assert!(pager.page("r2:0").is_err());
+        assert!(pager.page("r1").is_err());
+    }
+}
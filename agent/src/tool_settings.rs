@@ -0,0 +1,119 @@
+/// ツールごとの実行設定（制限時間・リトライ回数）
+///
+/// ツールによって所要時間や失敗しやすさが大きく異なるため、全体の既定値に加えて
+/// ツール名ごとに制限時間とリトライ回数を上書きできるようにする。
+/// 設定にないツールは全体の既定値を使用する。
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::agent::AgentError;
+
+/// ツールの実行設定
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolExecutionConfig {
+    /// すべてのツールに適用する制限時間（ミリ秒、None の場合は無制限）
+    #[serde(default, alias = "timeout_ms", skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u64>,
+
+    /// すべてのツールに適用する、失敗時の再試行回数
+    #[serde(default)]
+    pub retries: u32,
+
+    /// ツール名ごとの設定（全体の既定値を上書きする）
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub tools: HashMap<String, ToolSettings>,
+}
+
+/// 1つのツールの実行設定（未指定の項目は全体の既定値を使用する）
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolSettings {
+    /// 制限時間（ミリ秒）
+    #[serde(default, alias = "timeout_ms", skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u64>,
+
+    /// 失敗時の再試行回数
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retries: Option<u32>,
+}
+
+impl ToolExecutionConfig {
+    /// JSON形式の設定ファイルを読み込む
+    ///
+    /// # Errors
+    /// ファイルの読み込みやパースに失敗した場合
+    pub fn load_from_file(path: impl AsRef<Path>) -> Result<Self, AgentError> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            AgentError::ConfigError(format!(
+                "Failed to read tool settings {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        serde_json::from_str(&content).map_err(|e| {
+            AgentError::ConfigError(format!(
+                "Failed to parse tool settings {}: {}",
+                path.display(),
+                e
+            ))
+        })
+    }
+
+    /// ツールの制限時間を取得する（None の場合は無制限）
+    pub fn timeout_for(&self, tool_name: &str) -> Option<Duration> {
+        self.tools
+            .get(tool_name)
+            .and_then(|settings| settings.timeout_ms)
+            .or(self.timeout_ms)
+            .map(Duration::from_millis)
+    }
+
+    /// ツールの失敗時の再試行回数を取得する
+    pub fn retries_for(&self, tool_name: &str) -> u32 {
+        self.tools
+            .get(tool_name)
+            .and_then(|settings| settings.retries)
+            .unwrap_or(self.retries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tool_settings_override_global_defaults() {
+        let config: ToolExecutionConfig = serde_json::from_value(serde_json::json!({
+            "timeoutMs": 10000,
+            "retries": 1,
+            "tools": {
+                "git_log": { "timeout_ms": 60000, "retries": 2 },
+                "git_status": { "retries": 0 }
+            }
+        }))
+        .unwrap();
+
+        assert_eq!(config.timeout_for("git_log"), Some(Duration::from_secs(60)));
+        assert_eq!(config.retries_for("git_log"), 2);
+        // 未指定の項目は全体の既定値を使用する
+        assert_eq!(
+            config.timeout_for("git_status"),
+            Some(Duration::from_secs(10))
+        );
+        assert_eq!(config.retries_for("git_status"), 0);
+        assert_eq!(
+            config.timeout_for("git_diff"),
+            Some(Duration::from_secs(10))
+        );
+        assert_eq!(config.retries_for("git_diff"), 1);
+
+        let default = ToolExecutionConfig::default();
+        assert_eq!(default.timeout_for("git_log"), None);
+        assert_eq!(default.retries_for("git_log"), 0);
+    }
+}
@@ -0,0 +1,201 @@
+/// 応答レイテンシの計測（ベンチマーク）
+///
+/// `bench` で同じプロンプトを繰り返し送信し、最初のトークンが届くまでの時間（TTFT）と
+/// 応答が完了するまでの時間を計測して、最小・中央値・p95・最大を表示する。
+/// モデルやリージョンによる応答速度の違いを比較するために使用する。
+use std::io;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use clap::Args;
+
+use crate::output::OutputSink;
+use crate::{ClientArgs, TurnOptions, preflight_credentials, process_conversation_turn};
+
+/// `bench` サブコマンドの引数
+#[derive(Args)]
+pub struct BenchArgs {
+    #[command(flatten)]
+    client: ClientArgs,
+
+    /// 送信するプロンプト
+    #[arg(long, value_name = "TEXT")]
+    prompt: String,
+
+    /// 送信する回数
+    #[arg(long, value_name = "N", default_value_t = 5, value_parser = clap::value_parser!(u32).range(1..))]
+    runs: u32,
+}
+
+/// 1回分の計測結果
+struct BenchSample {
+    /// 送信から最初の応答テキストが届くまでの時間（テキストがない場合はNone）
+    time_to_first_token: Option<Duration>,
+    /// 送信から応答が完了するまでの時間
+    total: Duration,
+}
+
+/// 計測値の集計（最小・中央値・p95・最大）
+#[derive(Debug, PartialEq)]
+struct LatencyStats {
+    min: Duration,
+    median: Duration,
+    p95: Duration,
+    max: Duration,
+}
+
+impl LatencyStats {
+    /// 計測値を集計する（計測値がない場合はNone）
+    ///
+    /// p95 は最近傍順位法（昇順で `ceil(0.95 * n)` 番目の値）で求める。
+    fn from_samples(samples: &[Duration]) -> Option<Self> {
+        let mut sorted = samples.to_vec();
+        sorted.sort();
+        let (&min, &max) = (sorted.first()?, sorted.last()?);
+        let middle = sorted.len() / 2;
+        let median = if sorted.len() % 2 == 1 {
+            sorted[middle]
+        } else {
+            (sorted[middle - 1] + sorted[middle]) / 2
+        };
+        let p95_rank = (sorted.len() * 95).div_ceil(100);
+        Some(Self {
+            min,
+            median,
+            p95: sorted[p95_rank - 1],
+            max,
+        })
+    }
+}
+
+/// 応答テキストを表示せず、最初のテキストが届いた時刻を記録するシンク
+#[derive(Default)]
+struct FirstTokenSink {
+    first_text_at: Option<Instant>,
+}
+
+impl OutputSink for FirstTokenSink {
+    fn write_text(&mut self, text: &str) -> io::Result<()> {
+        if self.first_text_at.is_none() && !text.is_empty() {
+            self.first_text_at = Some(Instant::now());
+        }
+        Ok(())
+    }
+}
+
+/// 同じプロンプトを繰り返し送信し、応答のレイテンシを計測して集計を表示する
+///
+/// 各回は空の会話履歴から送信し、前の回の応答が次の回の入力に影響しないようにする。
+pub async fn run_bench(args: BenchArgs) -> Result<()> {
+    let mut agent = args.client.connect().await?;
+    preflight_credentials(&agent).await?;
+
+    println!(
+        "{} 回送信してレイテンシを計測します（モデル: {}）。",
+        args.runs,
+        agent.model_id()
+    );
+    let mut samples = Vec::new();
+    for run in 1..=args.runs {
+        agent.clear_history();
+        let mut sink = FirstTokenSink::default();
+        let started_at = Instant::now();
+        let response = agent
+            .send_message(&args.prompt)
+            .await
+            .with_context(|| format!("{} 回目の送信に失敗しました", run))?;
+        process_conversation_turn(
+            &mut agent,
+            response,
+            None,
+            &mut sink,
+            TurnOptions::default(),
+        )
+        .await
+        .with_context(|| format!("{} 回目の応答の受信に失敗しました", run))?;
+        let sample = BenchSample {
+            time_to_first_token: sink.first_text_at.map(|at| at - started_at),
+            total: started_at.elapsed(),
+        };
+
+        let ttft = sample
+            .time_to_first_token
+            .map_or_else(|| "-".to_string(), format_duration);
+        println!(
+            "[{}/{}] TTFT: {}、合計: {}",
+            run,
+            args.runs,
+            ttft,
+            format_duration(sample.total)
+        );
+        samples.push(sample);
+    }
+
+    println!();
+    let ttfts: Vec<Duration> = samples
+        .iter()
+        .filter_map(|sample| sample.time_to_first_token)
+        .collect();
+    let totals: Vec<Duration> = samples.iter().map(|sample| sample.total).collect();
+    print_stats("TTFT", &ttfts);
+    print_stats("合計", &totals);
+    if ttfts.len() < samples.len() {
+        println!(
+            "※ 応答テキストのなかった {} 回はTTFTの集計から除外しました。",
+            samples.len() - ttfts.len()
+        );
+    }
+    Ok(())
+}
+
+/// 集計結果を1行で表示する
+fn print_stats(label: &str, samples: &[Duration]) {
+    match LatencyStats::from_samples(samples) {
+        Some(stats) => println!(
+            "{}: 最小 {} / 中央値 {} / p95 {} / 最大 {}",
+            label,
+            format_duration(stats.min),
+            format_duration(stats.median),
+            format_duration(stats.p95),
+            format_duration(stats.max)
+        ),
+        None => println!("{}: 計測値がありません", label),
+    }
+}
+
+/// 時間をミリ秒単位で表示する
+fn format_duration(duration: Duration) -> String {
+    format!("{} ms", duration.as_millis())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latency_stats_from_samples() {
+        let millis = |values: &[u64]| -> Vec<Duration> {
+            values.iter().copied().map(Duration::from_millis).collect()
+        };
+
+        let stats = LatencyStats::from_samples(&millis(&[300, 100, 200])).unwrap();
+        assert_eq!(
+            stats,
+            LatencyStats {
+                min: Duration::from_millis(100),
+                median: Duration::from_millis(200),
+                p95: Duration::from_millis(300),
+                max: Duration::from_millis(300),
+            }
+        );
+
+        // 偶数個の場合は中央の2つの平均、p95 は20個中19番目の値
+        let samples: Vec<u64> = (1..=20).map(|i| i * 10).collect();
+        let stats = LatencyStats::from_samples(&millis(&samples)).unwrap();
+        assert_eq!(stats.median, Duration::from_millis(105));
+        assert_eq!(stats.p95, Duration::from_millis(190));
+        assert_eq!(stats.max, Duration::from_millis(200));
+
+        assert_eq!(LatencyStats::from_samples(&[]), None);
+    }
+}
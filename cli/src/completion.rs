@@ -0,0 +1,167 @@
+/// MCPプロンプトの引数入力
+///
+/// `/prompt <名前>` で選択したMCPプロンプトの引数を対話的に入力する。
+/// サーバーが補完（completion）機能を提供している場合は、Tabキーで候補を表示する。
+use agent::AgentClient;
+use anyhow::{Context as _, Result};
+use mcp::{Prompt, Reference};
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+
+/// プロンプト引数の入力中に、サーバーの補完機能で候補を提示する rustyline ヘルパー
+struct PromptArgumentHelper<'a> {
+    agent: &'a AgentClient,
+    prompt_name: String,
+    argument: String,
+}
+
+impl Completer for PromptArgumentHelper<'_> {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        // rustyline の補完は同期的なため、ランタイム上でブロックして結果を待つ
+        let candidates = tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current().block_on(self.agent.complete_mcp_argument(
+                Reference::for_prompt(self.prompt_name.clone()),
+                &self.argument,
+                &line[..pos],
+            ))
+        })
+        // 補完の失敗で入力を妨げないよう、候補なしとして扱う
+        .unwrap_or_default();
+
+        Ok((0, candidates))
+    }
+}
+
+impl Hinter for PromptArgumentHelper<'_> {
+    type Hint = String;
+}
+
+impl Highlighter for PromptArgumentHelper<'_> {}
+
+impl Validator for PromptArgumentHelper<'_> {}
+
+impl Helper for PromptArgumentHelper<'_> {}
+
+/// 接続中のMCPサーバーのプロンプト一覧を表示する
+pub async fn show_prompt_list(agent: &AgentClient) -> Result<()> {
+    let prompts = agent
+        .list_mcp_prompts()
+        .await
+        .context("プロンプト一覧の取得に失敗しました")?;
+
+    if prompts.is_empty() {
+        println!("利用可能なプロンプトはありません。");
+        return Ok(());
+    }
+
+    println!("利用可能なプロンプト:");
+    for prompt in &prompts {
+        println!("  📝 {}", prompt.name);
+        if let Some(description) = &prompt.description {
+            println!("     {}", description);
+        }
+    }
+    Ok(())
+}
+
+/// MCPプロンプトの引数を対話的に入力し、送信するテキストを組み立てる
+///
+/// # Arguments
+/// * `agent` - MCPサーバーに接続済みの AgentClient
+/// * `name` - プロンプト名
+///
+/// # Returns
+/// * `Ok(Some(String))` - 引数を埋め込んだプロンプトのテキスト
+/// * `Ok(None)` - プロンプトが見つからない、または入力が中断された場合
+/// * `Err` - プロンプトの取得に失敗した場合
+pub async fn collect_prompt_input(agent: &AgentClient, name: &str) -> Result<Option<String>> {
+    let prompts = agent
+        .list_mcp_prompts()
+        .await
+        .context("プロンプト一覧の取得に失敗しました")?;
+
+    let Some(prompt) = prompts.into_iter().find(|p| p.name == name) else {
+        println!("プロンプト '{}' が見つかりません。", name);
+        println!("/prompt で利用可能なプロンプトを確認できます。");
+        return Ok(None);
+    };
+
+    let Some(arguments) = read_prompt_arguments(agent, &prompt)? else {
+        println!("プロンプトの入力を中断しました。");
+        return Ok(None);
+    };
+
+    let text = agent
+        .render_mcp_prompt(name, arguments)
+        .await
+        .context("プロンプトの取得に失敗しました")?;
+    Ok(Some(text))
+}
+
+/// プロンプトの各引数を1つずつ入力する
+///
+/// # Returns
+/// * `Ok(Some(Map))` - 入力された引数（空入力の任意引数は含めない）
+/// * `Ok(None)` - Ctrl-C/Ctrl-D で入力が中断された場合
+fn read_prompt_arguments(
+    agent: &AgentClient,
+    prompt: &Prompt,
+) -> Result<Option<serde_json::Map<String, serde_json::Value>>> {
+    let mut values = serde_json::Map::new();
+    let arguments = prompt.arguments.as_deref().unwrap_or_default();
+    if arguments.is_empty() {
+        return Ok(Some(values));
+    }
+
+    let mut editor: Editor<PromptArgumentHelper, DefaultHistory> =
+        Editor::new().context("Failed to initialize rustyline editor")?;
+
+    for argument in arguments {
+        let required = argument.required.unwrap_or(false);
+        if let Some(description) = &argument.description {
+            println!("  {}", description);
+        }
+        editor.set_helper(Some(PromptArgumentHelper {
+            agent,
+            prompt_name: prompt.name.clone(),
+            argument: argument.name.clone(),
+        }));
+
+        let label = if required {
+            format!("  {} (必須) > ", argument.name)
+        } else {
+            format!("  {} > ", argument.name)
+        };
+
+        loop {
+            let value = match editor.readline(&label) {
+                Ok(line) => line.trim().to_string(),
+                Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => return Ok(None),
+                Err(err) => return Err(err).context("引数の入力に失敗しました"),
+            };
+
+            if !value.is_empty() {
+                values.insert(argument.name.clone(), serde_json::Value::String(value));
+                break;
+            }
+            if !required {
+                break;
+            }
+            println!("  この引数は必須です。");
+        }
+    }
+
+    Ok(Some(values))
+}
@@ -0,0 +1,89 @@
+/// シェルコマンドによる送信前・応答後のフック
+///
+/// `--pre-hook` で指定したコマンドはユーザー入力を標準入力で受け取り、標準出力に
+/// 書き出した内容が送信する入力になる。`--post-hook` で指定したコマンドは
+/// アシスタントの応答を標準入力で受け取る（出力は表示しない）。
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+use agent::{PostResponseHook, PreSendHook};
+use anyhow::{Context, Result};
+
+/// 入力をシェルコマンドで変換する送信前フックを作成する
+///
+/// コマンドが失敗した場合は警告を表示し、入力を変更せずに送信する。
+pub fn shell_pre_hook(command: String) -> PreSendHook {
+    Box::new(move |input: &mut String| match run_shell(&command, input) {
+        Ok(output) if output.status.success() => {
+            let transformed = String::from_utf8_lossy(&output.stdout);
+            *input = transformed.trim_end_matches(['\r', '\n']).to_string();
+        }
+        Ok(output) => eprintln!(
+            "警告: 送信前フックが失敗しました（{}）。入力を変更せずに送信します。",
+            output.status
+        ),
+        Err(e) => eprintln!(
+            "警告: 送信前フックを実行できませんでした: {:#}。入力を変更せずに送信します。",
+            e
+        ),
+    })
+}
+
+/// 応答をシェルコマンドに渡す応答後フックを作成する
+pub fn shell_post_hook(command: String) -> PostResponseHook {
+    Box::new(move |response: &str| match run_shell(&command, response) {
+        Ok(output) if output.status.success() => {}
+        Ok(output) => eprintln!("警告: 応答後フックが失敗しました（{}）。", output.status),
+        Err(e) => eprintln!("警告: 応答後フックを実行できませんでした: {:#}", e),
+    })
+}
+
+/// シェルでコマンドを実行し、`input` を標準入力に渡す
+fn run_shell(command: &str, input: &str) -> Result<Output> {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.args(["/C", command]);
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.args(["-c", command]);
+        shell
+    };
+    let mut child = shell
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .with_context(|| format!("コマンドを起動できませんでした: {}", command))?;
+
+    // 出力の読み取りと並行して書き込み、パイプの容量を超える入力でも詰まらないようにする
+    // （コマンドが入力を読まずに終了した場合の書き込みエラーは無視する）
+    if let Some(mut stdin) = child.stdin.take() {
+        let input = input.to_string();
+        std::thread::spawn(move || {
+            let _ = stdin.write_all(input.as_bytes());
+        });
+    }
+    child
+        .wait_with_output()
+        .with_context(|| format!("コマンドの完了待ちに失敗しました: {}", command))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shell_pre_hook_transforms_input() {
+        let mut hook = shell_pre_hook("sed 's/^tl;dr /要約して: /'".to_string());
+        let mut input = "tl;dr README.md".to_string();
+        hook(&mut input);
+        assert_eq!(input, "要約して: README.md");
+
+        // 失敗した場合は入力を変更しない
+        let mut hook = shell_pre_hook("exit 1".to_string());
+        let mut input = "そのまま".to_string();
+        hook(&mut input);
+        assert_eq!(input, "そのまま");
+    }
+}
@@ -1,12 +1,45 @@
-use agent::AgentClient;
+mod bench;
+mod completion;
+mod hooks;
+mod markdown;
+mod mcp_inputs;
+mod mcp_startup;
+mod models;
+mod output;
+mod replay;
+#[cfg(feature = "ws-server")]
+mod serve;
+mod tool_approval;
+mod tool_args;
+mod tool_images;
+mod transcript;
+
+use agent::history::{TranscriptContent, TranscriptRole};
+use agent::{
+    AgentClient, AgentError, CancellationToken, CredentialStatus, ModelTarget, RetryConfig,
+    RetryPolicy, SdkClientConfig, StopReason, StreamMetadata, TimeoutConfig, ToolCacheConfig,
+    ToolExecutionConfig, ToolGuardConfig, ToolResultPagingConfig, TurnAccumulator,
+    model_supports_tool_use,
+};
 use anyhow::{Context, Result};
-use clap::{Parser, Subcommand};
-use mcp::{McpClient, McpConfig};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use markdown::{MarkdownSink, RenderMode};
+use mcp::config::STARTER_CONFIG_TEMPLATE;
+use mcp::{
+    LaunchOptions, McpClient, McpConfig, McpConnectionInfo, ProtocolVersionMismatch, ServerConfig,
+};
+use output::{
+    BufferSink, CoalescingSink, DEFAULT_FLUSH_INTERVAL, MultiSink, OutputSink, StderrSink,
+    StdoutSink,
+};
 use rustyline::DefaultEditor;
 use rustyline::error::ReadlineError;
-use std::io::Write;
+use std::io::{IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use tokio::time::sleep;
+use tool_approval::{REJECTED_BY_USER, ToolApproval};
 
 // UI関連の設定
 const USER_NAME: &str = "User";
@@ -16,6 +49,15 @@ const LOADING_ANIMATION_CHARACTER: &str = ".";
 // ローディングアニメーションをクリアするためのスペース文字列
 // (ローディング中に表示される可能性のある最大文字数を想定: 約30-40文字分のドット)
 const CLEAR_LINE_SPACES: &str = "                                     "; // 37 spaces
+// 複数行入力モードで入力の終了を表す行
+const MULTILINE_TERMINATOR: &str = ".";
+const MULTILINE_CONTINUATION_PROMPT: &str = "  ... > ";
+// ツール実行後のフォローアップ応答の前に表示する、応答の続きであることを示す見出し
+const FOLLOW_UP_MARKER: &str = "  ↳ ";
+// Bedrockへのリクエストに付加するデフォルトのアプリケーション識別子
+const APP_NAME: &str = concat!(env!("CARGO_PKG_NAME"), "-", env!("CARGO_PKG_VERSION"));
+// 一時的なエラーで失敗したターンを自動で再送信するまでの待ち時間
+const AUTO_RETRY_DELAY: Duration = Duration::from_secs(1);
 
 // CLIの引数構造体定義
 #[derive(Parser)]
@@ -29,24 +71,501 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     /// エージェントを起動します
-    Run {
-        /// 使用するAWSプロファイル名
+    Run(Box<RunArgs>),
+    /// 一度だけ質問して応答を出力します（非対話）
+    Ask {
+        /// 質問内容（標準入力がパイプの場合は、読み込んだ内容を質問の後に続けて送信する）
+        prompt: Option<String>,
+
+        #[command(flatten)]
+        client: ClientArgs,
+
+        /// 応答をJSON形式で出力する
         #[arg(long)]
-        aws_profile: String,
+        json: bool,
+
+        /// JSON出力時も、受信中の応答テキストを標準エラー出力に表示する
+        #[arg(long, requires = "json")]
+        progress: bool,
+
+        /// ツール実行やフォローアップを含む1ターン全体の制限時間（秒）
+        #[arg(long, value_name = "SECONDS", alias = "timeout-total")]
+        turn_timeout: Option<u64>,
 
-        /// リージョン (オプション: デフォルトはプロファイル設定またはus-east-1など)
+        /// 応答テキストをまとめて書き出す間隔（ミリ秒、0で差分ごとに即座に書き出す）
+        #[arg(long, value_name = "MS", default_value_t = DEFAULT_FLUSH_INTERVAL.as_millis() as u64)]
+        flush_interval: u64,
+
+        /// 応答ごとのレイテンシとトークン使用量を標準エラー出力に表示する
         #[arg(long)]
-        region: Option<String>,
+        show_timing: bool,
     },
     /// MCPサーバーの情報を表示します
+    #[command(args_conflicts_with_subcommands = true)]
     Mcp {
+        #[command(subcommand)]
+        action: Option<McpAction>,
+
         /// 特定のMCPサーバー名（省略時は全サーバーのリストを表示）
         server_name: Option<String>,
 
-        /// mcp.jsonファイルのパス（省略時は.vscode/mcp.jsonまたはmcp.jsonを使用）
-        #[arg(long)]
+        /// mcp.jsonファイルのパス（省略時は.chatbot/mcp.json・.vscode/mcp.json・mcp.jsonの順に探索）
+        #[arg(long, conflicts_with = "config_url")]
         config: Option<String>,
+
+        /// mcp.jsonを取得するURL（信頼できる取得元のみ指定してください）
+        #[arg(long, value_name = "URL")]
+        config_url: Option<String>,
+
+        /// MCPサーバーの起動前に、解決済みのコマンド・引数・作業ディレクトリ・環境変数を表示する
+        #[arg(long)]
+        show_launch: bool,
+
+        /// MCPサーバーの標準エラー出力を、受信するたびに標準エラー出力に表示する
+        #[arg(long)]
+        debug: bool,
+
+        /// MCPサーバーの起動から初期化の完了までを待つ制限時間（秒、0で無制限）
+        #[arg(long, value_name = "SECONDS", default_value_t = mcp::DEFAULT_INIT_TIMEOUT.as_secs())]
+        mcp_init_timeout: u64,
     },
+    /// 保存した会話記録（トランスクリプト）を操作します
+    Transcript {
+        #[command(subcommand)]
+        command: transcript::TranscriptCommand,
+    },
+    /// 保存した会話のユーザー入力を、新しいセッションで順に再実行します
+    Replay(replay::ReplayArgs),
+    /// 同じプロンプトを繰り返し送信し、応答のレイテンシを計測します
+    Bench(bench::BenchArgs),
+    /// アカウントで利用可能なBedrockの基盤モデルを一覧表示します
+    Models(models::ModelsArgs),
+    /// WebSocketで会話を受け付けるサーバーを起動します
+    #[cfg(feature = "ws-server")]
+    Serve(serve::ServeArgs),
+}
+
+/// `mcp` サブコマンドの操作
+#[derive(Subcommand)]
+enum McpAction {
+    /// コメント付きのひな形から ~/.chatbot/mcp.json を作成します
+    Init {
+        /// 作成するファイルのパス（省略時は ~/.chatbot/mcp.json）
+        #[arg(long, value_name = "PATH")]
+        path: Option<PathBuf>,
+
+        /// 既存のファイルを上書きする
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+/// AgentClientの初期化（AWS接続・モデル）に関する共通引数
+#[derive(Args, Clone)]
+struct ClientArgs {
+    /// 使用するAWSプロファイル名
+    #[arg(long)]
+    aws_profile: String,
+
+    /// リージョン (オプション: デフォルトはプロファイル設定またはus-east-1など)
+    #[arg(long)]
+    region: Option<String>,
+
+    /// AWSの設定ファイル（省略時は AWS_CONFIG_FILE または ~/.aws/config）
+    #[arg(long, value_name = "PATH")]
+    aws_config_file: Option<PathBuf>,
+
+    /// AWSの認証情報ファイル（省略時は AWS_SHARED_CREDENTIALS_FILE または ~/.aws/credentials）
+    #[arg(long, value_name = "PATH")]
+    aws_credentials_file: Option<PathBuf>,
+
+    /// Bedrockのエンドポイントを上書きするURL（LocalStackやプロキシ経由での検証用。省略時はリージョンから解決）
+    #[arg(long, value_name = "URL")]
+    endpoint_url: Option<String>,
+
+    /// AWS SDKによるリクエストの最大試行回数（1でSDKのリトライを無効化。省略時はSDKのデフォルト）
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    sdk_max_attempts: Option<u32>,
+
+    /// スロットリングなど一時的なエラーで失敗したリクエストを再試行する最大回数（0で再試行しない）
+    #[arg(long, value_name = "N", default_value_t = agent::retry::DEFAULT_MAX_RETRIES)]
+    max_retries: u32,
+
+    /// 再試行までの最大待機時間（秒。待機時間は再試行のたびに倍になり、この値で打ち止めになる）
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        default_value_t = agent::retry::DEFAULT_MAX_RETRY_DELAY.as_secs(),
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    retry_max_delay: u64,
+
+    /// AWS SDKの接続タイムアウト（秒）
+    #[arg(long, value_name = "SECONDS")]
+    sdk_connect_timeout: Option<u64>,
+
+    /// AWS SDKの読み取りタイムアウト（秒。ストリーミング中の受信間隔にも適用される）
+    #[arg(long, value_name = "SECONDS")]
+    sdk_read_timeout: Option<u64>,
+
+    /// 使用するモデルID（省略時は既定のモデル）
+    #[arg(long, value_name = "MODEL_ID")]
+    model: Option<String>,
+
+    /// 使用する推論プロファイルのID（例: us.anthropic.claude-3-5-sonnet-20240620-v1:0）またはARN
+    #[arg(long, value_name = "ID_OR_ARN", conflicts_with = "model")]
+    inference_profile: Option<String>,
+
+    /// 会話全体に適用するシステムプロンプト
+    #[arg(long, value_name = "TEXT", conflicts_with = "system_file")]
+    system_prompt: Option<String>,
+
+    /// システムプロンプトを読み込むファイル
+    #[arg(long, value_name = "PATH")]
+    system_file: Option<PathBuf>,
+
+    /// モデルがスロットリング・利用不可の場合に順に試すフォールバックモデルID（複数指定可）
+    #[arg(long = "fallback-model", value_name = "MODEL_ID")]
+    fallback_models: Vec<String>,
+
+    /// リクエストのUser-Agentに付加するアプリケーション識別子（英数字と一部の記号のみ使用可能）
+    #[arg(long, value_name = "NAME", default_value = APP_NAME)]
+    app_name: String,
+
+    /// リクエストに付与するメタデータ（コスト配分タグなど。`キー=値` の形式で複数指定可）
+    #[arg(long = "metadata", value_name = "KEY=VALUE", value_parser = parse_key_value)]
+    metadata: Vec<(String, String)>,
+
+    /// モデルに渡す乱数シード（対応しているモデルのみ。同じ応答になることは保証されない）
+    #[arg(long, value_name = "N", allow_negative_numbers = true)]
+    seed: Option<i32>,
+
+    /// 応答のランダム性（0.0〜2.0。省略時はモデルのデフォルト）
+    #[arg(long, value_name = "VALUE")]
+    temperature: Option<f32>,
+
+    /// 核サンプリングの累積確率（0.0〜1.0。省略時はモデルのデフォルト）
+    #[arg(long, value_name = "VALUE")]
+    top_p: Option<f32>,
+
+    /// 応答の最大トークン数（省略時はモデルのデフォルト）
+    #[arg(long, value_name = "N")]
+    max_tokens: Option<i32>,
+
+    /// 送信前にユーザー入力を変換するシェルコマンド（入力を標準入力で受け取り、標準出力を送信する）
+    #[arg(long, value_name = "CMD")]
+    pre_hook: Option<String>,
+
+    /// アシスタントの応答を受け取った後に実行するシェルコマンド（応答を標準入力で受け取る）
+    #[arg(long, value_name = "CMD")]
+    post_hook: Option<String>,
+
+    /// ツール実行後のフォローアップのリクエストに限り、システムプロンプトに追加する指示
+    #[arg(long, value_name = "TEXT")]
+    tool_follow_up_instruction: Option<String>,
+
+    /// 1ターンにツール結果を返してモデルに問い合わせる回数の上限
+    #[arg(long, value_name = "N", default_value_t = agent::DEFAULT_MAX_TOOL_ITERATIONS)]
+    max_tool_iterations: usize,
+}
+
+/// `キー=値` 形式の引数をパースする
+fn parse_key_value(arg: &str) -> Result<(String, String), String> {
+    arg.split_once('=')
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .ok_or_else(|| format!("'{}' は キー=値 の形式で指定してください", arg))
+}
+
+/// `--max-cost` の値（正の金額）を解析する
+fn parse_max_cost(arg: &str) -> Result<f64, String> {
+    match arg.parse::<f64>() {
+        Ok(value) if value.is_finite() && value > 0.0 => Ok(value),
+        _ => Err(format!("'{}' は正の金額（USD）で指定してください", arg)),
+    }
+}
+
+impl ClientArgs {
+    /// AWS SDKのリトライ・タイムアウト設定、アプリケーション識別子、AWSの設定ファイル、エンドポイントURLを作成する
+    fn sdk_config(&self) -> SdkClientConfig {
+        let retry_config = self
+            .sdk_max_attempts
+            .map(|attempts| RetryConfig::standard().with_max_attempts(attempts));
+
+        let timeout_config =
+            (self.sdk_connect_timeout.is_some() || self.sdk_read_timeout.is_some()).then(|| {
+                let mut builder = TimeoutConfig::builder();
+                if let Some(secs) = self.sdk_connect_timeout {
+                    builder = builder.connect_timeout(Duration::from_secs(secs));
+                }
+                if let Some(secs) = self.sdk_read_timeout {
+                    builder = builder.read_timeout(Duration::from_secs(secs));
+                }
+                builder.build()
+            });
+
+        SdkClientConfig {
+            retry_config,
+            timeout_config,
+            app_name: Some(self.app_name.clone()),
+            config_file: self.aws_config_file.clone(),
+            credentials_file: self.aws_credentials_file.clone(),
+            endpoint_url: self.endpoint_url.clone(),
+        }
+    }
+
+    /// `--system-prompt` または `--system-file` で指定されたシステムプロンプトを取得する
+    ///
+    /// ファイルから読み込んだ場合は末尾の改行を取り除く。空のシステムプロンプトはエラーとする。
+    fn system_prompt(&self) -> Result<Option<String>> {
+        let prompt = match (&self.system_prompt, &self.system_file) {
+            (Some(prompt), _) => prompt.clone(),
+            (None, Some(path)) => std::fs::read_to_string(path)
+                .with_context(|| {
+                    format!(
+                        "システムプロンプトのファイルを読み込めませんでした: {}",
+                        path.display()
+                    )
+                })?
+                .trim_end()
+                .to_string(),
+            (None, None) => return Ok(None),
+        };
+        if prompt.trim().is_empty() {
+            anyhow::bail!("システムプロンプトが空です");
+        }
+        Ok(Some(prompt))
+    }
+
+    /// 引数の設定で AgentClient を作成する
+    async fn connect(self) -> Result<AgentClient> {
+        let system_prompt = self.system_prompt()?;
+        let sdk_config = self.sdk_config();
+        let mut agent = AgentClient::with_sdk_config(self.aws_profile, self.region, sdk_config)
+            .await
+            .context("Failed to initialize AgentClient")?;
+        if let Some(model) = self.model {
+            agent.set_model_id(model).context("Invalid --model")?;
+        }
+        if let Some(profile) = self.inference_profile {
+            agent
+                .set_inference_profile(profile)
+                .context("Invalid --inference-profile")?;
+        }
+        agent.set_fallback_models(self.fallback_models);
+        agent.set_retry_policy(RetryPolicy {
+            max_retries: self.max_retries,
+            max_delay: Duration::from_secs(self.retry_max_delay),
+            ..RetryPolicy::default()
+        });
+        agent
+            .set_request_metadata(self.metadata.into_iter().collect())
+            .context("Invalid --metadata")?;
+        agent.set_seed(self.seed);
+        if let Some(temperature) = self.temperature {
+            agent
+                .set_temperature(temperature)
+                .context("Invalid --temperature")?;
+        }
+        if let Some(top_p) = self.top_p {
+            agent.set_top_p(top_p).context("Invalid --top-p")?;
+        }
+        if let Some(max_tokens) = self.max_tokens {
+            agent
+                .set_max_tokens(max_tokens)
+                .context("Invalid --max-tokens")?;
+        }
+        agent.set_pre_send_hook(self.pre_hook.map(hooks::shell_pre_hook));
+        agent.set_post_response_hook(self.post_hook.map(hooks::shell_post_hook));
+        agent.set_tool_follow_up_instruction(self.tool_follow_up_instruction);
+        agent
+            .set_max_tool_iterations(self.max_tool_iterations)
+            .context("Invalid --max-tool-iterations")?;
+        if let Some(prompt) = system_prompt {
+            agent.set_system_prompt(prompt);
+        }
+        Ok(agent)
+    }
+}
+
+/// `run` サブコマンドの引数
+#[derive(Args)]
+struct RunArgs {
+    #[command(flatten)]
+    client: ClientArgs,
+
+    /// 終了時に会話履歴を自動保存するファイルパス（ディレクトリを指定した場合は日時入りのファイル名で保存）
+    #[arg(long, value_name = "PATH")]
+    autosave: Option<PathBuf>,
+
+    /// 保存する会話履歴に、メッセージごとの時刻を含める
+    #[arg(long)]
+    timestamps: bool,
+
+    /// 保存した会話履歴（JSON）を読み込み、その続きから会話を再開する
+    #[arg(long, value_name = "PATH")]
+    resume: Option<PathBuf>,
+
+    /// 自動保存するファイル名に、モデルが生成した会話のタイトルを含める（--autosave にディレクトリを指定した場合）
+    #[arg(long, requires = "autosave")]
+    auto_title: bool,
+
+    /// ツール実行やフォローアップを含む1ターン全体の制限時間（秒）
+    #[arg(long, value_name = "SECONDS", alias = "timeout-total")]
+    turn_timeout: Option<u64>,
+
+    /// 同一引数のツール呼び出し結果をキャッシュする（読み取り系のツールのみ）
+    #[arg(long)]
+    tool_cache: bool,
+
+    /// ツール結果キャッシュの有効期間（秒）
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        default_value_t = 300,
+        requires = "tool_cache"
+    )]
+    tool_cache_ttl: u64,
+
+    /// ツール結果キャッシュの最大エントリ数
+    #[arg(long, value_name = "N", default_value_t = 128, requires = "tool_cache")]
+    tool_cache_size: usize,
+
+    /// 読み取り系と判定されないツールを、キャッシュ対象として明示的に指定する（複数指定可）
+    #[arg(long = "cache-tool", value_name = "NAME", requires = "tool_cache")]
+    cache_tools: Vec<String>,
+
+    /// mcp.jsonを取得するURL（省略時は.chatbot/mcp.json・.vscode/mcp.json・mcp.jsonの順に探索）
+    #[arg(long, value_name = "URL")]
+    config_url: Option<String>,
+
+    /// MCPサーバーの起動前に、解決済みのコマンド・引数・作業ディレクトリ・環境変数を表示する
+    #[arg(long)]
+    show_launch: bool,
+
+    /// MCPサーバーの標準エラー出力を、受信するたびに標準エラー出力に表示する
+    #[arg(long)]
+    debug: bool,
+
+    /// MCPサーバーの起動から初期化の完了までを待つ制限時間（秒、0で無制限）
+    #[arg(long, value_name = "SECONDS", default_value_t = mcp::DEFAULT_INIT_TIMEOUT.as_secs())]
+    mcp_init_timeout: u64,
+
+    /// 起動時に必須のMCPサーバーへ同時に接続する数の上限
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = mcp_startup::DEFAULT_MCP_CONNECT_CONCURRENCY,
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    mcp_connect_concurrency: usize,
+
+    /// 削除・コマンド実行などの危険なツール呼び出しと、カレントディレクトリ外への書き込みを拒否する
+    #[arg(long)]
+    safe_mode: bool,
+
+    /// すべてのツールのパス引数を、許可したルート（省略時はカレントディレクトリ）内に制限する
+    #[arg(long)]
+    sandbox: bool,
+
+    /// ツール呼び出しの拒否ルールを記述したJSONファイル
+    #[arg(long, value_name = "PATH")]
+    tool_guard: Option<PathBuf>,
+
+    /// ツールの実行前に確認せず、すべてのツールを自動で実行する
+    #[arg(long)]
+    auto_approve: bool,
+
+    /// 実行前に確認せずに実行するツール名（複数指定可）
+    #[arg(
+        long = "approve-tool",
+        value_name = "NAME",
+        conflicts_with = "auto_approve"
+    )]
+    approve_tools: Vec<String>,
+
+    /// ツールごとの制限時間・リトライ回数を記述したJSONファイル
+    #[arg(long, value_name = "PATH")]
+    tool_config: Option<PathBuf>,
+
+    /// MCPサーバーへのリクエスト（ツールの実行・一覧の取得など）の応答を待つ制限時間（秒、0で無制限）
+    #[arg(long, value_name = "SECONDS", default_value_t = mcp::DEFAULT_REQUEST_TIMEOUT.as_secs())]
+    mcp_timeout: u64,
+
+    /// 指定した文字数を超えるツール結果をページに分割し、続きをモデルが取得できるようにする
+    #[arg(long, value_name = "CHARS", value_parser = clap::value_parser!(u64).range(1..))]
+    tool_result_page_size: Option<u64>,
+
+    /// 応答テキストをまとめて書き出す間隔（ミリ秒、0で差分ごとに即座に書き出す）
+    #[arg(long, value_name = "MS", default_value_t = DEFAULT_FLUSH_INTERVAL.as_millis() as u64)]
+    flush_interval: u64,
+
+    /// 応答ごとのレイテンシとトークン使用量を標準エラー出力に表示する
+    #[arg(long)]
+    show_timing: bool,
+
+    /// 応答テキストの最大文字数（超えた時点で受信を打ち切り、途中までの応答を履歴に残す）
+    #[arg(long, value_name = "CHARS")]
+    max_response_chars: Option<usize>,
+
+    /// セッションの費用の上限（USD）。トークン使用量から見積もった累計費用が達すると送信を止める
+    #[arg(long, value_name = "USD", value_parser = parse_max_cost)]
+    max_cost: Option<f64>,
+
+    /// ツール実行後の応答の見出し（continuation: 続きを示す記号、prompt: `Assistant >`）
+    #[arg(long, value_enum, default_value_t = FollowUpStyle::Continuation)]
+    follow_up_style: FollowUpStyle,
+
+    /// 応答の表示方法（raw: 受信したテキストをそのまま表示、markdown: 受信の完了後に整形して表示し直す）
+    #[arg(long, value_enum, default_value_t = RenderMode::Raw)]
+    render: RenderMode,
+
+    /// 起動時・終了時のバナーと状態表示を抑制し、会話だけを表示する（警告は表示する）
+    #[arg(long, conflicts_with = "banner")]
+    quiet: bool,
+
+    /// 起動時に枠の代わりに表示する文字列（空文字列で枠を表示しない）
+    #[arg(long, value_name = "TEXT")]
+    banner: Option<String>,
+}
+
+impl RunArgs {
+    /// MCPサーバーの起動時の設定を作成する
+    fn mcp_launch_settings(&self) -> McpLaunchSettings {
+        McpLaunchSettings {
+            show_launch: self.show_launch,
+            debug: self.debug,
+            init_timeout: seconds_limit(self.mcp_init_timeout),
+        }
+    }
+
+    /// ツールガードの設定を作成する（無効な場合は None）
+    fn tool_guard_config(&self) -> Result<Option<ToolGuardConfig>> {
+        let mut config = match &self.tool_guard {
+            Some(path) => ToolGuardConfig::load_from_file(path)?,
+            None if self.safe_mode || self.sandbox => ToolGuardConfig::default(),
+            None => return Ok(None),
+        };
+        if self.safe_mode || self.sandbox {
+            let root = std::env::current_dir().context("Failed to get current directory")?;
+            if self.safe_mode {
+                config.apply_safe_mode(root.clone());
+            }
+            if self.sandbox {
+                config.apply_sandbox(root);
+            }
+        }
+        Ok(Some(config))
+    }
+
+    /// ツール結果キャッシュの設定を作成する（無効な場合は None）
+    fn tool_cache_config(&self) -> Option<ToolCacheConfig> {
+        self.tool_cache.then(|| ToolCacheConfig {
+            ttl: Duration::from_secs(self.tool_cache_ttl),
+            max_entries: self.tool_cache_size,
+            cacheable_tools: self.cache_tools.iter().cloned().collect(),
+            ..ToolCacheConfig::default()
+        })
+    }
 }
 
 #[tokio::main]
@@ -55,17 +574,74 @@ async fn main() -> Result<()> {
     let cli = Cli::parse();
 
     match cli.command {
-        Commands::Run {
-            aws_profile,
-            region,
+        Commands::Run(args) => {
+            run_agent_cli(*args).await?;
+        }
+        Commands::Ask {
+            prompt,
+            client,
+            json,
+            progress,
+            turn_timeout,
+            flush_interval,
+            show_timing,
         } => {
-            run_agent_cli(aws_profile, region).await?;
+            let prompt = match (prompt, read_piped_stdin()?) {
+                (Some(prompt), Some(input)) => format!("{}\n\n{}", prompt, input),
+                (Some(prompt), None) => prompt,
+                (None, Some(input)) => input,
+                (None, None) => {
+                    anyhow::bail!("質問内容を引数で指定するか、標準入力にパイプで渡してください")
+                }
+            };
+            run_ask(
+                prompt,
+                client,
+                json,
+                progress,
+                turn_timeout.map(Duration::from_secs),
+                Duration::from_millis(flush_interval),
+                show_timing,
+            )
+            .await?;
         }
         Commands::Mcp {
+            action: Some(McpAction::Init { path, force }),
+            ..
+        } => {
+            init_mcp_config(path, force)?;
+        }
+        Commands::Mcp {
+            action: None,
             server_name,
             config,
+            config_url,
+            show_launch,
+            debug,
+            mcp_init_timeout,
         } => {
-            handle_mcp_command(server_name, config).await?;
+            let launch = McpLaunchSettings {
+                show_launch,
+                debug,
+                init_timeout: seconds_limit(mcp_init_timeout),
+            };
+            handle_mcp_command(server_name, config, config_url, launch).await?;
+        }
+        Commands::Transcript { command } => {
+            transcript::run_transcript_command(command)?;
+        }
+        Commands::Replay(args) => {
+            replay::run_replay(args).await?;
+        }
+        Commands::Bench(args) => {
+            bench::run_bench(args).await?;
+        }
+        Commands::Models(args) => {
+            models::run_models(args).await?;
+        }
+        #[cfg(feature = "ws-server")]
+        Commands::Serve(args) => {
+            serve::run_serve(args).await?;
         }
     }
 
@@ -76,46 +652,412 @@ async fn main() -> Result<()> {
 ///
 /// ユーザー入力の受け付け、ローディング表示、ストリーミングレスポンスの表示など、
 /// すべてのUI/UX処理を担当する。
-async fn run_agent_cli(aws_profile: String, region: Option<String>) -> Result<()> {
-    println!("Initializing Agent with profile: {}", aws_profile);
+async fn run_agent_cli(args: RunArgs) -> Result<()> {
+    let verbose = !args.quiet;
+    // 標準入力がパイプの場合は対話ループに入らず、読み込んだ内容を1回のプロンプトとして送信する。
+    // MCPの入力プロンプトが標準入力を読まないよう、接続より前に読み込んでおく。
+    let piped_input = read_piped_stdin()?;
+    if verbose {
+        println!(
+            "Initializing Agent with profile: {}",
+            args.client.aws_profile
+        );
+    }
+
+    let tool_cache_config = args.tool_cache_config();
+    let mcp_launch = args.mcp_launch_settings();
+    let tool_guard_config = args.tool_guard_config()?;
+    let tool_execution_config = args
+        .tool_config
+        .as_ref()
+        .map(ToolExecutionConfig::load_from_file)
+        .transpose()?;
 
     // エージェントクライアントの初期化（ビジネスロジック層）
-    let mut agent = AgentClient::new(aws_profile, region)
-        .await
-        .context("Failed to initialize AgentClient")?;
+    let mut agent = args.client.connect().await?;
+    preflight_credentials(&agent).await?;
+    agent.set_transcript_timestamps(args.timestamps);
+
+    if let Some(path) = &args.resume {
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("会話履歴の読み込みに失敗しました: {}", path.display()))?;
+        agent
+            .import_history(&json)
+            .with_context(|| format!("会話履歴を再開できません: {}", path.display()))?;
+        if verbose {
+            println!(
+                "会話履歴を読み込みました（{} 件）: {}",
+                agent.history_len(),
+                path.display()
+            );
+        }
+    }
+
+    if let Some(limit_usd) = args.max_cost {
+        if agent::pricing::pricing_for_model(agent.model_id()).is_none() {
+            anyhow::bail!(
+                "モデル '{}' は料金表にないため、--max-cost で費用を制限できません",
+                agent.model_id()
+            );
+        }
+        agent.set_max_cost(Some(limit_usd));
+        if verbose {
+            println!(
+                "セッションの費用の上限を ${:.2} に設定しました。",
+                limit_usd
+            );
+        }
+    }
+
+    if let Some(config) = tool_cache_config {
+        agent.enable_tool_cache(config);
+        if verbose {
+            println!("ツール結果キャッシュを有効にしました。");
+        }
+    }
+    if let Some(config) = tool_guard_config {
+        if verbose {
+            println!(
+                "ツールガードを有効にしました（拒否ルール: {} 件）。",
+                config.deny_tools.len()
+            );
+            if let (true, Some(root)) = (config.sandbox, &config.allowed_root) {
+                println!(
+                    "サンドボックスを有効にしました（ルート: {}）。",
+                    root.display()
+                );
+            }
+        }
+        agent.set_tool_guard(Some(config));
+    }
+    if let Some(page_chars) = args.tool_result_page_size {
+        agent.enable_tool_result_paging(ToolResultPagingConfig {
+            page_chars: page_chars as usize,
+        });
+    }
+    if let Some(config) = tool_execution_config {
+        if verbose {
+            println!(
+                "ツールの実行設定を読み込みました（個別設定: {} 件）。",
+                config.tools.len()
+            );
+        }
+        agent.set_tool_execution_config(config);
+    }
+    agent.set_mcp_request_timeout(seconds_limit(args.mcp_timeout));
 
     // mcp.json設定ファイルを読み込む（オプション）
-    let mcp_config = match McpConfig::load_default() {
+    let loaded_config = match &args.config_url {
+        Some(url) => load_mcp_config_from_url(url).await.map(Some),
+        None => McpConfig::load_default().map_err(anyhow::Error::from),
+    };
+    let mcp_config = match loaded_config {
         Ok(Some(config)) => {
-            println!("MCP設定ファイルを読み込みました。");
-            println!("利用可能なMCPサーバー: {}", config.server_names().len());
+            if verbose {
+                let paths: Vec<String> = config
+                    .source_paths
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect();
+                if paths.is_empty() {
+                    println!("MCP設定ファイルを読み込みました。");
+                } else {
+                    println!("MCP設定ファイルを読み込みました: {}", paths.join(", "));
+                }
+                println!("利用可能なMCPサーバー: {}", config.server_names().len());
+            }
             Some(config)
         }
         Ok(None) => {
-            println!("MCP設定ファイルが見つかりません。MCPなしで起動します。");
+            if verbose {
+                println!("MCP設定ファイルが見つかりません。MCPなしで起動します。");
+            }
             None
         }
         Err(e) => {
-            println!("警告: MCP設定ファイルの読み込みに失敗しました: {}", e);
+            println!("警告: MCP設定ファイルの読み込みに失敗しました: {:#}", e);
             println!("MCPなしで起動します。");
             None
         }
     };
 
-    // rustylineエディタの初期化（UI層）
-    let mut rl = DefaultEditor::new().context("Failed to initialize rustyline editor")?;
+    // 必須のMCPサーバー（required: true）には起動時に接続し、接続できなければ起動を中止する
+    if let Some(config) = &mcp_config {
+        mcp_startup::connect_required_mcp_servers(
+            &mut agent,
+            config,
+            mcp_launch,
+            args.mcp_connect_concurrency,
+        )
+        .await?;
+    }
+
+    if verbose {
+        match agent.model_target() {
+            ModelTarget::Model(model_id) => println!("Using Model: {}", model_id),
+            ModelTarget::InferenceProfile(profile) => {
+                println!("Using Inference Profile: {}", profile)
+            }
+        }
+        if let Some(endpoint_url) = agent.endpoint_url() {
+            println!("Using Endpoint: {}", endpoint_url);
+        }
+        if piped_input.is_none() {
+            print_startup_banner(args.banner.as_deref(), mcp_config.is_some());
+        }
+    }
+
+    let turn_timeout = args.turn_timeout.map(Duration::from_secs);
+    let settings = SessionSettings {
+        mcp_launch,
+        flush_interval: Duration::from_millis(args.flush_interval),
+        show_timing: args.show_timing,
+        max_response_chars: args.max_response_chars,
+        follow_up_style: args.follow_up_style,
+        render: args.render,
+        tool_approval: (!args.auto_approve).then(|| ToolApproval::new(args.approve_tools)),
+        ..SessionSettings::default()
+    };
+    let generation = settings.generation.clone();
+    // 終了シグナルを受信した場合も、通常終了と同じ後始末を行う
+    let conversation = async {
+        match piped_input {
+            Some(input) => run_piped_turn(&mut agent, &input, &settings, turn_timeout).await,
+            None => {
+                // rustylineエディタの初期化（UI層）
+                let rl = DefaultEditor::new().context("Failed to initialize rustyline editor")?;
+                run_conversation_loop(&mut agent, rl, mcp_config.as_ref(), settings, turn_timeout)
+                    .await
+            }
+        }
+    };
+    let loop_result = tokio::select! {
+        result = conversation => result,
+        _ = shutdown_signal(&generation) => {
+            println!("\n終了シグナルを受信しました。終了処理を行います...");
+            Ok(())
+        }
+    };
+
+    // 終了時の自動保存（正常終了・Ctrl-C/Ctrl-D・終了シグナル・エラー終了のいずれでも実行する）
+    if let Some(path) = &args.autosave {
+        autosave_history(&agent, path, args.auto_title).await;
+    }
+
+    // 会話終了時のクリーンアップ：MCPサーバーとの接続を切断してから破棄する
+    let mcp_connected = agent.is_mcp_connected();
+    if mcp_connected && verbose {
+        println!("MCPサーバーとの接続を切断中...");
+    }
+    if let Err(e) = agent.shutdown().await {
+        eprintln!("警告: MCP切断に失敗しました: {}", e);
+    } else if mcp_connected && verbose {
+        println!("MCPサーバーとの接続を切断しました。");
+    }
 
-    println!("Using Model: {}", agent.model_id());
+    // 出力をフラッシュしてから終了する
+    let _ = std::io::stdout().flush();
+    let _ = std::io::stderr().flush();
+
+    loop_result
+}
+
+/// パイプで渡された標準入力を読み込む
+///
+/// # Returns
+/// * `Ok(Some(String))` - 標準入力がTTYでない場合に、最後まで読み込んだ内容
+/// * `Ok(None)` - 標準入力がTTYの場合や、読み込んだ内容が空白のみの場合
+fn read_piped_stdin() -> Result<Option<String>> {
+    let mut stdin = std::io::stdin();
+    if stdin.is_terminal() {
+        return Ok(None);
+    }
+    let mut input = String::new();
+    stdin
+        .read_to_string(&mut input)
+        .context("標準入力の読み込みに失敗しました")?;
+    let input = input.trim();
+    Ok((!input.is_empty()).then(|| input.to_string()))
+}
+
+/// 標準入力から読み込んだプロンプトで1ターンだけ会話する
+///
+/// 応答を最後まで受け取れなかった場合は、スクリプトから判別できるようエラーを返す。
+async fn run_piped_turn(
+    agent: &mut AgentClient,
+    input: &str,
+    settings: &SessionSettings,
+    turn_timeout: Option<Duration>,
+) -> Result<()> {
+    match run_turn(agent, input, settings, turn_timeout).await? {
+        TurnOutcome::Completed => Ok(()),
+        TurnOutcome::RolledBack { .. } => anyhow::bail!("応答の取得に失敗しました"),
+        TurnOutcome::TimedOut => anyhow::bail!("制限時間内に応答が完了しませんでした"),
+        TurnOutcome::Cancelled => anyhow::bail!("応答の生成を中断しました"),
+        TurnOutcome::ToolLimitReached => {
+            anyhow::bail!("ツール呼び出しの回数が上限に達したため、応答が完了しませんでした")
+        }
+    }
+}
+
+/// 起動時のバナーを表示する
+///
+/// # Arguments
+/// * `custom` - `--banner` で指定された文字列（指定時は既定の枠の代わりにそのまま表示し、空文字列なら何も表示しない）
+/// * `mcp_available` - MCP設定ファイルを読み込んだかどうか（既定の枠にMCPコマンドの案内を含める）
+fn print_startup_banner(custom: Option<&str>, mcp_available: bool) {
+    if let Some(text) = custom {
+        if !text.is_empty() {
+            println!("{}", text);
+        }
+        return;
+    }
     println!("+--------------------------------------------------+");
     println!("| AI Agent Started. Type 'exit' or 'quit' to stop. |");
-    if mcp_config.is_some() {
+    if mcp_available {
         println!("| MCP commands: 'mcp <server_name>' to connect    |");
     }
     println!("+--------------------------------------------------+");
+}
+
+/// 生成中のターンをCtrl-Cで中断するための状態
+///
+/// 生成中はそのターンのキャンセルトークンを保持し、Ctrl-Cを受信した場合は
+/// プログラムを終了せずにトークンをキャンセルする。
+#[derive(Debug, Clone, Default)]
+struct GenerationInterrupt {
+    /// 生成中のターンのキャンセルトークン（生成中でない場合は None）
+    current: Arc<Mutex<Option<CancellationToken>>>,
+}
+
+impl GenerationInterrupt {
+    /// 生成の開始を登録し、そのターンで使うキャンセルトークンを返す
+    ///
+    /// 返したガードを破棄すると、生成の終了として登録を解除する。
+    fn begin(&self) -> GenerationGuard<'_> {
+        let token = CancellationToken::new();
+        *self.lock() = Some(token.clone());
+        GenerationGuard {
+            interrupt: self,
+            token,
+        }
+    }
+
+    /// 生成中であれば、そのターンをキャンセルする
+    ///
+    /// # Returns
+    /// 生成中でキャンセルした場合は true、生成中でなかった場合は false
+    fn interrupt(&self) -> bool {
+        match self.lock().as_ref() {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
 
+    fn lock(&self) -> std::sync::MutexGuard<'_, Option<CancellationToken>> {
+        self.current
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// 生成中であることを表すガード（破棄すると生成の終了として登録を解除する）
+struct GenerationGuard<'a> {
+    interrupt: &'a GenerationInterrupt,
+    token: CancellationToken,
+}
+
+impl Drop for GenerationGuard<'_> {
+    fn drop(&mut self) {
+        *self.interrupt.lock() = None;
+    }
+}
+
+/// Ctrl-Cにより生成を中断したことを表すエラー
+#[derive(Debug)]
+struct GenerationCancelled;
+
+impl std::fmt::Display for GenerationCancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Generation was cancelled")
+    }
+}
+
+impl std::error::Error for GenerationCancelled {}
+
+/// キャンセルされるまで待機する（トークンがない場合は完了しない）
+async fn wait_cancelled(cancel: Option<&CancellationToken>) {
+    match cancel {
+        Some(cancel) => cancel.cancelled().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// 終了シグナルを待機する
+///
+/// UnixではSIGTERMとSIGINT、それ以外のプラットフォームではCtrl-Cを待機する。
+/// シグナルハンドラの登録に失敗した場合は、そのシグナルを待機しない。
+/// 応答の生成中に受信したCtrl-Cは、終了せずにそのターンの生成を中断する。
+async fn shutdown_signal(generation: &GenerationInterrupt) {
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(e) => {
+                eprintln!("警告: SIGTERMハンドラの登録に失敗しました: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    let interrupt = async {
+        loop {
+            if let Err(e) = tokio::signal::ctrl_c().await {
+                eprintln!("警告: Ctrl-Cハンドラの登録に失敗しました: {}", e);
+                std::future::pending::<()>().await;
+            }
+            if !generation.interrupt() {
+                break;
+            }
+        }
+    };
+
+    tokio::select! {
+        _ = terminate => {}
+        _ = interrupt => {}
+    }
+}
+
+/// 対話ループを実行する
+///
+/// 終了コマンド・Ctrl-C・Ctrl-Dでループを抜けると `Ok(())` を返す。
+/// 終了時の後始末（自動保存やMCP切断）は呼び出し側で行う。
+/// 入力待ちの間も終了シグナルを受け付けられるよう、入力の読み取りは別スレッドで行う。
+/// `turn_timeout` を指定した場合、各ターンはツール実行を含めてその時間内に打ち切られる。
+async fn run_conversation_loop(
+    agent: &mut AgentClient,
+    mut rl: DefaultEditor,
+    mcp_config: Option<&McpConfig>,
+    mut settings: SessionSettings,
+    turn_timeout: Option<Duration>,
+) -> Result<()> {
     loop {
         // ユーザー入力の受け付け
-        let readline = rl.readline(&format!("{} > ", USER_NAME));
+        let multiline = settings.multiline;
+        let (editor, readline) = tokio::task::spawn_blocking(move || {
+            let readline = read_user_input(&mut rl, multiline);
+            (rl, readline)
+        })
+        .await
+        .context("Failed to read user input")?;
+        rl = editor;
         match readline {
             Ok(line) => {
                 let input = line.trim();
@@ -130,11 +1072,67 @@ async fn run_agent_cli(aws_profile: String, region: Option<String>) -> Result<()
                     break;
                 }
 
+                // MCPプロンプトの処理（引数を対話的に入力し、その結果を送信する）
+                let prompt_input;
+                let input = if let Some(prompt_name) = input
+                    .strip_prefix("/prompt")
+                    .filter(|rest| rest.is_empty() || rest.starts_with(' '))
+                {
+                    match handle_prompt_command(agent, prompt_name.trim()).await {
+                        Some(text) => {
+                            prompt_input = text;
+                            prompt_input.as_str()
+                        }
+                        None => continue,
+                    }
+                } else {
+                    input
+                };
+
+                // 直前に失敗した入力の再送信
+                let retry_input;
+                let input = if input == "/retry" {
+                    match settings.pending_retry.take() {
+                        Some(text) => {
+                            retry_input = text;
+                            retry_input.as_str()
+                        }
+                        None => {
+                            println!("再送信できる入力はありません。");
+                            continue;
+                        }
+                    }
+                } else {
+                    input
+                };
+
+                // MCPサーバーの接続管理（/mcp list・connect・disconnect）
+                if let Some(args) = input
+                    .strip_prefix("/mcp")
+                    .filter(|rest| rest.is_empty() || rest.starts_with(' '))
+                {
+                    handle_mcp_session_command(agent, mcp_config, &mut settings, args.trim())
+                        .await?;
+                    continue;
+                }
+
+                // スラッシュコマンドの処理
+                if let Some(command) = input.strip_prefix('/') {
+                    handle_slash_command(agent, &mut settings, command).await;
+                    continue;
+                }
+
                 // MCPコマンドの処理
                 if let Some(server_name) = input.strip_prefix("mcp ") {
-                    if let Some(ref config) = mcp_config {
-                        handle_mcp_connection_command(&mut agent, config, server_name.trim())
-                            .await?;
+                    if let Some(config) = mcp_config {
+                        let server_name = server_name.trim();
+                        handle_mcp_connection_command(
+                            agent,
+                            config,
+                            server_name,
+                            settings.mcp_launch,
+                        )
+                        .await?;
                     } else {
                         println!("MCP設定ファイルが読み込まれていません。");
                     }
@@ -144,44 +1142,21 @@ async fn run_agent_cli(aws_profile: String, region: Option<String>) -> Result<()
                 // 履歴に追加
                 let _ = rl.add_history_entry(input);
 
-                // アシスタントの応答開始を表示
-                print!("{} > ", AGENT_NAME);
-                std::io::stdout().flush()?;
-
-                // ローディングアニメーション開始
-                let loading_task = tokio::spawn(async {
-                    loop {
-                        sleep(Duration::from_millis(LOADING_ANIMATION_INTERVAL)).await;
-                        print!("{}", LOADING_ANIMATION_CHARACTER);
-                        // エラーが発生した場合はログに記録してループを抜ける
-                        if std::io::stdout().flush().is_err() {
-                            break;
-                        }
-                    }
-                });
-
-                // メッセージ送信（ビジネスロジック層）
-                let response_result = agent.send_message(input).await;
-
-                match response_result {
-                    Ok(response) => {
-                        // ツール使用フローを処理
-                        match process_conversation_turn(&mut agent, response, &loading_task).await {
-                            Ok(_) => {}
-                            Err(e) => {
-                                loading_task.abort();
-                                println!("\n[Error] Conversation processing failed: {}", e);
-                                agent.rollback_last_user_message();
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        loading_task.abort();
-                        println!("\n[Error] Bedrock API call failed: {}", e);
-                        // エラー時はユーザーメッセージを履歴から削除
-                        agent.rollback_last_user_message();
-                    }
+                let mut outcome = run_turn(agent, input, &settings, turn_timeout).await?;
+                // 一時的なエラーの場合は、一度だけ自動で再送信する
+                if matches!(outcome, TurnOutcome::RolledBack { transient: true }) {
+                    println!("[Info] 一時的なエラーのため、同じ入力を自動で再送信します。");
+                    sleep(AUTO_RETRY_DELAY).await;
+                    outcome = run_turn(agent, input, &settings, turn_timeout).await?;
                 }
+                // 失敗して履歴から取り消した入力は、/retry で再送信できるよう保持する
+                settings.pending_retry = match outcome {
+                    TurnOutcome::RolledBack { .. } => {
+                        println!("[Hint] /retry で同じ入力を再送信できます。");
+                        Some(input.to_string())
+                    }
+                    _ => None,
+                };
             }
             Err(ReadlineError::Interrupted) => {
                 println!("CTRL-C");
@@ -195,31 +1170,817 @@ async fn run_agent_cli(aws_profile: String, region: Option<String>) -> Result<()
                 println!("Error: {:?}", err);
                 break;
             }
-        }
+        }
+    }
+
+    Ok(())
+}
+
+/// ユーザー入力を送信して1ターン分の応答を処理する
+///
+/// 失敗した場合はエラーと対処方法を表示し、このターンで追加したメッセージ
+/// （ツール使用とその結果を含む）を履歴から取り消す
+/// （制限時間超過の場合は途中までの応答が記録済みのため、履歴を残す）。
+///
+/// # Returns
+/// * `Ok(TurnOutcome)` - ターンの結果
+/// * `Err(anyhow::Error)` - 標準出力への書き込みに失敗した場合
+async fn run_turn(
+    agent: &mut AgentClient,
+    input: &str,
+    settings: &SessionSettings,
+    turn_timeout: Option<Duration>,
+) -> Result<TurnOutcome> {
+    // アシスタントの応答開始を表示し、ローディングアニメーションを開始
+    let loading = LoadingAnimation::start(format!("{} > ", AGENT_NAME));
+    // 生成中のCtrl-Cは、終了せずにこのターンの生成を中断する
+    let generation = settings.generation.begin();
+
+    // 失敗した場合に、ターン全体を取り消すための送信前の件数
+    let history_len = agent.history_len();
+
+    // メッセージ送信（ビジネスロジック層）
+    let budget = turn_timeout.map(TurnBudget::start);
+    let sent = tokio::select! {
+        biased;
+        _ = generation.token.cancelled() => None,
+        sent = within_turn_budget(budget, agent.send_message(input)) => Some(sent),
+    };
+    let Some(response_result) = sent else {
+        loading.abort();
+        println!("\n[Info] 生成を中断しました。");
+        agent.rollback_to(history_len);
+        return Ok(TurnOutcome::Cancelled);
+    };
+    let response_result = response_result.and_then(|result| result);
+
+    // Markdownの整形は、ターミナルに表示する場合のみ行う
+    let stdout = CoalescingSink::new(StdoutSink, settings.flush_interval);
+    let mut sink: Box<dyn OutputSink> =
+        if settings.render == RenderMode::Markdown && std::io::stdout().is_terminal() {
+            Box::new(MarkdownSink::new(stdout))
+        } else {
+            Box::new(stdout)
+        };
+
+    match response_result {
+        Ok(response) => {
+            // ツール使用フローを処理
+            match process_conversation_turn(
+                agent,
+                response,
+                Some(&loading),
+                sink.as_mut(),
+                TurnOptions {
+                    budget,
+                    show_timing: settings.show_timing,
+                    max_response_chars: settings.max_response_chars,
+                    follow_up_style: settings.follow_up_style,
+                    cancel: Some(generation.token.clone()),
+                    approval: settings.tool_approval.clone(),
+                },
+            )
+            .await
+            {
+                Ok(_) => {
+                    if agent.last_model_id() != agent.model_id() {
+                        println!(
+                            "（フォールバックモデル '{}' が応答しました）",
+                            agent.last_model_id()
+                        );
+                    }
+                    Ok(TurnOutcome::Completed)
+                }
+                Err(e) if e.is::<GenerationCancelled>() => {
+                    loading.abort();
+                    // 途中までの応答は履歴に記録済みのため、履歴を残す
+                    println!("\n[Info] 生成を中断しました。");
+                    Ok(TurnOutcome::Cancelled)
+                }
+                Err(e) => {
+                    loading.abort();
+                    println!("\n[Error] Conversation processing failed: {}", e);
+                    let agent_error = e.downcast_ref::<AgentError>();
+                    if let Some(agent_error) = agent_error {
+                        print_error_guidance(agent_error);
+                    }
+                    // 制限時間超過の場合は途中までの応答が記録済みのため、履歴を残す
+                    if matches!(agent_error, Some(AgentError::TurnTimeout { .. })) {
+                        return Ok(TurnOutcome::TimedOut);
+                    }
+                    // ツール呼び出しの上限に達した場合は、それまでのツール結果が記録済みのため、履歴を残す
+                    if agent_error.is_some_and(AgentError::is_tool_iteration_limit) {
+                        return Ok(TurnOutcome::ToolLimitReached);
+                    }
+                    // ツール結果の後のフォローアップで失敗した場合も、結果のないツール使用が
+                    // 残って再送信できなくならないよう、ターン全体を取り消す
+                    agent.rollback_to(history_len);
+                    Ok(TurnOutcome::RolledBack {
+                        transient: agent_error.is_some_and(AgentError::is_transient),
+                    })
+                }
+            }
+        }
+        Err(e) => {
+            loading.abort();
+            println!("\n[Error] Bedrock API call failed: {}", e);
+            print_error_guidance(&e);
+            // エラー時はユーザーメッセージを履歴から削除
+            agent.rollback_to(history_len);
+            Ok(TurnOutcome::RolledBack {
+                transient: e.is_transient(),
+            })
+        }
+    }
+}
+
+/// 対話セッション中にスラッシュコマンドで切り替えられる設定
+#[derive(Debug, Default)]
+struct SessionSettings {
+    /// 複数行入力モード（`.` のみの行で入力を終了する）
+    multiline: bool,
+    /// MCPサーバーの起動時の設定
+    mcp_launch: McpLaunchSettings,
+    /// 応答テキストをまとめて書き出す間隔
+    flush_interval: Duration,
+    /// 応答ごとのレイテンシとトークン使用量を表示する
+    show_timing: bool,
+    /// 応答テキストの最大文字数（無制限の場合は None）
+    max_response_chars: Option<usize>,
+    /// ツール実行後の応答の見出しの表示方法
+    follow_up_style: FollowUpStyle,
+    /// 応答テキストの表示方法
+    render: RenderMode,
+    /// ツール実行前の承認の設定（確認しない場合は None）
+    tool_approval: Option<ToolApproval>,
+    /// 失敗して履歴から取り消した、`/retry` で再送信する入力
+    pending_retry: Option<String>,
+    /// 生成中のターンをCtrl-Cで中断するための状態
+    generation: GenerationInterrupt,
+}
+
+/// MCPサーバーの起動時の設定
+#[derive(Debug, Clone, Copy)]
+struct McpLaunchSettings {
+    /// サーバーの起動前に解決済みのコマンドを表示する
+    show_launch: bool,
+    /// サーバーの標準エラー出力を、受信するたびに表示する
+    debug: bool,
+    /// サーバーの起動から初期化の完了までを待つ制限時間（None の場合は無制限）
+    init_timeout: Option<Duration>,
+}
+
+impl Default for McpLaunchSettings {
+    fn default() -> Self {
+        Self {
+            show_launch: false,
+            debug: false,
+            init_timeout: Some(mcp::DEFAULT_INIT_TIMEOUT),
+        }
+    }
+}
+
+/// 秒数で指定した制限時間を変換する（0 の場合は無制限として None）
+fn seconds_limit(seconds: u64) -> Option<Duration> {
+    (seconds > 0).then(|| Duration::from_secs(seconds))
+}
+
+/// 1ターン分の送信の結果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TurnOutcome {
+    /// 応答の処理まで完了した
+    Completed,
+    /// 失敗し、このターンで追加したメッセージを履歴から取り消した（`transient` は一時的なエラーか）
+    RolledBack { transient: bool },
+    /// 制限時間を超過したが、途中までの応答を履歴に残した
+    TimedOut,
+    /// Ctrl-Cで生成を中断した（途中までの応答があれば履歴に残した）
+    Cancelled,
+    /// ツール呼び出しの回数が上限に達したが、それまでのツール結果を履歴に残した
+    ToolLimitReached,
+}
+
+/// ユーザー入力を1件読み取る
+///
+/// 複数行入力モードでは、`.` のみの行または Ctrl-D まで読み取った行を改行で連結して返す。
+/// ただし1行目がコマンド（`/...`、`exit`、`mcp <名前>` など）の場合はその行だけを返す。
+/// 2行目以降で Ctrl-C が押された場合は、入力途中の内容を破棄して空文字列を返す。
+fn read_user_input(rl: &mut DefaultEditor, multiline: bool) -> rustyline::Result<String> {
+    let first_line = rl.readline(&format!("{} > ", USER_NAME))?;
+    if !multiline || is_single_line_command(first_line.trim()) {
+        return Ok(first_line);
+    }
+    if first_line.trim_end() == MULTILINE_TERMINATOR {
+        return Ok(String::new());
+    }
+
+    let mut lines = vec![first_line];
+    loop {
+        match rl.readline(MULTILINE_CONTINUATION_PROMPT) {
+            Ok(line) if line.trim_end() == MULTILINE_TERMINATOR => break,
+            Ok(line) => lines.push(line),
+            Err(ReadlineError::Eof) => break,
+            Err(ReadlineError::Interrupted) => {
+                println!("入力を破棄しました。");
+                return Ok(String::new());
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(lines.join("\n"))
+}
+
+/// 複数行入力モードでも1行で完結させる入力（コマンド）かを判定する
+fn is_single_line_command(input: &str) -> bool {
+    input.starts_with('/')
+        || input.starts_with("mcp ")
+        || input.eq_ignore_ascii_case("exit")
+        || input.eq_ignore_ascii_case("quit")
+}
+
+/// テキストをシステムのクリップボードにコピーする
+fn copy_to_clipboard(text: &str) -> Result<()> {
+    let mut clipboard = arboard::Clipboard::new().context("クリップボードを開けませんでした")?;
+    clipboard
+        .set_text(text)
+        .context("クリップボードへの書き込みに失敗しました")
+}
+
+/// `/prompt` コマンドを処理する
+///
+/// 名前を省略した場合はプロンプト一覧を表示する。名前を指定した場合は引数を入力させ、
+/// 送信するテキストを返す。
+///
+/// # Returns
+/// * `Some(String)` - ユーザーメッセージとして送信するテキスト
+/// * `None` - 送信するものがない場合（一覧表示・中断・エラー時）
+async fn handle_prompt_command(agent: &AgentClient, prompt_name: &str) -> Option<String> {
+    if !agent.is_mcp_connected() {
+        println!("MCPサーバーに接続されていません。`mcp <サーバー名>` で接続してください。");
+        return None;
+    }
+
+    let result = if prompt_name.is_empty() {
+        completion::show_prompt_list(agent).await.map(|_| None)
+    } else {
+        completion::collect_prompt_input(agent, prompt_name).await
+    };
+
+    match result {
+        Ok(text) => text,
+        Err(e) => {
+            println!("[Error] {:#}", e);
+            None
+        }
+    }
+}
+
+/// スラッシュコマンド（`/cache clear` など）を処理する
+///
+/// # Arguments
+/// * `agent` - AgentClientへの可変参照
+/// * `settings` - 対話セッションの設定
+/// * `command` - 先頭の `/` を除いたコマンド文字列
+async fn handle_slash_command(
+    agent: &mut AgentClient,
+    settings: &mut SessionSettings,
+    command: &str,
+) {
+    let mut parts = command.split_whitespace();
+    match (parts.next(), parts.next()) {
+        (Some("multiline"), mode @ (None | Some("on") | Some("off"))) => {
+            settings.multiline = match mode {
+                Some(mode) => mode == "on",
+                None => !settings.multiline,
+            };
+            if settings.multiline {
+                println!(
+                    "複数行入力モードを有効にしました（'{}' のみの行またはCtrl-Dで送信、Ctrl-Cで入力を破棄）。",
+                    MULTILINE_TERMINATOR
+                );
+            } else {
+                println!("複数行入力モードを無効にしました。");
+            }
+        }
+        (Some("tools"), None) => {
+            let state = if agent.tools_enabled() {
+                "有効"
+            } else {
+                "無効"
+            };
+            println!("ツール使用: {}", state);
+            if !model_supports_tool_use(agent.model_id()) {
+                println!(
+                    "  ※ モデル {} はツール使用に対応していないため、ツール定義は送信されません。",
+                    agent.model_id()
+                );
+            }
+        }
+        (Some("tools"), Some("refresh")) => match agent.refresh_mcp_tools().await {
+            Ok(count) => println!("ツール一覧を再取得しました（{} 個）。", count),
+            Err(e) => eprintln!("警告: ツール一覧の再取得に失敗しました: {}", e),
+        },
+        (Some("tools"), Some(mode @ ("on" | "off"))) => {
+            agent.set_tools_enabled(mode == "on");
+            if agent.tools_enabled() {
+                println!("ツール使用を有効にしました。");
+            } else {
+                println!("ツール使用を無効にしました（MCP接続は維持されます）。");
+            }
+        }
+        (Some("status"), None) => {
+            match agent.model_target() {
+                ModelTarget::Model(model_id) => println!("モデル: {}", model_id),
+                ModelTarget::InferenceProfile(profile) => {
+                    println!("モデル: {}（推論プロファイル）", profile)
+                }
+            }
+            if agent.is_mcp_connected() {
+                println!("MCPサーバー: {}", agent.mcp_server_names().join(", "));
+            } else {
+                println!("MCPサーバー: 未接続");
+            }
+            println!("会話履歴: {} 件", agent.history_len());
+            match agent.max_cost() {
+                Some(limit_usd) => println!(
+                    "費用の見積もり: ${:.4}（上限 ${:.2}）",
+                    agent.session_cost(),
+                    limit_usd
+                ),
+                None => println!("費用の見積もり: ${:.4}", agent.session_cost()),
+            }
+            if let Err(e) = agent.validate_history() {
+                eprintln!(
+                    "警告: 会話履歴が不正な状態です（次のリクエストが失敗する可能性があります）: {}",
+                    e
+                );
+            }
+        }
+        (Some("history"), None) => print_history(agent),
+        (Some("clear"), None) => {
+            agent.clear_history();
+            settings.pending_retry = None;
+            println!("履歴をクリアしました。");
+        }
+        (Some("override-budget"), None) => {
+            if agent.max_cost().is_some() {
+                agent.override_cost_limit();
+                println!(
+                    "費用の上限を超えても送信を続けます（現在の見積もり: ${:.4}）。",
+                    agent.session_cost()
+                );
+            } else {
+                println!("費用の上限は設定されていません（--max-cost で設定できます）。");
+            }
+        }
+        (Some("cache"), None) => match agent.tool_cache() {
+            Some(cache) => println!(
+                "ツール結果キャッシュ: 有効（{} 件保持、有効期間 {} 秒）",
+                cache.len(),
+                cache.config().ttl.as_secs()
+            ),
+            None => println!("ツール結果キャッシュ: 無効"),
+        },
+        (Some("cache"), Some("clear")) => {
+            agent.clear_tool_cache();
+            println!("ツール結果キャッシュをクリアしました。");
+        }
+        (Some("cache"), Some("off")) => {
+            agent.disable_tool_cache();
+            println!("ツール結果キャッシュを無効にしました。");
+        }
+        (Some("copy"), None) => match agent.last_assistant_text() {
+            Some(text) => match copy_to_clipboard(&text) {
+                Ok(()) => println!("直近の応答をクリップボードにコピーしました。"),
+                Err(e) => eprintln!("警告: クリップボードへのコピーに失敗しました: {:#}", e),
+            },
+            None => println!("コピーできる応答がまだありません。"),
+        },
+        (Some("last"), Some(_)) => {
+            let path = command["last".len()..].trim();
+            match agent.last_assistant_text() {
+                Some(text) => match std::fs::write(path, text) {
+                    Ok(()) => println!("直近の応答を保存しました: {}", path),
+                    Err(e) => eprintln!("警告: 応答の保存に失敗しました: {}", e),
+                },
+                None => println!("保存できる応答がまだありません。"),
+            }
+        }
+        _ => {
+            println!("不明なコマンドです: /{}", command);
+            println!(
+                "利用可能なコマンド: /multiline [on|off], /prompt [名前], /mcp [list|connect|disconnect], /tools [on|off|refresh], /cache, /cache clear, /cache off, /copy, /last <ファイル>, /retry, /status, /history, /clear, /override-budget"
+            );
+        }
+    }
+}
+
+/// `/history` で表示する各メッセージの要約の最大文字数
+const HISTORY_SUMMARY_CHARS: usize = 60;
+
+/// 会話履歴の一覧を、メッセージを追加した時刻とともに表示する
+fn print_history(agent: &AgentClient) {
+    let transcript = match agent.transcript() {
+        Ok(transcript) => transcript,
+        Err(e) => {
+            eprintln!("警告: 会話履歴を取得できませんでした: {}", e);
+            return;
+        }
+    };
+    if transcript.messages.is_empty() {
+        println!("会話履歴はまだありません。");
+        return;
+    }
+
+    for (index, (message, timestamp)) in transcript
+        .messages
+        .iter()
+        .zip(agent.message_timestamps())
+        .enumerate()
+    {
+        let time = chrono::DateTime::<chrono::Local>::from(*timestamp).format("%Y-%m-%d %H:%M:%S");
+        let role = match message.role {
+            TranscriptRole::User => "user",
+            TranscriptRole::Assistant => "assistant",
+        };
+        let summary: Vec<String> = message
+            .content
+            .iter()
+            .map(|content| match content {
+                TranscriptContent::Text { text } => {
+                    let line = text.lines().next().unwrap_or_default();
+                    if text.chars().count() > HISTORY_SUMMARY_CHARS || line.len() < text.len() {
+                        let head: String = line.chars().take(HISTORY_SUMMARY_CHARS).collect();
+                        format!("{}…", head)
+                    } else {
+                        text.clone()
+                    }
+                }
+                TranscriptContent::ToolUse { name, .. } => format!("[tool_use] {}", name),
+                TranscriptContent::ToolResult { .. } => "[tool_result]".to_string(),
+            })
+            .collect();
+        println!(
+            "{:>3}. [{}] {}: {}",
+            index + 1,
+            time,
+            role,
+            summary.join(" / ")
+        );
+    }
+}
+
+/// 会話履歴を自動保存する
+///
+/// `path` が既存のディレクトリの場合は、その中に日時入りのファイル名で保存する。
+/// `auto_title` が有効な場合は、モデルが生成した会話のタイトルもファイル名に含める
+/// （生成に失敗した場合は日時のみのファイル名で保存する）。
+/// 保存の失敗は終了処理を妨げないよう警告の表示にとどめる。
+async fn autosave_history(agent: &AgentClient, path: &Path, auto_title: bool) {
+    if agent.history_len() == 0 {
+        return;
+    }
+
+    let path = if path.is_dir() {
+        let mut file_name = format!("session-{}", chrono::Local::now().format("%Y%m%d-%H%M%S"));
+        if auto_title {
+            match agent.generate_title().await {
+                Ok(title) => {
+                    let slug = title_slug(&title);
+                    if !slug.is_empty() {
+                        file_name.push('-');
+                        file_name.push_str(&slug);
+                    }
+                }
+                Err(e) => eprintln!("警告: 会話のタイトルを生成できませんでした: {}", e),
+            }
+        }
+        path.join(format!("{}.json", file_name))
+    } else {
+        path.to_path_buf()
+    };
+
+    let result = agent
+        .export_history()
+        .context("会話履歴の書き出しに失敗しました")
+        .and_then(|json| {
+            std::fs::write(&path, json)
+                .with_context(|| format!("ファイルへの書き込みに失敗しました: {}", path.display()))
+        });
+
+    match result {
+        Ok(()) => println!("会話履歴を保存しました: {}", path.display()),
+        Err(e) => eprintln!("警告: 会話履歴の自動保存に失敗しました: {:#}", e),
+    }
+}
+
+/// タイトルをファイル名に使える文字列に変換する
+///
+/// 英数字（日本語などを含む）以外の文字の並びを `-` に置き換え、英字は小文字にする。
+fn title_slug(title: &str) -> String {
+    title
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// 一度だけ質問して応答を出力する
+///
+/// `json` が有効な場合、応答テキストをバッファに蓄積して最後にJSONとして出力する。
+/// `progress` が有効な場合は、同じテキストを受信と同時に標準エラー出力にも表示する。
+/// 両方の出力先に同じ差分を書き出すため、JSONの `text` はストリームされた内容と一致する。
+/// 端末への表示は `flush_interval` ごとにまとめて書き出す。
+async fn run_ask(
+    prompt: String,
+    client: ClientArgs,
+    json: bool,
+    progress: bool,
+    turn_timeout: Option<Duration>,
+    flush_interval: Duration,
+    show_timing: bool,
+) -> Result<()> {
+    let mut agent = client.connect().await?;
+    preflight_credentials(&agent).await?;
+
+    let budget = turn_timeout.map(TurnBudget::start);
+    let response = within_turn_budget(budget, agent.send_message(&prompt))
+        .await?
+        .context("Bedrock API call failed")?;
+
+    if json {
+        let mut buffer = BufferSink::new();
+        let mut stderr = CoalescingSink::new(StderrSink, flush_interval);
+        {
+            let mut sink = MultiSink::new().with(&mut buffer);
+            if progress {
+                sink = sink.with(&mut stderr);
+            }
+            process_conversation_turn(
+                &mut agent,
+                response,
+                None,
+                &mut sink,
+                TurnOptions {
+                    budget,
+                    show_timing,
+                    ..TurnOptions::default()
+                },
+            )
+            .await?;
+        }
+        if progress {
+            eprintln!();
+        }
+
+        let output = serde_json::json!({
+            "model": agent.last_model_id(),
+            "prompt": prompt,
+            "text": buffer.into_text(),
+        });
+        println!("{}", serde_json::to_string_pretty(&output)?);
+    } else {
+        let mut stdout = CoalescingSink::new(StdoutSink, flush_interval);
+        process_conversation_turn(
+            &mut agent,
+            response,
+            None,
+            &mut stdout,
+            TurnOptions {
+                budget,
+                show_timing,
+                ..TurnOptions::default()
+            },
+        )
+        .await?;
+        println!();
+    }
+
+    Ok(())
+}
+
+/// 認証情報を事前に確認し、問題があれば原因に応じた対処方法を表示する
+///
+/// 認証情報を取得できないことが確実な場合はエラーを返し、起動を中止する。
+/// 確認自体に失敗した場合（ネットワークエラーなど）は警告のみ表示して続行する。
+async fn preflight_credentials(agent: &AgentClient) -> Result<()> {
+    match agent.check_credentials().await {
+        Ok(CredentialStatus::Valid { .. }) => Ok(()),
+        Ok(CredentialStatus::RegionNotConfigured { region }) => {
+            eprintln!(
+                "[Hint] リージョンが設定されていないため、{} を使用します。",
+                region
+            );
+            eprintln!("       - --region またはプロファイルの region で指定してください");
+            Ok(())
+        }
+        Ok(CredentialStatus::ProfileNotFound { profile }) => {
+            eprintln!("[Hint] プロファイル '{}' が見つかりません。", profile);
+            eprintln!("       - --aws-profile に ~/.aws/config のプロファイル名を指定してください");
+            eprintln!(
+                "       - または `aws configure --profile {}` でプロファイルを作成してください",
+                profile
+            );
+            anyhow::bail!("AWS profile '{}' is not defined", profile)
+        }
+        Ok(CredentialStatus::SsoLoginRequired { profile }) => {
+            eprintln!(
+                "[Hint] プロファイル '{}' のSSOセッションが期限切れ、または未ログインです。",
+                profile
+            );
+            eprintln!(
+                "       - `aws sso login --profile {}` を実行してください",
+                profile
+            );
+            anyhow::bail!("SSO login is required for AWS profile '{}'", profile)
+        }
+        Ok(CredentialStatus::NotFound) => {
+            eprintln!("[Hint] AWSの認証情報が見つかりません。");
+            eprintln!("       - --aws-profile で認証情報を設定したプロファイルを指定してください");
+            eprintln!(
+                "       - または環境変数 AWS_ACCESS_KEY_ID / AWS_SECRET_ACCESS_KEY を設定してください"
+            );
+            anyhow::bail!("No AWS credentials were found")
+        }
+        Err(e) => {
+            eprintln!("警告: 認証情報を事前に確認できませんでした: {}", e);
+            Ok(())
+        }
+    }
+}
+
+/// エージェントのエラー種別に応じて、ユーザー向けの対処方法を表示する
+fn print_error_guidance(error: &AgentError) {
+    if let AgentError::CostLimitExceeded { .. } = error {
+        println!("[Hint] セッションの費用が --max-cost で指定した上限に達しました。");
+        println!("       - 送信を続ける場合は /override-budget を実行してください");
+    }
+
+    if let AgentError::ToolIterationLimit { limit } = error {
+        println!(
+            "[Hint] 1ターンのツール呼び出しの回数が上限（{} 回）に達したため、応答を打ち切りました。",
+            limit
+        );
+        println!("       - それまでのツール結果は会話履歴に記録されています");
+        println!("       - 必要に応じて --max-tool-iterations の値を大きくしてください");
+    }
+
+    if let AgentError::TurnTimeout { .. } = error {
+        println!("[Hint] 途中までの応答は会話履歴に記録されています。");
+        println!("       - 必要に応じて --turn-timeout の値を大きくしてください");
+    }
+
+    if let AgentError::ModelNotAvailable {
+        model_id, region, ..
+    } = error
+    {
+        println!(
+            "[Hint] モデル '{}' はリージョン '{}' で利用できません。",
+            model_id, region
+        );
+        println!(
+            "       - Bedrockコンソールで、このリージョンのモデルアクセスを有効にしてください"
+        );
+        println!(
+            "       - または --region で提供リージョンに切り替えてください（例: {}）",
+            AgentError::suggested_regions().join(", ")
+        );
+    }
+
+    match error {
+        AgentError::AccessDenied(_) => {
+            println!("[Hint] Bedrockへのアクセスが拒否されました。");
+            println!(
+                "       - Bedrockコンソールで、このリージョンのモデルアクセスが有効か確認してください"
+            );
+            println!(
+                "       - IAMポリシーで bedrock:InvokeModelWithResponseStream が許可されているか確認してください"
+            );
+        }
+        AgentError::Throttling(_) => {
+            println!("[Hint] リクエストが多すぎるため、Bedrockに制限されました。");
+            println!("       - しばらく待ってから /retry で再送信してください");
+            println!(
+                "       - --max-retries で再試行の回数を増やすか、--fallback-model で別のモデルを指定できます"
+            );
+        }
+        AgentError::Validation(_) => {
+            println!("[Hint] リクエストの内容がBedrockに拒否されました。");
+            println!(
+                "       - --max-tokens・--temperature などのパラメータがモデルの制限内か確認してください"
+            );
+        }
+        AgentError::ModelNotReady(_) | AgentError::ServiceUnavailable(_) => {
+            println!("[Hint] モデルまたはBedrockが一時的に利用できません。");
+            println!("       - しばらく待ってから /retry で再送信してください");
+        }
+        AgentError::InternalServer(_) => {
+            println!("[Hint] Bedrockで内部エラーが発生しました。");
+            println!("       - /retry で再送信してください");
+        }
+        AgentError::Network(_) => {
+            println!("[Hint] Bedrockに接続できなかったか、応答がタイムアウトしました。");
+            println!(
+                "       - ネットワーク接続やプロキシ、--endpoint-url の設定を確認してから /retry で再送信してください"
+            );
+        }
+        _ => {}
+    }
+}
+
+/// 応答の受信中に表示するローディングアニメーション
+///
+/// 見出し（`Assistant > ` など）を表示した後、一定間隔でドットを出力する。
+struct LoadingAnimation {
+    task: tokio::task::JoinHandle<()>,
+    /// アニメーションの前に表示した見出し（クリア後に再表示する）
+    prompt: String,
+}
+
+impl LoadingAnimation {
+    /// 見出しを表示してアニメーションを開始する
+    fn start(prompt: String) -> Self {
+        print!("{}", prompt);
+        let _ = std::io::stdout().flush();
+        let task = tokio::spawn(async {
+            loop {
+                sleep(Duration::from_millis(LOADING_ANIMATION_INTERVAL)).await;
+                print!("{}", LOADING_ANIMATION_CHARACTER);
+                // エラーが発生した場合はループを抜ける
+                if std::io::stdout().flush().is_err() {
+                    break;
+                }
+            }
+        });
+        Self { task, prompt }
     }
 
-    // 会話終了時のクリーンアップ：MCPサーバーとの接続を切断
-    if agent.is_mcp_connected() {
-        println!("MCPサーバーとの接続を切断中...");
-        if let Err(e) = agent.disconnect_mcp().await {
-            eprintln!("警告: MCP切断に失敗しました: {}", e);
-        } else {
-            println!("MCPサーバーとの接続を切断しました。");
-        }
+    /// アニメーションを停止する（表示はクリアしない）
+    fn abort(&self) {
+        self.task.abort();
     }
 
-    Ok(())
+    /// アニメーションを停止し、表示をクリアして見出しを再表示する
+    ///
+    /// 行頭に戻り、スペースで上書きしてから再度行頭に戻り、見出しを表示する。
+    fn stop(&self) {
+        self.abort();
+        print!("\r{}{}\r{}", self.prompt, CLEAR_LINE_SPACES, self.prompt);
+        let _ = std::io::stdout().flush();
+    }
+}
+
+/// ローディングアニメーションを停止して表示をクリアする
+///
+/// ローディング表示を行っていない場合（`None`）は何もしない。
+fn stop_loading_animation(loading: Option<&LoadingAnimation>) {
+    if let Some(loading) = loading {
+        loading.stop();
+    }
 }
 
-/// ローディングアニメーションをクリアしてカーソルを戻す
+/// URLからmcp.jsonを読み込む
 ///
-/// 行頭に戻り、スペースで上書きしてから再度行頭に戻り、プロンプトを表示する。
-fn clear_loading_animation() {
-    print!(
-        "\r{} > {}\r{} > ",
-        AGENT_NAME, CLEAR_LINE_SPACES, AGENT_NAME
+/// リモートの設定は任意のコマンドを起動できるため、読み込む前に警告を表示する。
+async fn load_mcp_config_from_url(url: &str) -> Result<McpConfig> {
+    eprintln!(
+        "⚠️  リモートのMCP設定を読み込みます: {}\n   この設定に含まれるコマンドはローカルで実行されます。信頼できる取得元のみ使用してください。",
+        url
     );
-    let _ = std::io::stdout().flush();
+    McpConfig::load_from_url(url)
+        .await
+        .with_context(|| format!("MCP設定の取得に失敗しました: {}", url))
+}
+
+/// ひな形からmcp.jsonを作成する（`mcp init`）
+///
+/// # Arguments
+/// * `path` - 作成するファイルのパス（Noneの場合は ~/.chatbot/mcp.json）
+/// * `force` - 既存のファイルを上書きするか
+fn init_mcp_config(path: Option<PathBuf>, force: bool) -> Result<()> {
+    let path = match path {
+        Some(path) => path,
+        None => McpConfig::user_config_location()
+            .context("ホームディレクトリが見つかりません。--path で作成先を指定してください")?,
+    };
+    if path.exists() && !force {
+        anyhow::bail!(
+            "{} は既に存在します。上書きする場合は --force を指定してください",
+            path.display()
+        );
+    }
+
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("ディレクトリの作成に失敗しました: {}", parent.display()))?;
+    }
+    std::fs::write(&path, STARTER_CONFIG_TEMPLATE)
+        .with_context(|| format!("ファイルの書き込みに失敗しました: {}", path.display()))?;
+
+    println!("mcp.jsonのひな形を作成しました: {}", path.display());
+    println!("サーバーの設定を編集した後、`mcp` で一覧を確認できます。");
+    Ok(())
 }
 
 /// MCPコマンドを処理する
@@ -227,12 +1988,18 @@ fn clear_loading_animation() {
 /// # Arguments
 /// * `server_name` - サーバー名（Noneの場合は全サーバーのリストを表示）
 /// * `config_path` - mcp.jsonファイルのパス（Noneの場合はデフォルトパスを使用）
+/// * `config_url` - mcp.jsonを取得するURL（指定時はconfig_pathより優先）
+/// * `launch` - サーバーの起動時の設定
 async fn handle_mcp_command(
     server_name: Option<String>,
     config_path: Option<String>,
+    config_url: Option<String>,
+    launch: McpLaunchSettings,
 ) -> Result<()> {
     // 設定ファイルを読み込む
-    let config = if let Some(path) = config_path {
+    let config = if let Some(url) = config_url {
+        load_mcp_config_from_url(&url).await?
+    } else if let Some(path) = config_path {
         McpConfig::load_from_file(&path)
             .with_context(|| format!("設定ファイルの読み込みに失敗しました: {}", path))?
     } else {
@@ -240,18 +2007,30 @@ async fn handle_mcp_command(
             Some(config) => config,
             None => {
                 println!("mcp.jsonファイルが見つかりません。");
-                println!("以下のいずれかのパスに配置してください：");
-                println!("  - .vscode/mcp.json");
-                println!("  - mcp.json");
+                println!("以下のいずれかのパスに配置してください（上から優先）：");
+                for path in McpConfig::workspace_config_candidates(Path::new("")) {
+                    println!("  - {}", path.display());
+                }
+                println!("  - ~/.chatbot/mcp.json（ユーザー単位の設定）");
+                println!("`mcp init` でひな形を作成できます。");
                 return Ok(());
             }
         }
     };
 
+    // 設定の誤りをまとめて表示し、接続を試みずに終了する
+    if let Err(problems) = config.validate() {
+        eprintln!("設定ファイルに {} 件の問題があります：", problems.len());
+        for problem in &problems {
+            eprintln!("  - {}", problem);
+        }
+        anyhow::bail!("設定ファイルの検証に失敗しました");
+    }
+
     match server_name {
         // サーバー名が指定された場合：そのサーバーのツール一覧を表示
         Some(name) => {
-            show_server_tools(&config, &name).await?;
+            show_server_tools(&config, &name, launch).await?;
         }
         // サーバー名が指定されていない場合：全サーバーのリストを表示
         None => {
@@ -275,7 +2054,14 @@ fn show_server_list(config: &McpConfig) {
     for (name, server) in &config.servers {
         println!("  📦 {}", name);
         println!("     タイプ: {}", server.server_type);
-        println!("     コマンド: {}", server.command);
+        if server.is_http() {
+            println!(
+                "     URL: {}",
+                server.url.as_deref().unwrap_or("（未指定）")
+            );
+        } else {
+            println!("     コマンド: {}", server.command);
+        }
 
         if !server.args.is_empty() {
             println!("     引数: {}", server.args.join(" "));
@@ -293,16 +2079,25 @@ fn show_server_list(config: &McpConfig) {
 }
 
 /// 特定のMCPサーバーのツール一覧を表示
-async fn show_server_tools(config: &McpConfig, server_name: &str) -> Result<()> {
+async fn show_server_tools(
+    config: &McpConfig,
+    server_name: &str,
+    launch: McpLaunchSettings,
+) -> Result<()> {
     // サーバー設定を取得
     let server = config
         .get_server(server_name)
         .with_context(|| format!("サーバー '{}' が見つかりません", server_name))?;
+    // ${input:<id>} で参照された入力の値を尋ねる
+    let Some(server) = &mcp_inputs::resolve_server_inputs(config, server)? else {
+        println!("入力を中断しました。");
+        return Ok(());
+    };
 
-    // stdio以外のタイプはサポート外
-    if server.server_type != "stdio" {
+    // stdio・http以外のタイプはサポート外
+    if !server.is_stdio() && !server.is_http() {
         anyhow::bail!(
-            "サーバータイプ '{}' はサポートされていません。現在は'stdio'のみ対応しています。",
+            "サーバータイプ '{}' はサポートされていません。'stdio' と 'http' に対応しています。",
             server.server_type
         );
     }
@@ -314,21 +2109,22 @@ async fn show_server_tools(config: &McpConfig, server_name: &str) -> Result<()>
         .ok()
         .and_then(|p| p.to_str().map(String::from));
 
-    let command = server.resolve_command(workspace_folder.as_deref());
-    let args = server.resolve_args(workspace_folder.as_deref());
-
-    // 引数をVec<&str>に変換
-    let args_refs: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+    if launch.show_launch {
+        print_launch_command(server, workspace_folder.as_deref());
+    }
 
     // MCPクライアントで接続
-    let client = McpClient::new(&command, args_refs)
-        .await
-        .with_context(|| format!("MCPサーバー '{}' への接続に失敗しました", server_name))?;
+    let client = if server.is_http() {
+        connect_http_server(server).await
+    } else {
+        connect_stdio_server(server, workspace_folder.as_deref(), launch).await
+    }
+    .with_context(|| format!("MCPサーバー '{}' への接続に失敗しました", server_name))?;
 
     // サーバー情報を表示
-    if let Some(info) = client.server_info() {
+    if let Some(info) = client.connection_info() {
         println!("サーバー情報:");
-        println!("  {:?}", info);
+        print_connection_info(&info, "  ");
         println!();
     }
 
@@ -358,91 +2154,435 @@ async fn show_server_tools(config: &McpConfig, server_name: &str) -> Result<()>
     Ok(())
 }
 
+/// コマンドを起動してstdioのMCPサーバーに接続する
+///
+/// `env` と `envFile` の環境変数と、`cwd` の作業ディレクトリをサーバープロセスに設定する。
+/// `launch.debug` が true の場合は、サーバーの標準エラー出力を受信するたびに表示する。
+/// 初期化が `launch.init_timeout` の制限時間内に完了しない場合は、サーバープロセスを終了して失敗する。
+async fn connect_stdio_server(
+    server: &ServerConfig,
+    workspace_folder: Option<&str>,
+    launch: McpLaunchSettings,
+) -> Result<McpClient> {
+    let command = server.resolve_command(workspace_folder);
+    let args = server.resolve_args(workspace_folder);
+    let options = LaunchOptions {
+        env: server.resolve_env(workspace_folder)?,
+        cwd: server.resolve_cwd(workspace_folder).map(PathBuf::from),
+        forward_stderr: launch.debug,
+        init_timeout: launch.init_timeout,
+    };
+    // 引数をVec<&str>に変換
+    let args_refs: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+    let client = McpClient::new_with_options(&command, args_refs, options).await?;
+    Ok(client)
+}
+
+/// HTTPのMCPサーバーに接続する
+///
+/// `headers` のうち、`Authorization: Bearer <トークン>` のみ送信する。
+/// それ以外のヘッダーは送信できないため、警告を表示する。
+#[cfg(feature = "mcp-http")]
+async fn connect_http_server(server: &ServerConfig) -> Result<McpClient> {
+    let url = server
+        .url
+        .as_deref()
+        .context("HTTPのMCPサーバーには url の指定が必要です")?;
+    let unsupported = server.unsupported_header_names();
+    if !unsupported.is_empty() {
+        eprintln!(
+            "警告: 次のヘッダーは送信されません（Authorization のみ対応しています）: {}",
+            unsupported.join(", ")
+        );
+    }
+    let client = McpClient::connect_http_with_token(url, server.bearer_token().as_deref()).await?;
+    Ok(client)
+}
+
+/// HTTPのMCPサーバーに接続する（`mcp-http` フィーチャーが無効なビルドでは接続できない）
+#[cfg(not(feature = "mcp-http"))]
+async fn connect_http_server(_server: &ServerConfig) -> Result<McpClient> {
+    anyhow::bail!(
+        "HTTPのMCPサーバーに接続するには、`mcp-http` フィーチャーを有効にしてビルドしてください"
+    )
+}
+
+/// MCPサーバーの起動に使用する解決済みのコマンドを表示する
+///
+/// 環境変数のうち、トークンやパスワードなど機密情報と思われる値はマスクして表示する。
+/// HTTPのサーバーの場合は、接続先のURLと認証の有無を表示する（トークンは表示しない）。
+fn print_launch_command(server: &ServerConfig, workspace_folder: Option<&str>) {
+    if server.is_http() {
+        println!("🌐 接続先:");
+        println!("   URL: {}", server.url.as_deref().unwrap_or("（未指定）"));
+        let auth = if server.bearer_token().is_some() {
+            "Bearerトークン"
+        } else {
+            "なし"
+        };
+        println!("   認証: {}", auth);
+        return;
+    }
+    println!("🚀 起動コマンド:");
+    println!("   コマンド: {}", server.resolve_command(workspace_folder));
+    println!("   引数: {:?}", server.resolve_args(workspace_folder));
+    let cwd = server
+        .resolve_cwd(workspace_folder)
+        .or_else(|| workspace_folder.map(String::from))
+        .unwrap_or_else(|| "（不明）".to_string());
+    println!("   作業ディレクトリ: {}", cwd);
+
+    let env = server.redacted_env(workspace_folder);
+    if env.is_empty() {
+        println!("   環境変数: なし");
+    } else {
+        println!("   環境変数:");
+        for (key, value) in env {
+            println!("     {}={}", key, value);
+        }
+    }
+    if let Some(env_file) = &server.env_file {
+        println!("   環境変数ファイル: {}", env_file);
+    }
+}
+
+/// MCPサーバーの接続情報を表示し、プロトコルバージョンが食い違う場合は警告する
+///
+/// # Arguments
+/// * `info` - 接続情報
+/// * `indent` - 各行の先頭に付けるインデント
+fn print_connection_info(info: &McpConnectionInfo, indent: &str) {
+    println!(
+        "{}サーバー: {} {}",
+        indent, info.server_name, info.server_version
+    );
+    println!(
+        "{}プロトコルバージョン: {}（クライアント: {}）",
+        indent, info.protocol_version, info.client_protocol_version
+    );
+
+    let Some(mismatch) = info.protocol_mismatch() else {
+        return;
+    };
+    let detail = match mismatch {
+        ProtocolVersionMismatch::ServerOlder => {
+            "サーバーのプロトコルが古いため、一部の機能（補完、進捗メッセージ等）が動作しない可能性があります。"
+        }
+        ProtocolVersionMismatch::ServerNewer => {
+            "サーバーのプロトコルが新しいため、クライアントが未対応の挙動をする可能性があります。"
+        }
+        ProtocolVersionMismatch::Unknown => {
+            "クライアントが認識していないプロトコルバージョンです。ツールが正しく動作しない可能性があります。"
+        }
+    };
+    eprintln!(
+        "{}⚠️  プロトコルバージョンが一致しません: {}",
+        indent, detail
+    );
+}
+
+/// `/mcp` コマンドで会話中のMCPサーバーの接続を管理する
+///
+/// * `/mcp` または `/mcp list` - 接続中のサーバー（名前・ツール数・プロセスID）を表示する
+/// * `/mcp connect <名前>` - サーバーに接続する（`mcp <名前>` と同じ。他のサーバーとの接続は維持する）
+/// * `/mcp disconnect <名前>` - サーバーとの接続を切断する
+async fn handle_mcp_session_command(
+    agent: &mut AgentClient,
+    mcp_config: Option<&McpConfig>,
+    settings: &mut SessionSettings,
+    args: &str,
+) -> Result<()> {
+    let mut parts = args.split_whitespace();
+    match (parts.next(), parts.next()) {
+        (None | Some("list"), None) => {
+            if !agent.is_mcp_connected() {
+                println!("接続中のMCPサーバーはありません。");
+                return Ok(());
+            }
+            println!("接続中のMCPサーバー:");
+            for name in agent.mcp_server_names() {
+                let Some(client) = agent.mcp_client(name) else {
+                    continue;
+                };
+                let tool_count = match client.list_tools().await {
+                    Ok(tools) => format!("{} 個", tools.len()),
+                    Err(_) => "取得失敗".to_string(),
+                };
+                let pid = client
+                    .pid()
+                    .map_or_else(|| "不明".to_string(), |pid| pid.to_string());
+                println!("  📦 {}（ツール: {}、PID: {}）", name, tool_count, pid);
+            }
+        }
+        (Some("connect"), Some(name)) => {
+            let Some(config) = mcp_config else {
+                println!("MCP設定ファイルが読み込まれていません。");
+                return Ok(());
+            };
+            handle_mcp_connection_command(agent, config, name, settings.mcp_launch).await?;
+        }
+        (Some("disconnect"), Some(name)) => {
+            if !agent.is_mcp_server_connected(name) {
+                println!("MCPサーバー '{}' には接続していません。", name);
+                return Ok(());
+            }
+            // 失敗した場合も接続中の一覧からは取り除かれているため、警告を表示して会話を続ける
+            match agent.disconnect_mcp_named(name).await {
+                Ok(()) => println!("MCPサーバー '{}' との接続を切断しました。", name),
+                Err(e) => eprintln!(
+                    "警告: MCPサーバー '{}' との接続の切断に失敗しました: {}",
+                    name, e
+                ),
+            }
+        }
+        _ => {
+            println!(
+                "使い方: /mcp [list] | /mcp connect <サーバー名> | /mcp disconnect <サーバー名>"
+            );
+        }
+    }
+    Ok(())
+}
+
 /// 会話中のMCPサーバー接続コマンドを処理する
 ///
 /// # Arguments
 /// * `agent` - AgentClientへの可変参照
 /// * `config` - MCP設定
 /// * `server_name` - 接続するサーバー名
+/// * `launch` - サーバーの起動時の設定
 async fn handle_mcp_connection_command(
     agent: &mut AgentClient,
     config: &McpConfig,
     server_name: &str,
+    launch: McpLaunchSettings,
 ) -> Result<()> {
+    let Some(server) = prepare_mcp_server(config, server_name) else {
+        return Ok(());
+    };
+    let workspace_folder = current_workspace_folder();
+
+    println!("MCPサーバー '{}' に接続中...", server_name);
+    if launch.show_launch {
+        print_launch_command(&server, workspace_folder.as_deref());
+    }
+
+    match launch_mcp_server(&server, workspace_folder.as_deref(), launch).await {
+        Ok(client) => attach_mcp_server(agent, server_name, client).await,
+        Err(e) => print_connection_failure(server_name, &server, workspace_folder.as_deref(), &e),
+    }
+
+    Ok(())
+}
+
+/// 接続するサーバーの設定を取得し、`${input:<id>}` で参照された入力の値を尋ねる
+///
+/// # Returns
+/// サーバーが見つからない、入力を中断した、または未対応のタイプの場合は、理由を表示して None
+fn prepare_mcp_server(config: &McpConfig, server_name: &str) -> Option<ServerConfig> {
     // サーバー設定を取得
-    let server = match config.get_server(server_name) {
-        Some(s) => s,
-        None => {
-            println!("エラー: サーバー '{}' が見つかりません", server_name);
-            println!("利用可能なサーバー: {:?}", config.server_names());
-            return Ok(());
+    let Some(server) = config.get_server(server_name) else {
+        println!("エラー: サーバー '{}' が見つかりません", server_name);
+        println!("利用可能なサーバー: {:?}", config.server_names());
+        return None;
+    };
+
+    // ${input:<id>} で参照された入力の値を尋ねる
+    let server = match mcp_inputs::resolve_server_inputs(config, server) {
+        Ok(Some(server)) => server,
+        Ok(None) => {
+            println!("入力を中断しました。");
+            return None;
+        }
+        Err(e) => {
+            println!("エラー: {:#}", e);
+            return None;
         }
     };
 
-    // stdio以外のタイプはサポート外
-    if server.server_type != "stdio" {
+    // stdio・http以外のタイプはサポート外
+    if !server.is_stdio() && !server.is_http() {
         println!(
             "エラー: サーバータイプ '{}' はサポートされていません。",
             server.server_type
         );
-        return Ok(());
-    }
-
-    // 既存の接続がある場合は切断
-    if agent.is_mcp_connected() {
-        println!("既存のMCPサーバーとの接続を切断中...");
-        agent
-            .disconnect_mcp()
-            .await
-            .context("既存のMCP接続の切断に失敗しました")?;
-        println!("既存のMCPサーバーとの接続を切断しました。");
+        return None;
     }
+    Some(server)
+}
 
-    // カレントディレクトリをワークスペースフォルダとして使用
-    let workspace_folder = std::env::current_dir()
+/// カレントディレクトリをワークスペースフォルダとして取得する
+fn current_workspace_folder() -> Option<String> {
+    std::env::current_dir()
         .ok()
-        .and_then(|p| p.to_str().map(String::from));
-
-    let command = server.resolve_command(workspace_folder.as_deref());
-    let args = server.resolve_args(workspace_folder.as_deref());
-
-    println!("MCPサーバー '{}' に接続中...", server_name);
+        .and_then(|p| p.to_str().map(String::from))
+}
 
-    // 引数をVec<&str>に変換（ライフタイムに注意）
-    let args_refs: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+/// サーバーのタイプに応じて、stdioのサーバーを起動するかHTTPのサーバーに接続する
+async fn launch_mcp_server(
+    server: &ServerConfig,
+    workspace_folder: Option<&str>,
+    launch: McpLaunchSettings,
+) -> Result<McpClient> {
+    if server.is_http() {
+        connect_http_server(server).await
+    } else {
+        connect_stdio_server(server, workspace_folder, launch).await
+    }
+}
 
-    // AgentClientを通じて接続
-    match agent.connect_mcp(&command, args_refs).await {
-        Ok(()) => {
-            println!("✅ MCPサーバー '{}' に接続しました。", server_name);
+/// 接続したクライアントをサーバー名を付けてAgentClientに追加し、接続情報とツール一覧を表示する
+///
+/// 他のサーバーとの接続は維持し、同じ名前のサーバーとの接続のみ置き換える。
+async fn attach_mcp_server(agent: &mut AgentClient, server_name: &str, client: McpClient) {
+    agent.attach_mcp_client(server_name, client).await;
+    println!("✅ MCPサーバー '{}' に接続しました。", server_name);
+    let Some(client) = agent.mcp_client(server_name) else {
+        return;
+    };
+    if let Some(info) = client.connection_info() {
+        print_connection_info(&info, "   ");
+    }
 
-            // ツール一覧を取得して表示
-            match agent.list_mcp_tools().await {
-                Ok(tools) => {
-                    if tools.is_empty() {
-                        println!("   利用可能なツール: なし");
-                    } else {
-                        println!("   利用可能なツール: {} 個", tools.len());
-                        for tool in tools.iter().take(5) {
-                            println!("     - {}", tool.name);
-                        }
-                        if tools.len() > 5 {
-                            println!("     ... 他 {} 個", tools.len() - 5);
-                        }
-                    }
+    // ツール一覧を取得して表示
+    match client.list_tools().await {
+        Ok(tools) => {
+            if tools.is_empty() {
+                println!("   利用可能なツール: なし");
+            } else {
+                println!("   利用可能なツール: {} 個", tools.len());
+                for tool in tools.iter().take(5) {
+                    println!("     - {}", tool.name);
                 }
-                Err(e) => {
-                    eprintln!("   警告: ツール一覧の取得に失敗しました: {}", e);
+                if tools.len() > 5 {
+                    println!("     ... 他 {} 個", tools.len() - 5);
                 }
             }
         }
         Err(e) => {
-            println!("❌ MCPサーバーへの接続に失敗しました: {}", e);
-            println!("   コマンド: {} {}", command, args.join(" "));
+            eprintln!("   警告: ツール一覧の取得に失敗しました: {}", e);
         }
     }
+}
 
-    Ok(())
+/// MCPサーバーへの接続に失敗したことを、起動したコマンド（HTTPの場合はURL）とあわせて表示する
+fn print_connection_failure(
+    server_name: &str,
+    server: &ServerConfig,
+    workspace_folder: Option<&str>,
+    error: &anyhow::Error,
+) {
+    println!(
+        "❌ MCPサーバー '{}' への接続に失敗しました: {:#}",
+        server_name, error
+    );
+    if server.is_http() {
+        println!("   URL: {}", server.url.as_deref().unwrap_or("（未指定）"));
+    } else {
+        println!(
+            "   コマンド: {} {}",
+            server.resolve_command(workspace_folder),
+            server.resolve_args(workspace_folder).join(" ")
+        );
+    }
+}
+
+/// 1ターンの処理の設定
+#[derive(Debug, Clone, Default)]
+struct TurnOptions {
+    /// ツール実行やフォローアップを含むターン全体の制限時間（無制限の場合はNone）
+    budget: Option<TurnBudget>,
+    /// 応答ごとのレイテンシとトークン使用量を標準エラー出力に表示するか
+    show_timing: bool,
+    /// 応答ごとのテキストの最大文字数（無制限の場合はNone）
+    max_response_chars: Option<usize>,
+    /// ツール実行後のフォローアップ応答の見出しの表示方法
+    follow_up_style: FollowUpStyle,
+    /// 生成を中断するためのトークン（中断しない場合はNone）
+    cancel: Option<CancellationToken>,
+    /// ツール実行前にユーザーの承認を求める設定（確認せずに実行する場合はNone）
+    approval: Option<ToolApproval>,
+}
+
+/// ツール実行後のフォローアップ応答の見出しの表示方法
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+enum FollowUpStyle {
+    /// 同じ応答の続きであることを示す記号（`↳`）を表示する
+    #[default]
+    Continuation,
+    /// 通常の応答と同じ `Assistant >` の見出しを表示する
+    Prompt,
+}
+
+impl FollowUpStyle {
+    /// フォローアップ応答の前に表示する見出しを返す
+    fn prompt(self) -> String {
+        match self {
+            Self::Continuation => FOLLOW_UP_MARKER.to_string(),
+            Self::Prompt => format!("{} > ", AGENT_NAME),
+        }
+    }
+}
+
+/// ツール実行やフォローアップを含む、1ターン全体の制限時間
+#[derive(Debug, Clone, Copy)]
+struct TurnBudget {
+    /// 制限時間
+    budget: Duration,
+    /// ターンの期限
+    deadline: tokio::time::Instant,
+}
+
+impl TurnBudget {
+    /// 現在時刻から制限時間を開始する
+    fn start(budget: Duration) -> Self {
+        Self {
+            budget,
+            deadline: tokio::time::Instant::now() + budget,
+        }
+    }
+
+    /// 制限時間を超えたことを表すエラーを作成する
+    fn exceeded(&self) -> AgentError {
+        AgentError::TurnTimeout {
+            budget: self.budget,
+        }
+    }
+}
+
+/// ターン全体の制限時間内で非同期処理を実行する
+///
+/// # Returns
+/// * `Ok(T)` - 期限内に完了した場合（制限時間がない場合は常に完了を待つ）
+/// * `Err(AgentError::TurnTimeout)` - 期限を超えた場合
+async fn within_turn_budget<T>(
+    budget: Option<TurnBudget>,
+    future: impl std::future::Future<Output = T>,
+) -> Result<T, AgentError> {
+    match budget {
+        Some(budget) => tokio::time::timeout_at(budget.deadline, future)
+            .await
+            .map_err(|_| budget.exceeded()),
+        None => Ok(future.await),
+    }
+}
+
+/// 応答のレイテンシとトークン使用量を標準エラー出力に表示する
+fn print_timing(metadata: &StreamMetadata) {
+    let mut parts = Vec::new();
+    if let Some(latency_ms) = metadata.latency_ms {
+        parts.push(format!("応答時間: {} ms", latency_ms));
+    }
+    if let Some(usage) = &metadata.usage {
+        parts.push(format!(
+            "トークン: 入力 {} / 出力 {}",
+            usage.input_tokens(),
+            usage.output_tokens()
+        ));
+    }
+    if !parts.is_empty() {
+        eprintln!("⏱️  {}", parts.join("、"));
+    }
 }
 
 /// 会話のターンを処理する（ツール使用を含む）
@@ -452,121 +2592,185 @@ async fn handle_mcp_connection_command(
 /// # Arguments
 /// * `agent` - AgentClientへの可変参照
 /// * `response` - Bedrockからのレスポンス
-/// * `loading_task` - ローディングアニメーションタスク
+/// * `loading` - ローディングアニメーション（表示しない場合はNone）
+/// * `sink` - 応答テキストの出力先
+/// * `options` - 制限時間や表示方法などのターンの設定
+///
+/// # Returns
+/// ターンの最後の応答が終了した理由（ツール使用が連鎖した場合は、最終的な応答のもの）
+///
+/// # Note
+/// ツールを実行するのは、応答の終了理由（stopReason）が `tool_use` の場合のみ。
+/// 応答テキストが最大文字数に達した場合は、以降のストリームを受信せずに打ち切り、
+/// 途中までの応答を履歴に追加する。
+/// 制限時間を超えた場合は、途中までの応答を履歴に記録したうえで
+/// `AgentError::TurnTimeout` を返す。
+/// `options.cancel` のトークンがキャンセルされた場合も、途中までの応答を履歴に記録したうえで
+/// `GenerationCancelled` を返す（未実行のツールは実行しない）。
 async fn process_conversation_turn(
     agent: &mut AgentClient,
     response: agent::ConverseStreamResponse,
-    loading_task: &tokio::task::JoinHandle<()>,
-) -> Result<()> {
-    use aws_sdk_bedrockruntime::types::{ContentBlock, ConverseStreamOutput, ToolUseBlock};
+    loading: Option<&LoadingAnimation>,
+    sink: &mut dyn OutputSink,
+    options: TurnOptions,
+) -> Result<StopReason> {
+    use aws_sdk_bedrockruntime::types::{ContentBlock, ConverseStreamOutput};
+
+    let TurnOptions {
+        budget,
+        show_timing,
+        max_response_chars,
+        follow_up_style,
+        ..
+    } = options;
+    let cancel = options.cancel.as_ref();
+    let approval = options.approval.as_ref();
 
     let mut stream = response.stream;
-    let mut content_blocks: Vec<ContentBlock> = Vec::new();
-    let mut current_text = String::new();
-    let mut current_tool_use: Option<(String, String, String)> = None; // (tool_use_id, name, input)
+    let mut accumulator = match max_response_chars {
+        Some(max_chars) => TurnAccumulator::new().with_max_text_chars(max_chars),
+        None => TurnAccumulator::new(),
+    };
     let mut is_first_event = true;
-    let mut loading_stopped = false;
 
     // ストリーム受信ループ
-    while let Some(event) = stream.recv().await.context("Stream receive error")? {
+    loop {
+        let received = tokio::select! {
+            biased;
+            _ = wait_cancelled(cancel) => None,
+            received = within_turn_budget(budget, stream.recv()) => Some(received),
+        };
+        let event = match received {
+            None => {
+                stop_loading_animation(loading);
+                sink.flush()?;
+                agent.record_cancelled_response(accumulator.finish())?;
+                return Err(GenerationCancelled.into());
+            }
+            Some(Ok(event)) => event.context("Stream receive error")?,
+            Some(Err(timeout)) => {
+                stop_loading_animation(loading);
+                sink.flush()?;
+                agent.record_partial_response(&accumulator.text())?;
+                return Err(timeout.into());
+            }
+        };
+        let Some(event) = event else {
+            break;
+        };
+
         // 最初のイベントが届いたタイミングでローディングを消す
         if is_first_event {
-            loading_task.abort();
-            loading_stopped = true;
-            clear_loading_animation();
+            stop_loading_animation(loading);
             is_first_event = false;
         }
 
-        match event {
-            // テキストチャンク
-            ConverseStreamOutput::ContentBlockDelta(delta) => {
-                if let Some(delta_block) = delta.delta {
-                    if let Ok(text) = delta_block.as_text() {
-                        print!("{}", text);
-                        current_text.push_str(text);
-                        std::io::stdout().flush()?;
-                    } else if let Ok(tool_use_delta) = delta_block.as_tool_use() {
-                        // ツール使用のinputが段階的に来る
-                        if let Some((_, _, ref mut input)) = current_tool_use {
-                            input.push_str(tool_use_delta.input());
-                        }
-                    }
-                }
-            }
-            // コンテンツブロック開始
-            ConverseStreamOutput::ContentBlockStart(start) => {
-                if let Some(start_block) = start.start
-                    && let Ok(tool_use) = start_block.as_tool_use()
-                {
-                    // ツール使用開始
-                    current_tool_use = Some((
-                        tool_use.tool_use_id().to_string(),
-                        tool_use.name().to_string(),
-                        String::new(),
-                    ));
-                }
-            }
-            // コンテンツブロック終了
-            ConverseStreamOutput::ContentBlockStop(_) => {
-                // テキストブロックが完了した場合
-                if !current_text.is_empty() {
-                    content_blocks.push(ContentBlock::Text(current_text.clone()));
-                    current_text.clear();
-                }
+        // コンテンツブロックの区切りでは、溜めている出力を書き出す
+        let is_block_end = matches!(event, ConverseStreamOutput::ContentBlockStop(_));
+        if let Some(text) = accumulator.push_event(event)? {
+            sink.write_text(&text)?;
+        }
+        if is_block_end {
+            sink.flush()?;
+        }
 
-                // ツール使用ブロックが完了した場合
-                if let Some((tool_use_id, name, input)) = current_tool_use.take() {
-                    // JSON形式のinputをパース
-                    let input_json: serde_json::Value = serde_json::from_str(&input)
-                        .context("Failed to parse tool use input as JSON")?;
-
-                    // Convert serde_json::Value to AWS Document using agent's utility function
-                    let input_doc = agent
-                        .json_to_document(input_json.clone())
-                        .context("Failed to convert JSON to Document")?;
-
-                    let tool_use_block = ToolUseBlock::builder()
-                        .tool_use_id(tool_use_id.clone())
-                        .name(name.clone())
-                        .input(input_doc)
-                        .build()
-                        .context("Failed to build ToolUseBlock")?;
-
-                    content_blocks.push(ContentBlock::ToolUse(tool_use_block));
-                }
-            }
-            _ => {}
+        // 最大文字数に達した場合は、残りのストリームを受信せずに打ち切る
+        if accumulator.is_truncated() {
+            sink.flush()?;
+            // `ask` の標準出力を応答本文のみに保つため、通知は標準エラー出力に表示する
+            eprintln!(
+                "\n[Info] 応答が最大文字数（{}文字）に達したため、受信を打ち切りました。",
+                max_response_chars.unwrap_or_default()
+            );
+            break;
         }
     }
 
     // ストリーム終了処理
-    if !loading_stopped {
-        loading_task.abort();
-        clear_loading_animation();
+    if is_first_event {
+        stop_loading_animation(loading);
+    }
+    sink.finish_message(loading.map_or("", |loading| loading.prompt.as_str()))?;
+
+    let metadata = accumulator.metadata().cloned();
+    let received_stop_reason = accumulator.stop_reason().cloned();
+    let mut content_blocks = accumulator.finish();
+    let has_tool_use = content_blocks
+        .iter()
+        .any(|block| matches!(block, ContentBlock::ToolUse(_)));
+    // MessageStop を受信せずに打ち切った場合は、応答の内容から終了理由を推定する
+    let stop_reason = received_stop_reason.unwrap_or(if has_tool_use {
+        StopReason::ToolUse
+    } else {
+        StopReason::EndTurn
+    });
+    if let Some(usage) = metadata
+        .as_ref()
+        .and_then(|metadata| metadata.usage.as_ref())
+    {
+        agent.record_usage(usage);
     }
 
-    // 残りのテキストがあれば追加
-    if !current_text.is_empty() {
-        content_blocks.push(ContentBlock::Text(current_text));
+    if loading.is_some() {
+        println!(); // 最後に改行
+    }
+    if show_timing && let Some(metadata) = &metadata {
+        if loading.is_none() {
+            eprintln!();
+        }
+        print_timing(metadata);
     }
 
-    println!(); // 最後に改行
+    if stop_reason == StopReason::MaxTokens {
+        eprintln!(
+            "⚠️  応答が最大トークン数に達したため、途中で打ち切られました。続きが必要な場合は --max-tokens を増やしてください。"
+        );
+    }
+    // 終了理由が tool_use 以外の場合はツールを実行しないため、ツール結果と対にならない
+    // ツール使用を履歴に残さないよう取り除く
+    if has_tool_use && stop_reason != StopReason::ToolUse {
+        eprintln!(
+            "⚠️  Warning: Ignoring tool use because the response stopped with {:?}",
+            stop_reason
+        );
+        content_blocks.retain(|block| !matches!(block, ContentBlock::ToolUse(_)));
+    }
 
     // アシスタントのメッセージを履歴に追加
     agent
         .add_assistant_message_with_blocks(content_blocks.clone())
         .context("Failed to add assistant message")?;
 
-    // ツール使用があればそれを処理
-    let has_tool_use = content_blocks
-        .iter()
-        .any(|block| matches!(block, ContentBlock::ToolUse(_)));
+    // 終了理由がツール使用であれば、ツールを実行して会話を継続する
+    if stop_reason == StopReason::ToolUse && has_tool_use && agent.is_mcp_connected() {
+        let mut timed_out = false;
 
-    if has_tool_use && agent.is_mcp_connected() {
         // ツール実行して結果を返す
         for block in &content_blocks {
             if let ContentBlock::ToolUse(tool_use) = block {
-                println!("\n🔧 ツール実行中: {}...", tool_use.name());
+                // 制限時間を超えた後や中断した後のツールは実行せず、履歴の整合性のためエラー結果を返す
+                let skip_reason = if timed_out {
+                    Some("Skipped: the turn exceeded its total time budget")
+                } else if cancel.is_some_and(CancellationToken::is_cancelled) {
+                    Some("Skipped: generation was cancelled by the user")
+                } else {
+                    None
+                };
+                if let Some(skip_reason) = skip_reason {
+                    let skipped_result = serde_json::json!({ "error": skip_reason });
+                    agent
+                        .add_tool_result(tool_use.tool_use_id().to_string(), skipped_result)
+                        .context("Failed to add tool error result")?;
+                    continue;
+                }
+
+                let approval_required =
+                    approval.is_some_and(|approval| !approval.is_allowed(tool_use.name()));
+                if approval_required {
+                    println!("\n🔐 ツールの実行の確認: {}", tool_use.name());
+                } else {
+                    println!("\n🔧 ツール実行中: {}...", tool_use.name());
+                }
 
                 // Convert AWS Document to serde_json::Value for MCP tool call
                 let input_doc = tool_use.input();
@@ -591,22 +2795,73 @@ async fn process_conversation_turn(
                         None
                     }
                 };
+                if let Some(arguments) = arguments.as_ref().filter(|args| !args.is_empty()) {
+                    println!("{}", tool_args::format_tool_arguments(arguments, "   "));
+                }
 
-                // MCPツールを実行
-                match agent
-                    .call_mcp_tool(tool_use.name().to_string(), arguments)
-                    .await
+                // 承認されなかったツールは実行せず、拒否したことをツール結果としてモデルに伝える
+                if let Some(approval) = approval.filter(|_| approval_required)
+                    && !approval.confirm(tool_use.name()).await?
                 {
-                    Ok(result) => {
-                        println!("✅ ツール実行完了");
+                    println!("🚫 {} → 実行を拒否しました", tool_use.name());
+                    let rejected_result = serde_json::json!({ "error": REJECTED_BY_USER });
+                    agent
+                        .add_tool_result(tool_use.tool_use_id().to_string(), rejected_result)
+                        .context("Failed to add tool error result")?;
+                    continue;
+                }
+
+                // MCPツールを実行（キャッシュが有効な場合は前回の結果を再利用）
+                // 実行中の進捗通知は同じ行を上書きして表示する
+                let mut progress_shown = false;
+                let call_result = within_turn_budget(
+                    budget,
+                    agent.call_mcp_tool_cached(
+                        tool_use.name().to_string(),
+                        arguments,
+                        |progress| {
+                            print!("\r   ⏳ {}{}", progress, CLEAR_LINE_SPACES);
+                            let _ = std::io::stdout().flush();
+                            progress_shown = true;
+                        },
+                    ),
+                )
+                .await
+                .unwrap_or_else(|timeout| {
+                    timed_out = true;
+                    Err(timeout)
+                });
+                if progress_shown {
+                    println!();
+                }
+                match call_result {
+                    Ok(outcome) => {
+                        let summary = tool_args::summarize_tool_result(&outcome.result);
+                        if outcome.from_cache {
+                            println!(
+                                "♻️  {} → {}（キャッシュ済みの結果）",
+                                tool_use.name(),
+                                summary
+                            );
+                        } else {
+                            println!("✅ {} → {}", tool_use.name(), summary);
+                        }
+                        tool_images::show_tool_images(&outcome.result);
 
                         // ツール結果を履歴に追加
                         agent
-                            .add_tool_result(tool_use.tool_use_id().to_string(), result)
+                            .add_tool_result(tool_use.tool_use_id().to_string(), outcome.result)
                             .context("Failed to add tool result")?;
                     }
                     Err(e) => {
-                        eprintln!("❌ ツール実行エラー: {}", e);
+                        if let AgentError::ToolBlocked { tool_name, reason } = &e {
+                            eprintln!(
+                                "🚫 ツール呼び出しをブロックしました: {} ({})",
+                                tool_name, reason
+                            );
+                        } else {
+                            eprintln!("❌ {} → エラー: {}", tool_use.name(), e);
+                        }
 
                         // エラーもツール結果として返す
                         let error_result = serde_json::json!({
@@ -620,40 +2875,62 @@ async fn process_conversation_turn(
             }
         }
 
-        // ツール実行後、再度Bedrockに問い合わせて最終的な応答を得る
-        println!("\n{} > ", AGENT_NAME);
-        std::io::stdout().flush()?;
+        if timed_out && let Some(budget) = budget {
+            agent.record_partial_response("")?;
+            return Err(budget.exceeded().into());
+        }
+        if cancel.is_some_and(CancellationToken::is_cancelled) {
+            agent.record_cancelled_response(Vec::new())?;
+            return Err(GenerationCancelled.into());
+        }
 
-        // ローディングアニメーション再開
-        let loading_task2 = tokio::spawn(async {
-            loop {
-                sleep(Duration::from_millis(LOADING_ANIMATION_INTERVAL)).await;
-                print!("{}", LOADING_ANIMATION_CHARACTER);
-                if std::io::stdout().flush().is_err() {
-                    break;
-                }
-            }
+        // ツール実行後、再度Bedrockに問い合わせて最終的な応答を得る
+        // 対話時は、同じターンの応答の続きであることが分かる見出しを表示する
+        let follow_up_loading = loading.map(|_| {
+            println!();
+            LoadingAnimation::start(follow_up_style.prompt())
         });
 
         // ツール結果後のフォローアップリクエストを送信
         // 新しいユーザーメッセージは追加せず、既存の履歴（ツール結果を含む）を使用する
-        let follow_up_response = agent
-            .send_tool_result_follow_up()
-            .await
-            .context("Failed to send follow-up message after tool use")?;
+        let follow_up_request = within_turn_budget(budget, agent.send_tool_result_follow_up());
+        let follow_up = tokio::select! {
+            biased;
+            _ = wait_cancelled(cancel) => None,
+            response = follow_up_request => Some(response),
+        };
+        let follow_up_response = match follow_up {
+            None => {
+                if let Some(loading) = &follow_up_loading {
+                    loading.abort();
+                }
+                agent.record_cancelled_response(Vec::new())?;
+                return Err(GenerationCancelled.into());
+            }
+            Some(Ok(response)) => {
+                response.context("Failed to send follow-up message after tool use")?
+            }
+            Some(Err(timeout)) => {
+                if let Some(loading) = &follow_up_loading {
+                    loading.abort();
+                }
+                agent.record_partial_response("")?;
+                return Err(timeout.into());
+            }
+        };
 
         // 再帰的に処理（ツール使用が連鎖する可能性があるため）
         // Box::pin を使用して無限サイズのfutureを回避
-        Box::pin(process_conversation_turn(
+        // 履歴は ツール使用 → ツール結果 → 最終応答 の順に追加され、同じターンの一連のメッセージになる
+        return Box::pin(process_conversation_turn(
             agent,
             follow_up_response,
-            &loading_task2,
+            follow_up_loading.as_ref(),
+            sink,
+            options,
         ))
-        .await?;
-
-        // 最後のユーザーメッセージ（空）をロールバック
-        agent.rollback_last_user_message();
+        .await;
     }
 
-    Ok(())
+    Ok(stop_reason)
 }
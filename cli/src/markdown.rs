@@ -0,0 +1,329 @@
+/// 応答テキストのMarkdownレンダリング
+///
+/// ストリーミング中は受信したテキストをそのまま表示し、応答1件の受信が完了した時点で
+/// 表示した生テキストを消去して、Markdownとして整形した内容を表示し直す。
+/// コードブロックは syntect でシンタックスハイライトし、それ以外は termimad で
+/// 見出し・ボールド・リストなどをANSIスタイルで表示する。
+use std::io;
+use std::sync::LazyLock;
+
+use clap::ValueEnum;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::parsing::SyntaxSet;
+use syntect::util::{LinesWithEndings, as_24_bit_terminal_escaped};
+use termimad::MadSkin;
+use unicode_width::UnicodeWidthStr;
+
+use crate::output::OutputSink;
+
+/// コードブロックのハイライトに使うテーマ
+const CODE_THEME: &str = "base16-ocean.dark";
+
+/// ANSIスタイルを解除するエスケープシーケンス
+const RESET_STYLE: &str = "\x1b[0m";
+
+/// シンタックス定義（初回のハイライト時に読み込む）
+static SYNTAX_SET: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
+
+/// ハイライトのテーマ（初回のハイライト時に読み込む）
+static THEME: LazyLock<Theme> = LazyLock::new(|| {
+    let mut themes = ThemeSet::load_defaults().themes;
+    themes.remove(CODE_THEME).unwrap_or_default()
+});
+
+/// 応答テキストの表示方法
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum RenderMode {
+    /// 受信したテキストをそのまま表示する
+    #[default]
+    Raw,
+    /// 受信の完了後に、Markdownとして整形して表示し直す
+    Markdown,
+}
+
+/// Markdownのテキストを、コードブロックとそれ以外に分けた区間
+#[derive(Debug, PartialEq, Eq)]
+enum Segment<'a> {
+    /// コードブロック以外のMarkdown
+    Markdown(String),
+    /// コードブロック（`language` はフェンスに指定された言語名）
+    Code { language: &'a str, code: String },
+}
+
+/// テキストをコードブロックとそれ以外の区間に分ける
+///
+/// 閉じられていないコードブロック（最大文字数で打ち切った場合など）は、
+/// テキストの最後までをコードとして扱う。
+fn split_code_blocks(text: &str) -> Vec<Segment<'_>> {
+    let mut segments = Vec::new();
+    let mut markdown = String::new();
+    let mut code: Option<(&str, &str, String)> = None;
+
+    for line in text.split_inclusive('\n') {
+        let trimmed = line.trim();
+        match &mut code {
+            Some((fence, language, body)) => {
+                if trimmed == *fence {
+                    segments.push(Segment::Code {
+                        language,
+                        code: std::mem::take(body),
+                    });
+                    code = None;
+                } else {
+                    body.push_str(line);
+                }
+            }
+            None => {
+                let fence = ["```", "~~~"]
+                    .into_iter()
+                    .find(|fence| trimmed.starts_with(fence));
+                if let Some(fence) = fence {
+                    if !markdown.is_empty() {
+                        segments.push(Segment::Markdown(std::mem::take(&mut markdown)));
+                    }
+                    code = Some((fence, trimmed[fence.len()..].trim(), String::new()));
+                } else {
+                    markdown.push_str(line);
+                }
+            }
+        }
+    }
+
+    if let Some((_, language, code)) = code {
+        segments.push(Segment::Code { language, code });
+    }
+    if !markdown.is_empty() {
+        segments.push(Segment::Markdown(markdown));
+    }
+    segments
+}
+
+/// リンク（`[テキスト](URL)`）を、termimad で表示できる形式に置き換える
+///
+/// termimad はリンクに対応していないため、テキストを斜体、URLをインラインコードとして
+/// 表示する。インラインコードの中のリンクは置き換えない。
+fn rewrite_links(markdown: &str) -> String {
+    let mut result = String::with_capacity(markdown.len());
+    let mut in_code = false;
+    let mut rest = markdown;
+
+    while let Some(c) = rest.chars().next() {
+        if c == '`' {
+            in_code = !in_code;
+        } else if c == '[' && !in_code {
+            let link = rest[1..].split_once("](").and_then(|(label, after)| {
+                let (url, remaining) = after.split_once(')')?;
+                let valid = !label.contains(['[', ']', '\n']) && !url.contains([' ', '\n']);
+                valid.then_some((label, url, remaining))
+            });
+            if let Some((label, url, remaining)) = link {
+                result.push_str(&format!("*{}* (`{}`)", label, url));
+                rest = remaining;
+                continue;
+            }
+        }
+        result.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    result
+}
+
+/// コードブロックをシンタックスハイライトする
+///
+/// 言語名が指定されていない場合や、対応していない言語の場合はハイライトせずに表示する。
+fn highlight_code(language: &str, code: &str) -> String {
+    let syntax = SYNTAX_SET
+        .find_syntax_by_token(language)
+        .unwrap_or_else(|| SYNTAX_SET.find_syntax_plain_text());
+    let mut highlighter = HighlightLines::new(syntax, &THEME);
+    let mut result = String::new();
+    for line in LinesWithEndings::from(code) {
+        match highlighter.highlight_line(line, &SYNTAX_SET) {
+            Ok(ranges) => result.push_str(&as_24_bit_terminal_escaped(&ranges, false)),
+            Err(_) => result.push_str(line),
+        }
+    }
+    result.push_str(RESET_STYLE);
+    result
+}
+
+/// Markdownのテキストを、ANSIスタイル付きのターミナル表示用の文字列に変換する
+///
+/// # Arguments
+/// * `text` - Markdownのテキスト
+/// * `width` - 表示幅（桁数）
+pub fn render_markdown(text: &str, width: usize) -> String {
+    let skin = MadSkin::default();
+    let mut rendered = String::new();
+    for segment in split_code_blocks(text) {
+        match segment {
+            Segment::Markdown(markdown) => {
+                let markdown = rewrite_links(&markdown);
+                rendered.push_str(&skin.text(&markdown, Some(width)).to_string());
+            }
+            Segment::Code { language, code } => {
+                rendered.push_str(&highlight_code(language, &code));
+            }
+        }
+    }
+    rendered.trim_end().to_string()
+}
+
+/// テキストをターミナルに表示したときの行数を計算する
+///
+/// # Arguments
+/// * `text` - 表示したテキスト
+/// * `start_column` - 1行目の表示を開始した桁（見出しの表示幅）
+/// * `width` - ターミナルの幅（桁数）
+fn displayed_rows(text: &str, start_column: usize, width: usize) -> usize {
+    let width = width.max(1);
+    text.split('\n')
+        .enumerate()
+        .map(|(index, line)| {
+            let offset = if index == 0 { start_column } else { 0 };
+            (offset + line.width()).div_ceil(width).max(1)
+        })
+        .sum()
+}
+
+/// 応答の受信完了後に、表示した生テキストをMarkdownとして整形し直すシンク
+///
+/// 受信中のテキストはそのまま内側のシンクへ書き出し、[`OutputSink::finish_message`] で
+/// 表示した行を消去してから整形した内容を書き出す。
+///
+/// # Note
+/// 生テキストがターミナルの高さを超えた場合は、画面外の行を消去できないため整形しない。
+pub struct MarkdownSink<S> {
+    inner: S,
+    text: String,
+}
+
+impl<S: OutputSink> MarkdownSink<S> {
+    /// 内側のシンクを指定して作成する
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            text: String::new(),
+        }
+    }
+
+    /// 表示した生テキストを消去し、整形した内容を書き出す
+    ///
+    /// # Arguments
+    /// * `prompt` - 応答の前に表示した見出し
+    /// * `size` - ターミナルの幅と高さ（桁数・行数）
+    fn rerender(&mut self, prompt: &str, (width, height): (usize, usize)) -> io::Result<()> {
+        let text = std::mem::take(&mut self.text);
+        let rows = displayed_rows(&text, prompt.width(), width);
+        if text.trim().is_empty() || rows >= height {
+            return Ok(());
+        }
+
+        let rendered = render_markdown(&text, width);
+        let mut output = String::new();
+        // 表示した生テキストの1行目に戻り、以降を消去する
+        if rows > 1 {
+            output.push_str(&format!("\x1b[{}A", rows - 1));
+        }
+        output.push_str("\r\x1b[J");
+        output.push_str(prompt);
+        if rendered.contains('\n') {
+            output.push('\n');
+        }
+        output.push_str(&rendered);
+        self.inner.write_text(&output)?;
+        self.inner.flush()
+    }
+}
+
+impl<S: OutputSink> OutputSink for MarkdownSink<S> {
+    fn write_text(&mut self, text: &str) -> io::Result<()> {
+        self.text.push_str(text);
+        self.inner.write_text(text)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+
+    fn finish_message(&mut self, prompt: &str) -> io::Result<()> {
+        self.inner.finish_message(prompt)?;
+        let (width, height) = termimad::terminal_size();
+        self.rerender(prompt, (usize::from(width), usize::from(height)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::BufferSink;
+
+    #[test]
+    fn test_split_code_blocks() {
+        let text = "# 見出し\n説明\n```rust\nfn main() {}\n```\n続き\n~~~\n閉じていない";
+        assert_eq!(
+            split_code_blocks(text),
+            vec![
+                Segment::Markdown("# 見出し\n説明\n".to_string()),
+                Segment::Code {
+                    language: "rust",
+                    code: "fn main() {}\n".to_string()
+                },
+                Segment::Markdown("続き\n".to_string()),
+                Segment::Code {
+                    language: "",
+                    code: "閉じていない".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rewrite_links() {
+        assert_eq!(
+            rewrite_links("詳細は [公式サイト](https://example.com) を参照"),
+            "詳細は *公式サイト* (`https://example.com`) を参照"
+        );
+        // インラインコードの中や、リンクの形式でないものは置き換えない
+        assert_eq!(rewrite_links("`[a](b)` と [c] (d)"), "`[a](b)` と [c] (d)");
+    }
+
+    #[test]
+    fn test_render_markdown_applies_styles() {
+        let rendered = render_markdown("**太字** です\n\n```rust\nlet x = 1;\n```\n", 80);
+        assert!(rendered.contains("太字"));
+        assert!(!rendered.contains("**"));
+        assert!(!rendered.contains("```"));
+        assert!(rendered.contains("\x1b["));
+        assert!(rendered.ends_with(RESET_STYLE));
+    }
+
+    #[test]
+    fn test_displayed_rows() {
+        // 見出しの後に続く1行目と、折り返し、全角文字の幅を考慮する
+        assert_eq!(displayed_rows("abc", 12, 80), 1);
+        assert_eq!(displayed_rows("a".repeat(70).as_str(), 12, 80), 2);
+        assert_eq!(displayed_rows("あいう\n\nxyz", 0, 4), 2 + 1 + 1);
+        assert_eq!(displayed_rows("abc\n", 0, 80), 2);
+    }
+
+    #[test]
+    fn test_markdown_sink_rerenders_after_message() {
+        let mut sink = MarkdownSink::new(BufferSink::new());
+        sink.write_text("# タイトル\n").unwrap();
+        sink.write_text("本文").unwrap();
+        sink.rerender("Assistant > ", (80, 24)).unwrap();
+        let output = sink.inner.into_text();
+        let (raw, rerendered) = output.split_once("\x1b[1A\r\x1b[J").unwrap();
+        assert_eq!(raw, "# タイトル\n本文");
+        assert!(rerendered.starts_with("Assistant > \n"));
+        assert!(!rerendered.contains('#'));
+
+        // 画面に収まらない場合は整形し直さない
+        let mut sink = MarkdownSink::new(BufferSink::new());
+        sink.write_text(&"行\n".repeat(30)).unwrap();
+        sink.rerender("", (80, 24)).unwrap();
+        assert_eq!(sink.inner.into_text(), "行\n".repeat(30));
+    }
+}
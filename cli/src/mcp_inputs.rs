@@ -0,0 +1,108 @@
+/// mcp.jsonの入力プロンプト（`inputs`）の入力
+///
+/// サーバーの設定で `${input:<id>}` が参照されている場合、対応する `inputs` の定義の
+/// `description` を表示してユーザーに値を尋ねる。入力した値はCLIの終了まで保持し、
+/// 同じ入力を参照するサーバーに接続し直しても再度は尋ねない。
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+
+use anyhow::{Context, Result};
+use mcp::{McpConfig, ServerConfig};
+use rustyline::completion::Completer;
+use rustyline::config::Configurer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::{CmdKind, Highlighter};
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{ColorMode, Editor, Helper};
+
+/// 入力した値のキャッシュ（入力IDごと）
+static INPUT_VALUES: LazyLock<Mutex<HashMap<String, String>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// `password: true` の入力で、入力中の文字を `*` に置き換えて表示する rustyline ヘルパー
+struct MaskingHelper;
+
+impl Highlighter for MaskingHelper {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        Cow::Owned("*".repeat(line.chars().count()))
+    }
+
+    fn highlight_char(&self, _line: &str, _pos: usize, kind: CmdKind) -> bool {
+        kind != CmdKind::MoveCursor
+    }
+}
+
+impl Completer for MaskingHelper {
+    type Candidate = String;
+}
+
+impl Hinter for MaskingHelper {
+    type Hint = String;
+}
+
+impl Validator for MaskingHelper {}
+
+impl Helper for MaskingHelper {}
+
+/// サーバーの設定が参照する入力の値を尋ね、`${input:<id>}` を置き換えた設定を返す
+///
+/// 入力済みの値はキャッシュから使用する。
+///
+/// # Returns
+/// * `Ok(Some(ServerConfig))` - 入力の参照を置き換えた設定
+/// * `Ok(None)` - Ctrl-C/Ctrl-D で入力が中断された場合
+/// * `Err` - 参照された入力が `inputs` に定義されていない場合や、入力に失敗した場合
+pub fn resolve_server_inputs(
+    config: &McpConfig,
+    server: &ServerConfig,
+) -> Result<Option<ServerConfig>> {
+    let ids = server.input_ids();
+    if ids.is_empty() {
+        return Ok(Some(server.clone()));
+    }
+
+    let mut cache = INPUT_VALUES.lock().unwrap_or_else(|e| e.into_inner());
+    for id in ids {
+        if cache.contains_key(&id) {
+            continue;
+        }
+        let input = config
+            .get_input(&id)
+            .with_context(|| format!("入力 '{}' が inputs に定義されていません", id))?;
+        let Some(value) = read_input(&input.description, input.password)? else {
+            return Ok(None);
+        };
+        cache.insert(id, value);
+    }
+    Ok(Some(server.with_inputs(&cache)))
+}
+
+/// 入力の値を1つ尋ねる
+///
+/// # Arguments
+/// * `description` - プロンプトとして表示する説明文
+/// * `password` - 入力を表示しないか
+///
+/// # Returns
+/// * `Ok(Some(String))` - 入力された値
+/// * `Ok(None)` - Ctrl-C/Ctrl-D で入力が中断された場合
+fn read_input(description: &str, password: bool) -> Result<Option<String>> {
+    let mut editor: Editor<MaskingHelper, DefaultHistory> =
+        Editor::new().context("Failed to initialize rustyline editor")?;
+    // 入力した値を履歴に残さない
+    editor.set_auto_add_history(false);
+    if password {
+        editor.set_helper(Some(MaskingHelper));
+        // 端末の判定によらず、必ずマスクして表示する
+        editor.set_color_mode(ColorMode::Forced);
+    }
+
+    match editor.readline(&format!("🔑 {}: ", description)) {
+        Ok(line) => Ok(Some(line.trim().to_string())),
+        Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => Ok(None),
+        Err(err) => Err(err).context("入力に失敗しました"),
+    }
+}
@@ -0,0 +1,141 @@
+/// 起動時の必須のMCPサーバーへの接続
+///
+/// 設定ファイルで `required: true` を指定したサーバーを、同時に最大
+/// `--mcp-connect-concurrency` 個まで並行して起動し、接続が完了した順に結果を表示する。
+/// 接続に失敗したサーバーがあっても残りのサーバーへの接続を続け、すべて完了した後に
+/// 失敗したサーバーがあれば起動を中止する。
+use std::future::Future;
+use std::sync::Arc;
+
+use agent::AgentClient;
+use anyhow::{Context, Result};
+use mcp::McpConfig;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+use crate::{
+    McpLaunchSettings, attach_mcp_server, current_workspace_folder, launch_mcp_server,
+    prepare_mcp_server, print_connection_failure, print_launch_command,
+};
+
+/// 起動時に同時に接続するMCPサーバーの数のデフォルト
+pub const DEFAULT_MCP_CONNECT_CONCURRENCY: usize = 4;
+
+/// 設定ファイルで必須とされたすべてのMCPサーバーに、サーバー名を付けて接続する
+///
+/// # Arguments
+/// * `agent` - 接続したクライアントを追加するAgentClient
+/// * `config` - MCP設定
+/// * `launch` - サーバーの起動時の設定
+/// * `concurrency` - 同時に接続するサーバーの数の上限
+///
+/// # Errors
+/// 必須のサーバーのいずれかに接続できなかった場合（起動を中止する）
+pub async fn connect_required_mcp_servers(
+    agent: &mut AgentClient,
+    config: &McpConfig,
+    launch: McpLaunchSettings,
+    concurrency: usize,
+) -> Result<()> {
+    let workspace_folder = current_workspace_folder();
+    let mut failed = Vec::new();
+
+    // 入力の値を尋ねることがあるため、接続の準備はサーバーごとに順に行う
+    let mut servers = Vec::new();
+    for name in config.required_server_names() {
+        let Some(server) = prepare_mcp_server(config, name) else {
+            failed.push(name.to_string());
+            continue;
+        };
+        if launch.show_launch {
+            println!("MCPサーバー '{}' の起動設定:", name);
+            print_launch_command(&server, workspace_folder.as_deref());
+        }
+        servers.push((name.to_string(), server));
+    }
+
+    let connections = servers.into_iter().map(|(name, server)| {
+        let workspace_folder = workspace_folder.clone();
+        async move {
+            println!("MCPサーバー '{}' に接続中...", name);
+            let result = launch_mcp_server(&server, workspace_folder.as_deref(), launch).await;
+            (name, server, result)
+        }
+    });
+    let mut tasks = spawn_limited(connections, concurrency);
+    while let Some(joined) = tasks.join_next().await {
+        let (name, server, result) = joined.context("MCPサーバーへの接続処理が異常終了しました")?;
+        match result {
+            Ok(client) => attach_mcp_server(agent, &name, client).await,
+            Err(e) => {
+                print_connection_failure(&name, &server, workspace_folder.as_deref(), &e);
+                failed.push(name);
+            }
+        }
+    }
+
+    if !failed.is_empty() {
+        failed.sort_unstable();
+        anyhow::bail!(
+            "必須のMCPサーバーに接続できませんでした（{}）。起動を中止します。",
+            failed.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// 同時に最大 `limit` 個まで実行されるよう、セマフォで制限してタスクを起動する
+///
+/// 結果は [`JoinSet::join_next`] で完了した順に受け取る。
+fn spawn_limited<I, F>(futures: I, limit: usize) -> JoinSet<F::Output>
+where
+    I: IntoIterator<Item = F>,
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let semaphore = Arc::new(Semaphore::new(limit.max(1)));
+    let mut tasks = JoinSet::new();
+    for future in futures {
+        let semaphore = Arc::clone(&semaphore);
+        tasks.spawn(async move {
+            // セマフォは閉じないため、許可の取得は失敗しない（タスクの終了まで許可を保持する）
+            let _permit = semaphore.acquire_owned().await;
+            future.await
+        });
+    }
+    tasks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_spawn_limited_runs_at_most_limit_tasks() {
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+        let futures = (0..8).map(|i| {
+            let running = Arc::clone(&running);
+            let max_running = Arc::clone(&max_running);
+            async move {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                max_running.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+                // 失敗したタスクがあっても、残りのタスクは実行を続ける
+                if i % 3 == 0 { Err(i) } else { Ok(i) }
+            }
+        });
+
+        let mut tasks = spawn_limited(futures, 3);
+        let mut completed = Vec::new();
+        while let Some(joined) = tasks.join_next().await {
+            completed.push(joined.unwrap().unwrap_or_else(|i| i));
+        }
+        completed.sort_unstable();
+        assert_eq!(completed, (0..8).collect::<Vec<_>>());
+        assert_eq!(max_running.load(Ordering::SeqCst), 3);
+    }
+}
@@ -0,0 +1,158 @@
+/// 利用可能な基盤モデルの一覧表示
+///
+/// `models` でアカウントが使用中のリージョンで利用できる基盤モデルを取得し、
+/// モデルID・プロバイダ名・入出力のモダリティを表形式で表示する。
+/// `--model` に指定できるモデルIDを調べるために使用する。
+use anyhow::{Context, Result};
+use clap::Args;
+use unicode_width::UnicodeWidthStr;
+
+use agent::{FoundationModelInfo, ModelListFilter};
+
+use crate::{ClientArgs, preflight_credentials};
+
+/// 表の列の区切り
+const COLUMN_SEPARATOR: &str = "  ";
+
+/// `models` サブコマンドの引数
+#[derive(Args)]
+pub struct ModelsArgs {
+    #[command(flatten)]
+    client: ClientArgs,
+
+    /// プロバイダ名で絞り込む（例: Anthropic）
+    #[arg(long, value_name = "NAME")]
+    provider: Option<String>,
+
+    /// ストリーミングに対応するモデルのみ表示する
+    #[arg(long)]
+    streaming: bool,
+
+    /// Converse APIに対応する（テキストを入出力する）モデルのみ表示する
+    #[arg(long)]
+    converse: bool,
+}
+
+/// 利用可能な基盤モデルの一覧を表示する
+pub async fn run_models(args: ModelsArgs) -> Result<()> {
+    let agent = args.client.connect().await?;
+    preflight_credentials(&agent).await?;
+
+    let filter = ModelListFilter {
+        provider: args.provider,
+        streaming_only: args.streaming,
+        converse_only: args.converse,
+    };
+    let models = agent
+        .list_foundation_models(&filter)
+        .await
+        .context("基盤モデルの一覧の取得に失敗しました")?;
+    if models.is_empty() {
+        println!(
+            "条件に合う基盤モデルがありません（リージョン: {}）。",
+            agent.region()
+        );
+    } else {
+        println!("{}", format_model_table(&models));
+        println!();
+        println!(
+            "{} 件の基盤モデル（リージョン: {}）",
+            models.len(),
+            agent.region()
+        );
+    }
+    Ok(())
+}
+
+/// 基盤モデルの一覧を、列の幅をそろえた表にする
+fn format_model_table(models: &[FoundationModelInfo]) -> String {
+    let header = ["モデルID", "プロバイダ", "入力", "出力", "ストリーミング"];
+    let rows: Vec<[String; 5]> = models
+        .iter()
+        .map(|model| {
+            [
+                model.model_id.clone(),
+                model
+                    .provider_name
+                    .clone()
+                    .unwrap_or_else(|| "-".to_string()),
+                model.input_modalities.join(","),
+                model.output_modalities.join(","),
+                if model.response_streaming_supported {
+                    "○".to_string()
+                } else {
+                    "-".to_string()
+                },
+            ]
+        })
+        .collect();
+
+    let mut widths = header.map(UnicodeWidthStr::width);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.width());
+        }
+    }
+
+    let format_row = |cells: [&str; 5]| -> String {
+        let line: Vec<String> = cells
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{}{}", cell, " ".repeat(width - cell.width())))
+            .collect();
+        line.join(COLUMN_SEPARATOR).trim_end().to_string()
+    };
+    let mut lines = vec![format_row(header)];
+    lines.push(format_row(
+        widths
+            .map(|width| "-".repeat(width))
+            .each_ref()
+            .map(String::as_str),
+    ));
+    for row in &rows {
+        lines.push(format_row(row.each_ref().map(String::as_str)));
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_model_table_aligns_columns() {
+        let models = vec![
+            FoundationModelInfo {
+                model_id: "anthropic.claude-3-haiku-20240307-v1:0".to_string(),
+                model_name: Some("Claude 3 Haiku".to_string()),
+                provider_name: Some("Anthropic".to_string()),
+                input_modalities: vec!["TEXT".to_string(), "IMAGE".to_string()],
+                output_modalities: vec!["TEXT".to_string()],
+                response_streaming_supported: true,
+                inference_types: vec!["ON_DEMAND".to_string()],
+            },
+            FoundationModelInfo {
+                model_id: "amazon.titan-embed-text-v2:0".to_string(),
+                model_name: None,
+                provider_name: None,
+                input_modalities: vec!["TEXT".to_string()],
+                output_modalities: vec!["EMBEDDING".to_string()],
+                response_streaming_supported: false,
+                inference_types: vec![],
+            },
+        ];
+        let table = format_model_table(&models);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("モデルID"));
+        assert!(lines[1].starts_with("-----"));
+        assert!(lines[2].starts_with("anthropic.claude-3-haiku-20240307-v1:0  Anthropic"));
+        assert!(lines[2].ends_with("TEXT,IMAGE  TEXT       ○"));
+        assert!(lines[3].starts_with("amazon.titan-embed-text-v2:0            -"));
+        assert!(lines[3].ends_with("EMBEDDING  -"));
+        // 全角の見出しも表示幅で列をそろえる
+        let header_column = lines[0].find("プロバイダ").unwrap();
+        assert_eq!(lines[0][..header_column].width(), 40);
+        assert_eq!(lines[2].find("Anthropic"), Some(40));
+    }
+}
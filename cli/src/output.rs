@@ -0,0 +1,293 @@
+/// 応答テキストの出力先（シンク）
+///
+/// ストリーミングされたアシスタントの応答を、ターミナルやメモリ上のバッファなど
+/// 複数の出力先へ同時に書き出すための抽象化を提供する。
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+/// 出力をまとめて書き出す間隔のデフォルト（約60fps）
+pub const DEFAULT_FLUSH_INTERVAL: Duration = Duration::from_millis(16);
+
+/// 応答テキストの出力先を表すトレイト
+///
+/// 会話処理を別タスクで実行できるよう、スレッド間で送信可能であることを要求する。
+pub trait OutputSink: Send {
+    /// テキスト差分を書き出す
+    fn write_text(&mut self, text: &str) -> io::Result<()>;
+
+    /// バッファリングされた出力を確定する
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// 応答1件分の受信が完了したことを通知する
+    ///
+    /// 既定では `flush` と同じ。受信した応答全体を表示し直すシンクが使用する。
+    ///
+    /// # Arguments
+    /// * `prompt` - 応答の前に表示した見出し（表示していない場合は空文字列）
+    fn finish_message(&mut self, _prompt: &str) -> io::Result<()> {
+        self.flush()
+    }
+}
+
+/// 標準出力へ逐次書き出すシンク
+pub struct StdoutSink;
+
+impl OutputSink for StdoutSink {
+    fn write_text(&mut self, text: &str) -> io::Result<()> {
+        let mut stdout = io::stdout();
+        stdout.write_all(text.as_bytes())?;
+        stdout.flush()
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stdout().flush()
+    }
+}
+
+/// 標準エラー出力へ逐次書き出すシンク
+///
+/// JSON出力時など、標準出力を汚さずに進捗を表示したい場合に使用する。
+pub struct StderrSink;
+
+impl OutputSink for StderrSink {
+    fn write_text(&mut self, text: &str) -> io::Result<()> {
+        let mut stderr = io::stderr();
+        stderr.write_all(text.as_bytes())?;
+        stderr.flush()
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()
+    }
+}
+
+/// メモリ上に応答テキストを蓄積するシンク
+#[derive(Debug, Default)]
+pub struct BufferSink {
+    buffer: String,
+}
+
+impl BufferSink {
+    /// 空のバッファを作成する
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 蓄積したテキストを取り出す
+    pub fn into_text(self) -> String {
+        self.buffer
+    }
+}
+
+impl OutputSink for BufferSink {
+    fn write_text(&mut self, text: &str) -> io::Result<()> {
+        self.buffer.push_str(text);
+        Ok(())
+    }
+}
+
+/// テキスト差分をまとめて書き出すシンク
+///
+/// 差分ごとに書き出すと、細かい差分が高頻度で届くストリームでは表示のちらつきや
+/// システムコールの増加を招くため、前回の書き出しから `interval` が経過するまで
+/// 差分をバッファに溜め、まとめて内側のシンクへ書き出す。
+///
+/// # Note
+/// 溜めた差分は、次の差分の受信時（間隔経過後）または `flush` の呼び出し時に書き出される。
+/// `interval` が0の場合は差分ごとにそのまま書き出す。
+pub struct CoalescingSink<S> {
+    inner: S,
+    interval: Duration,
+    pending: String,
+    last_write: Option<Instant>,
+}
+
+impl<S: OutputSink> CoalescingSink<S> {
+    /// 内側のシンクと書き出し間隔を指定して作成する
+    pub fn new(inner: S, interval: Duration) -> Self {
+        Self {
+            inner,
+            interval,
+            pending: String::new(),
+            last_write: None,
+        }
+    }
+
+    /// 溜めている差分を内側のシンクへ書き出す
+    fn write_pending(&mut self) -> io::Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        self.inner.write_text(&self.pending)?;
+        self.pending.clear();
+        self.last_write = Some(Instant::now());
+        Ok(())
+    }
+}
+
+impl<S: OutputSink> OutputSink for CoalescingSink<S> {
+    fn write_text(&mut self, text: &str) -> io::Result<()> {
+        self.pending.push_str(text);
+        let due = self
+            .last_write
+            .is_none_or(|last| last.elapsed() >= self.interval);
+        if due {
+            self.write_pending()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_pending()?;
+        self.inner.flush()
+    }
+
+    fn finish_message(&mut self, prompt: &str) -> io::Result<()> {
+        self.write_pending()?;
+        self.inner.finish_message(prompt)
+    }
+}
+
+/// 複数のシンクへ同じテキストを書き出すシンク
+#[derive(Default)]
+pub struct MultiSink<'a> {
+    sinks: Vec<&'a mut dyn OutputSink>,
+}
+
+impl<'a> MultiSink<'a> {
+    /// 空の `MultiSink` を作成する
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 出力先を追加する
+    pub fn with(mut self, sink: &'a mut dyn OutputSink) -> Self {
+        self.sinks.push(sink);
+        self
+    }
+}
+
+impl OutputSink for MultiSink<'_> {
+    fn write_text(&mut self, text: &str) -> io::Result<()> {
+        for sink in self.sinks.iter_mut() {
+            sink.write_text(text)?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        for sink in self.sinks.iter_mut() {
+            sink.flush()?;
+        }
+        Ok(())
+    }
+
+    fn finish_message(&mut self, prompt: &str) -> io::Result<()> {
+        for sink in self.sinks.iter_mut() {
+            sink.finish_message(prompt)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use agent::TurnAccumulator;
+    use aws_sdk_bedrockruntime::types::{
+        ContentBlockDelta, ContentBlockDeltaEvent, ContentBlockStopEvent, ConverseStreamOutput,
+    };
+
+    /// テキスト差分のみで構成されたモックストリームを作成する
+    fn mock_stream(deltas: &[&str]) -> Vec<ConverseStreamOutput> {
+        let mut events: Vec<ConverseStreamOutput> = deltas
+            .iter()
+            .map(|text| {
+                ConverseStreamOutput::ContentBlockDelta(
+                    ContentBlockDeltaEvent::builder()
+                        .content_block_index(0)
+                        .delta(ContentBlockDelta::Text(text.to_string()))
+                        .build()
+                        .unwrap(),
+                )
+            })
+            .collect();
+        events.push(ConverseStreamOutput::ContentBlockStop(
+            ContentBlockStopEvent::builder()
+                .content_block_index(0)
+                .build()
+                .unwrap(),
+        ));
+        events
+    }
+
+    /// 内側のシンクへの書き出し回数を数えるシンク
+    #[derive(Default)]
+    struct CountingSink {
+        text: String,
+        writes: usize,
+    }
+
+    impl OutputSink for &mut CountingSink {
+        fn write_text(&mut self, text: &str) -> io::Result<()> {
+            self.text.push_str(text);
+            self.writes += 1;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_coalescing_sink_reduces_writes() {
+        let deltas: Vec<String> = (0..100).map(|i| format!("{} ", i)).collect();
+
+        let mut immediate = CountingSink::default();
+        let mut coalesced = CountingSink::default();
+        {
+            let mut immediate_sink = CoalescingSink::new(&mut immediate, Duration::ZERO);
+            let mut coalesced_sink = CoalescingSink::new(&mut coalesced, Duration::from_secs(60));
+            for delta in &deltas {
+                immediate_sink.write_text(delta).unwrap();
+                coalesced_sink.write_text(delta).unwrap();
+            }
+            immediate_sink.flush().unwrap();
+            coalesced_sink.flush().unwrap();
+        }
+
+        // 最初の差分は即座に書き出し、残りは flush でまとめて書き出す
+        assert_eq!(immediate.writes, 100);
+        assert_eq!(coalesced.writes, 2);
+        assert_eq!(coalesced.text, deltas.concat());
+        assert_eq!(immediate.text, coalesced.text);
+    }
+
+    #[test]
+    fn test_buffered_text_matches_streamed_deltas() {
+        let deltas = [
+            "Rust",
+            "は",
+            "安全な",
+            "言語です。\n",
+            "```rust\nfn main() {}\n```",
+        ];
+        let mut terminal = BufferSink::new();
+        let mut buffer = BufferSink::new();
+        let mut accumulator = TurnAccumulator::new();
+
+        {
+            let mut sink = MultiSink::new().with(&mut terminal).with(&mut buffer);
+            for event in mock_stream(&deltas) {
+                if let Some(text) = accumulator.push_event(event).unwrap() {
+                    sink.write_text(&text).unwrap();
+                }
+            }
+            sink.flush().unwrap();
+        }
+
+        let expected = deltas.concat();
+        assert_eq!(buffer.into_text(), expected);
+        assert_eq!(terminal.into_text(), expected);
+        assert_eq!(accumulator.text(), expected);
+    }
+}
@@ -0,0 +1,171 @@
+/// 保存した会話のユーザー入力の再実行（リプレイ）
+///
+/// `replay` でトランスクリプトからユーザーの入力だけを取り出し、新しいセッションに
+/// 順に送信して新しいトランスクリプトを作成する。モデルやシステムプロンプトを変えたときに、
+/// 同じ入力の流れに対する応答がどう変わるかを確認する回帰テストに使用する。
+use std::path::PathBuf;
+use std::time::Duration;
+
+use agent::{AgentClient, AgentError};
+use anyhow::{Context, Result};
+use clap::Args;
+use mcp::McpConfig;
+
+use crate::output::{CoalescingSink, DEFAULT_FLUSH_INTERVAL, StdoutSink};
+use crate::transcript::{load_transcript, print_transcript_diff};
+use crate::{
+    AGENT_NAME, ClientArgs, McpLaunchSettings, TurnBudget, TurnOptions,
+    handle_mcp_connection_command, preflight_credentials, print_error_guidance,
+    process_conversation_turn, within_turn_budget,
+};
+
+/// `replay` サブコマンドの引数
+#[derive(Args)]
+pub struct ReplayArgs {
+    /// 再実行するトランスクリプト（JSON）
+    transcript: PathBuf,
+
+    #[command(flatten)]
+    client: ClientArgs,
+
+    /// 新しいトランスクリプトの保存先
+    #[arg(long, value_name = "PATH")]
+    output: PathBuf,
+
+    /// 再実行の前に接続するMCPサーバー名（.chatbot/mcp.json・.vscode/mcp.jsonまたはmcp.jsonから読み込む）
+    #[arg(long, value_name = "SERVER")]
+    mcp: Option<String>,
+
+    /// 再実行後に、元のトランスクリプトとの差分を表示する
+    #[arg(long)]
+    diff: bool,
+
+    /// ツール実行やフォローアップを含む1ターン全体の制限時間（秒）
+    #[arg(long, value_name = "SECONDS")]
+    turn_timeout: Option<u64>,
+}
+
+/// トランスクリプトのユーザー入力を再実行する
+///
+/// 途中のターンが失敗した場合も、それまでの会話を新しいトランスクリプトとして保存してから
+/// エラーを返す。
+pub async fn run_replay(args: ReplayArgs) -> Result<()> {
+    let original = load_transcript(&args.transcript)?;
+    let inputs = original.user_inputs();
+    if inputs.is_empty() {
+        anyhow::bail!(
+            "再実行するユーザー入力がありません: {}",
+            args.transcript.display()
+        );
+    }
+
+    let mut agent = args.client.connect().await?;
+    preflight_credentials(&agent).await?;
+    // --system-prompt・--system-file を省略した場合は、元のシステムプロンプトを使用する
+    if agent.system_prompt().is_none()
+        && let Some(prompt) = original.system_prompt.clone()
+    {
+        agent.set_system_prompt(prompt);
+    }
+    if let Some(server_name) = &args.mcp {
+        connect_mcp_server(&mut agent, server_name).await?;
+    }
+
+    println!(
+        "{} ターンを再実行します（モデル: {}）。",
+        inputs.len(),
+        agent.model_id()
+    );
+    let turn_timeout = args.turn_timeout.map(Duration::from_secs);
+    let mut replay_result = Ok(());
+    for (index, input) in inputs.iter().enumerate() {
+        println!();
+        println!("[{}/{}] User > {}", index + 1, inputs.len(), input);
+        print!("{} > ", AGENT_NAME);
+        if let Err(e) = replay_turn(&mut agent, input, turn_timeout).await {
+            replay_result = Err(e.context(format!("ターン {} の再実行に失敗しました", index + 1)));
+            break;
+        }
+        println!();
+    }
+
+    let replayed = agent.transcript();
+    if let Err(e) = agent.shutdown().await {
+        eprintln!("警告: MCP切断に失敗しました: {}", e);
+    }
+
+    let replayed = replayed.context("会話履歴をトランスクリプトに変換できませんでした")?;
+    let json = serde_json::to_string_pretty(&replayed)?;
+    std::fs::write(&args.output, json).with_context(|| {
+        format!(
+            "トランスクリプトの保存に失敗しました: {}",
+            args.output.display()
+        )
+    })?;
+    println!();
+    println!("トランスクリプトを保存しました: {}", args.output.display());
+
+    if args.diff {
+        println!();
+        print_transcript_diff(&args.transcript, &original, &args.output, &replayed);
+    }
+    replay_result
+}
+
+/// 1ターン分のユーザー入力を送信し、ツール実行を含む応答を表示する
+///
+/// 失敗した場合は、次のターンに影響しないよう、このターンで追加したメッセージ
+/// （ツール使用とその結果を含む）を履歴から取り除く
+/// （制限時間を超えた場合は、途中までの応答が記録済みのため履歴を残す）。
+async fn replay_turn(
+    agent: &mut AgentClient,
+    input: &str,
+    turn_timeout: Option<Duration>,
+) -> Result<()> {
+    let budget = turn_timeout.map(TurnBudget::start);
+    let history_len = agent.history_len();
+    let result = match within_turn_budget(budget, agent.send_message(input))
+        .await
+        .and_then(|result| result)
+    {
+        Ok(response) => {
+            process_conversation_turn(
+                agent,
+                response,
+                None,
+                &mut CoalescingSink::new(StdoutSink, DEFAULT_FLUSH_INTERVAL),
+                TurnOptions {
+                    budget,
+                    ..TurnOptions::default()
+                },
+            )
+            .await
+        }
+        Err(e) => Err(e.into()),
+    };
+
+    if let Err(e) = &result {
+        println!();
+        let agent_error = e.downcast_ref::<AgentError>();
+        if let Some(agent_error) = agent_error {
+            print_error_guidance(agent_error);
+        }
+        if !matches!(agent_error, Some(AgentError::TurnTimeout { .. })) {
+            agent.rollback_to(history_len);
+        }
+    }
+    result.map(|_| ())
+}
+
+/// mcp.jsonに定義されたMCPサーバーに接続する
+async fn connect_mcp_server(agent: &mut AgentClient, server_name: &str) -> Result<()> {
+    let config = McpConfig::load_default()?.context(
+        "MCP設定ファイル（.chatbot/mcp.json・.vscode/mcp.jsonまたはmcp.json）が見つかりません",
+    )?;
+    handle_mcp_connection_command(agent, &config, server_name, McpLaunchSettings::default())
+        .await?;
+    if !agent.is_mcp_connected() {
+        anyhow::bail!("MCPサーバー '{}' に接続できませんでした", server_name);
+    }
+    Ok(())
+}
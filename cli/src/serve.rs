@@ -0,0 +1,246 @@
+/// WebSocketサーバー（`ws-server` フィーチャー）
+///
+/// `serve --ws` で起動し、WebSocketで受け取ったプロンプトに対する応答を
+/// JSONイベントとして逐次返す。接続ごとに独立した `AgentClient` のセッションを持つ。
+use std::io;
+use std::time::Duration;
+
+use agent::{AgentClient, AgentError};
+use anyhow::{Context, Result};
+use clap::Args;
+use futures_util::{SinkExt, StreamExt};
+use serde::Serialize;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::output::{BufferSink, MultiSink, OutputSink};
+use crate::{ClientArgs, TurnBudget, TurnOptions, process_conversation_turn, within_turn_budget};
+
+/// `serve` サブコマンドの引数
+#[derive(Args)]
+pub struct ServeArgs {
+    #[command(flatten)]
+    client: ClientArgs,
+
+    /// WebSocketで待ち受ける（現在はWebSocketのみ対応）
+    #[arg(long)]
+    ws: bool,
+
+    /// 待ち受けるアドレス
+    #[arg(long, default_value = "127.0.0.1")]
+    host: String,
+
+    /// 待ち受けるポート番号
+    #[arg(long, default_value_t = 8765)]
+    port: u16,
+
+    /// ツール実行やフォローアップを含む1ターン全体の制限時間（秒）
+    #[arg(long, value_name = "SECONDS")]
+    turn_timeout: Option<u64>,
+}
+
+/// クライアントへ送信するイベント（1メッセージにつき1つのJSONオブジェクト）
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ServerEvent<'a> {
+    /// 応答テキストの差分
+    Text { text: &'a str },
+
+    /// ターンの完了
+    Done { model: &'a str, text: &'a str },
+
+    /// ターンの失敗
+    Error { message: String },
+}
+
+/// 応答テキストの差分をイベントとして送信するシンク
+///
+/// 送信はチャネル経由で行い、WebSocketへの書き込みは別タスクが担当する。
+struct EventSink {
+    tx: mpsc::UnboundedSender<String>,
+}
+
+impl EventSink {
+    /// イベントをJSONに変換して送信する
+    fn send(&self, event: &ServerEvent) -> io::Result<()> {
+        let json = serde_json::to_string(event)?;
+        self.tx
+            .send(json)
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "WebSocket connection closed"))
+    }
+}
+
+impl OutputSink for EventSink {
+    fn write_text(&mut self, text: &str) -> io::Result<()> {
+        self.send(&ServerEvent::Text { text })
+    }
+}
+
+/// WebSocketサーバーを起動し、終了シグナルを受信するまで接続を受け付ける
+pub async fn run_serve(args: ServeArgs) -> Result<()> {
+    if !args.ws {
+        anyhow::bail!("待ち受ける方式を指定してください（現在は --ws のみ対応しています）");
+    }
+
+    let listener = TcpListener::bind((args.host.as_str(), args.port))
+        .await
+        .with_context(|| format!("{}:{} で待ち受けできませんでした", args.host, args.port))?;
+    println!(
+        "WebSocketサーバーを起動しました: ws://{}",
+        listener.local_addr()?
+    );
+
+    let turn_timeout = args.turn_timeout.map(Duration::from_secs);
+    // サーバーでは生成をCtrl-Cで中断しないため、Ctrl-Cは常に終了として扱う
+    let generation = crate::GenerationInterrupt::default();
+    let shutdown = crate::shutdown_signal(&generation);
+    tokio::pin!(shutdown);
+
+    loop {
+        let (stream, peer) = tokio::select! {
+            accepted = listener.accept() => accepted.context("接続の受け付けに失敗しました")?,
+            _ = &mut shutdown => {
+                println!("終了シグナルを受信しました。サーバーを停止します。");
+                break;
+            }
+        };
+
+        let client = args.client.clone();
+        tokio::spawn(async move {
+            println!("接続を受け付けました: {}", peer);
+            if let Err(e) = handle_connection(stream, client, turn_timeout).await {
+                eprintln!("警告: 接続 {} の処理に失敗しました: {:#}", peer, e);
+            }
+            println!("接続が終了しました: {}", peer);
+        });
+    }
+
+    Ok(())
+}
+
+/// 1つのWebSocket接続を処理する
+///
+/// テキストメッセージをプロンプトとして受け取り、接続ごとの会話履歴を保ったまま応答する。
+async fn handle_connection(
+    stream: TcpStream,
+    client: ClientArgs,
+    turn_timeout: Option<Duration>,
+) -> Result<()> {
+    let ws = tokio_tungstenite::accept_async(stream)
+        .await
+        .context("WebSocketのハンドシェイクに失敗しました")?;
+    let (mut writer, mut reader) = ws.split();
+
+    // イベントの書き込みを担当するタスク
+    let (tx, mut rx) = mpsc::unbounded_channel::<String>();
+    let forward_task = tokio::spawn(async move {
+        while let Some(event) = rx.recv().await {
+            if writer.send(Message::text(event)).await.is_err() {
+                break;
+            }
+        }
+        let _ = writer.close().await;
+    });
+
+    let mut events = EventSink { tx };
+    let result = async {
+        let mut agent = match client.connect().await {
+            Ok(agent) => agent,
+            Err(e) => {
+                let _ = events.send(&ServerEvent::Error {
+                    message: format!("{:#}", e),
+                });
+                return Err(e);
+            }
+        };
+
+        while let Some(message) = reader.next().await {
+            let prompt = match message.context("WebSocketの受信に失敗しました")? {
+                Message::Text(text) => text.to_string(),
+                Message::Close(_) => break,
+                // Ping/Pongはライブラリが応答するため、それ以外は無視する
+                _ => continue,
+            };
+            if prompt.trim().is_empty() {
+                continue;
+            }
+
+            run_turn(&mut agent, prompt.trim(), &mut events, turn_timeout).await?;
+        }
+        Ok(())
+    }
+    .await;
+
+    // 送信側を閉じて、書き込み済みのイベントを送り切ってから終了する
+    drop(events);
+    let _ = forward_task.await;
+    result
+}
+
+/// 1ターン分の会話を処理し、結果をイベントとして送信する
+///
+/// # Returns
+/// * `Ok(())` - ターンが完了した場合、またはエラーをイベントとして送信した場合
+/// * `Err` - 接続が閉じられてイベントを送信できない場合
+async fn run_turn(
+    agent: &mut AgentClient,
+    prompt: &str,
+    events: &mut EventSink,
+    turn_timeout: Option<Duration>,
+) -> Result<()> {
+    let budget = turn_timeout.map(TurnBudget::start);
+    let mut buffer = BufferSink::new();
+    // 失敗した場合に、ターン全体を取り消すための送信前の件数
+    let history_len = agent.history_len();
+
+    let response = match within_turn_budget(budget, agent.send_message(prompt))
+        .await
+        .and_then(|result| result)
+    {
+        Ok(response) => response,
+        Err(e) => {
+            // エラー時はユーザーメッセージを履歴から削除
+            agent.rollback_to(history_len);
+            events.send(&ServerEvent::Error {
+                message: e.to_string(),
+            })?;
+            return Ok(());
+        }
+    };
+
+    let result = {
+        let mut sink = MultiSink::new().with(&mut buffer).with(events);
+        process_conversation_turn(
+            agent,
+            response,
+            None,
+            &mut sink,
+            TurnOptions {
+                budget,
+                ..TurnOptions::default()
+            },
+        )
+        .await
+    };
+
+    match result {
+        Ok(_) => events.send(&ServerEvent::Done {
+            model: agent.last_model_id(),
+            text: &buffer.into_text(),
+        })?,
+        Err(e) => {
+            // 制限時間超過の場合は途中までの応答が記録済みのため、履歴を残す。
+            // それ以外は、結果のないツール使用が残って以降のプロンプトを送信できなく
+            // ならないよう、ツール使用とその結果を含めてターン全体を取り消す
+            let agent_error = e.downcast_ref::<AgentError>();
+            if !matches!(agent_error, Some(AgentError::TurnTimeout { .. })) {
+                agent.rollback_to(history_len);
+            }
+            events.send(&ServerEvent::Error {
+                message: format!("{:#}", e),
+            })?;
+        }
+    }
+    Ok(())
+}
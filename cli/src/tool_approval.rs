@@ -0,0 +1,96 @@
+/// ツール実行前のユーザーの承認
+///
+/// ファイルの削除やコミットなどの破壊的な操作を行うツールが確認なしに実行されないよう、
+/// ツールを実行する前に実行してよいかをユーザーに尋ねる。許可リストに含まれるツールは
+/// 確認せずに実行する。
+use std::collections::HashSet;
+use std::io::IsTerminal;
+
+use anyhow::{Context, Result};
+use rustyline::DefaultEditor;
+use rustyline::config::Configurer;
+use rustyline::error::ReadlineError;
+
+/// ユーザーが実行を拒否したツール呼び出しに返すエラーメッセージ
+pub const REJECTED_BY_USER: &str = "Rejected: the user declined to run this tool";
+
+/// ツール実行前の承認の設定
+#[derive(Debug, Clone, Default)]
+pub struct ToolApproval {
+    /// 確認せずに実行するツール名
+    allowed_tools: HashSet<String>,
+}
+
+impl ToolApproval {
+    /// 確認せずに実行するツール名を指定して作成する
+    pub fn new(allowed_tools: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            allowed_tools: allowed_tools.into_iter().collect(),
+        }
+    }
+
+    /// ツールを確認せずに実行してよいか
+    pub fn is_allowed(&self, tool_name: &str) -> bool {
+        self.allowed_tools.contains(tool_name)
+    }
+
+    /// ツールを実行してよいかをユーザーに尋ねる
+    ///
+    /// 標準入力が端末でない場合は尋ねられないため、実行を拒否する。
+    ///
+    /// # Returns
+    /// * `Ok(true)` - 許可リストに含まれる場合や、ユーザーが承認した場合
+    /// * `Ok(false)` - ユーザーが拒否した場合（Ctrl-C/Ctrl-D を含む）
+    /// * `Err` - 入力に失敗した場合
+    pub async fn confirm(&self, tool_name: &str) -> Result<bool> {
+        if self.is_allowed(tool_name) {
+            return Ok(true);
+        }
+        if !std::io::stdin().is_terminal() {
+            eprintln!(
+                "⚠️  標準入力が端末でないため、ツールの実行を確認できません。--auto-approve または --approve-tool を指定してください。"
+            );
+            return Ok(false);
+        }
+        tokio::task::spawn_blocking(read_approval)
+            .await
+            .context("ツールの実行の確認に失敗しました")?
+    }
+}
+
+/// ツールを実行するかの回答を読み取る
+fn read_approval() -> Result<bool> {
+    let mut editor = DefaultEditor::new().context("Failed to initialize rustyline editor")?;
+    // 回答を履歴に残さない
+    editor.set_auto_add_history(false);
+    match editor.readline("   このツールを実行しますか? [y/N] ") {
+        Ok(answer) => Ok(is_yes(&answer)),
+        Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => Ok(false),
+        Err(err) => Err(err).context("入力に失敗しました"),
+    }
+}
+
+/// 回答が承認（`y`・`yes`）か
+fn is_yes(answer: &str) -> bool {
+    matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allowed_tools_and_answers() {
+        let approval = ToolApproval::new(["git_status".to_string(), "read_file".to_string()]);
+        assert!(approval.is_allowed("git_status"));
+        assert!(!approval.is_allowed("git_commit"));
+        assert!(!ToolApproval::default().is_allowed("git_status"));
+
+        assert!(is_yes("y"));
+        assert!(is_yes(" YES\n"));
+        // 既定は拒否
+        assert!(!is_yes(""));
+        assert!(!is_yes("n"));
+        assert!(!is_yes("yep"));
+    }
+}
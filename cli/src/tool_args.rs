@@ -0,0 +1,203 @@
+/// ツール呼び出しの引数と結果の表示
+///
+/// ツールを実行する前に、モデルが渡そうとしている引数を確認できるよう、
+/// 整形したJSONとして表示するための変換を提供する。機密情報らしい引数の値は伏せ、
+/// 長い文字列や大きな入力は切り詰める。
+/// 実行後は、どのツールの結果かが分かるよう、結果の内容を1行に要約して表示する。
+use serde_json::{Map, Value};
+
+/// 値を伏せて表示する引数名に含まれる語（小文字で比較する）
+const SECRET_ARGUMENT_KEYWORDS: &[&str] = &[
+    "password",
+    "passwd",
+    "secret",
+    "token",
+    "api_key",
+    "apikey",
+    "credential",
+    "private_key",
+    "authorization",
+];
+
+/// 伏せた引数の表示値
+const REDACTED_VALUE: &str = "********";
+
+/// 文字列の値を表示する最大文字数
+const MAX_STRING_CHARS: usize = 200;
+
+/// 整形したJSONを表示する最大行数
+const MAX_LINES: usize = 20;
+
+/// ツール結果の要約を表示する最大文字数
+const MAX_SUMMARY_CHARS: usize = 80;
+
+/// ツールの引数を表示用に整形する
+///
+/// 機密情報らしい名前の引数の値を伏せ、長い文字列を切り詰めたうえで、
+/// インデント付きのJSONとして返す。行数が多い場合は残りの行数を示して省略する。
+///
+/// # Arguments
+/// * `arguments` - ツールに渡す引数
+/// * `indent` - 各行の先頭に付ける文字列
+///
+/// # Returns
+/// 表示用の文字列
+pub fn format_tool_arguments(arguments: &Map<String, Value>, indent: &str) -> String {
+    let display = sanitize(&Value::Object(arguments.clone()));
+    let pretty = serde_json::to_string_pretty(&display).unwrap_or_else(|_| display.to_string());
+
+    let lines: Vec<&str> = pretty.lines().collect();
+    let mut output: Vec<String> = lines
+        .iter()
+        .take(MAX_LINES)
+        .map(|line| format!("{}{}", indent, line))
+        .collect();
+    if lines.len() > MAX_LINES {
+        output.push(format!(
+            "{}…（残り {} 行を省略）",
+            indent,
+            lines.len() - MAX_LINES
+        ));
+    }
+    output.join("\n")
+}
+
+/// ツールの実行結果を1行に要約する
+///
+/// MCPのツール結果（`content` 配列）の場合は最初のテキストの1行目を、
+/// それ以外は文字列の1行目、オブジェクトのキー数、配列の要素数などを返す。
+///
+/// # Arguments
+/// * `result` - ツールの実行結果
+///
+/// # Returns
+/// 要約した文字列（最大 [`MAX_SUMMARY_CHARS`] 文字）
+pub fn summarize_tool_result(result: &Value) -> String {
+    let summary = match result {
+        Value::Object(map) => match map.get("content").and_then(Value::as_array) {
+            Some(content) => {
+                let text = content
+                    .iter()
+                    .find_map(|item| item.get("text").and_then(Value::as_str));
+                match text {
+                    Some(text) => first_line(text),
+                    None => format!("{}件のコンテンツ", content.len()),
+                }
+            }
+            None => format!("{}個のキー", map.len()),
+        },
+        Value::Array(items) => format!("{}件の要素", items.len()),
+        Value::String(text) => first_line(text),
+        other => other.to_string(),
+    };
+    truncate_chars(&summary, MAX_SUMMARY_CHARS)
+}
+
+/// 最初の空でない行を返す（空の場合は「（空）」）
+fn first_line(text: &str) -> String {
+    text.lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .unwrap_or("（空）")
+        .to_string()
+}
+
+/// 表示用に、機密情報らしい値を伏せて長い文字列を切り詰めたJSON値を返す
+fn sanitize(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(key, value)| {
+                    let value = if is_secret_argument(key) && !value.is_null() {
+                        Value::String(REDACTED_VALUE.to_string())
+                    } else {
+                        sanitize(value)
+                    };
+                    (key.clone(), value)
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(sanitize).collect()),
+        Value::String(text) => Value::String(truncate_chars(text, MAX_STRING_CHARS)),
+        other => other.clone(),
+    }
+}
+
+/// 引数名から、値が機密情報かを判定する
+fn is_secret_argument(key: &str) -> bool {
+    let key = key.to_ascii_lowercase();
+    SECRET_ARGUMENT_KEYWORDS
+        .iter()
+        .any(|keyword| key.contains(keyword))
+}
+
+/// 文字列を最大文字数で切り詰め、省略した文字数を付記する
+fn truncate_chars(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => format!(
+            "{}…（{}文字省略）",
+            &text[..end],
+            text[end..].chars().count()
+        ),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_format_tool_arguments_redacts_and_truncates() {
+        let arguments = json!({
+            "repo_path": ".",
+            "auth": { "api_key": "sk-secret", "author": "alice" },
+            "message": "a".repeat(205)
+        });
+        let formatted = format_tool_arguments(arguments.as_object().unwrap(), "   ");
+
+        assert!(formatted.contains("   {"));
+        assert!(formatted.contains("\"repo_path\": \".\""));
+        assert!(formatted.contains("\"api_key\": \"********\""));
+        assert!(!formatted.contains("sk-secret"));
+        assert!(formatted.contains("\"author\": \"alice\""));
+        assert!(formatted.contains(&format!("{}…（5文字省略）", "a".repeat(200))));
+    }
+
+    #[test]
+    fn test_summarize_tool_result() {
+        let mcp_result = json!({
+            "content": [{ "type": "text", "text": "\nOn branch main\nnothing to commit" }],
+            "isError": false
+        });
+        assert_eq!(summarize_tool_result(&mcp_result), "On branch main");
+        assert_eq!(
+            summarize_tool_result(&json!({ "content": [{ "type": "image" }] })),
+            "1件のコンテンツ"
+        );
+        assert_eq!(
+            summarize_tool_result(&json!({ "a": 1, "b": 2 })),
+            "2個のキー"
+        );
+        assert_eq!(summarize_tool_result(&json!([1, 2, 3])), "3件の要素");
+        assert_eq!(summarize_tool_result(&json!("")), "（空）");
+        assert_eq!(summarize_tool_result(&json!(42)), "42");
+        assert_eq!(
+            summarize_tool_result(&json!("a".repeat(85))),
+            format!("{}…（5文字省略）", "a".repeat(80))
+        );
+    }
+
+    #[test]
+    fn test_format_tool_arguments_limits_lines() {
+        let items: Vec<u32> = (0..50).collect();
+        let arguments = json!({ "items": items });
+        let formatted = format_tool_arguments(arguments.as_object().unwrap(), "");
+
+        let lines: Vec<&str> = formatted.lines().collect();
+        assert_eq!(lines.len(), MAX_LINES + 1);
+        // `{`、`"items": [`、50個の要素、`]`、`}` の計54行のうち20行を表示する
+        assert_eq!(lines[MAX_LINES], "…（残り 34 行を省略）");
+    }
+}
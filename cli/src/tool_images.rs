@@ -0,0 +1,247 @@
+/// MCPツール結果に含まれる画像の表示
+///
+/// MCPのツール結果（`content` 配列）に画像（`"type": "image"`）が含まれる場合、
+/// 端末が画像のインライン表示（iTerm2・kittyのプロトコル）に対応していれば端末に描画する。
+/// 対応していない場合は、base64のまま表示せずに一時ファイルへ保存してパスを表示する。
+use std::io::{self, IsTerminal, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+
+/// kittyのプロトコルで1回のエスケープシーケンスに含めるbase64の最大文字数
+const KITTY_CHUNK_SIZE: usize = 4096;
+
+/// 一時ファイル名の重複を避けるための連番
+static SAVED_IMAGE_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// ツール結果に含まれる画像
+#[derive(Debug, PartialEq)]
+struct ToolImage {
+    /// base64でエンコードされた画像データ
+    data: String,
+    /// 画像のMIMEタイプ（例: `image/png`）
+    mime_type: String,
+}
+
+/// 端末の画像表示プロトコル
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ImageProtocol {
+    /// iTerm2のインライン画像プロトコル（WezTermも対応）
+    Iterm2,
+    /// kittyのグラフィックスプロトコル
+    Kitty,
+}
+
+impl ImageProtocol {
+    /// 環境変数から、端末が対応する画像表示プロトコルを判定する
+    ///
+    /// # Arguments
+    /// * `env` - 環境変数名から値を取得する関数
+    fn detect(env: impl Fn(&str) -> Option<String>) -> Option<Self> {
+        if env("KITTY_WINDOW_ID").is_some() || env("TERM").as_deref() == Some("xterm-kitty") {
+            return Some(Self::Kitty);
+        }
+        let iterm2 = matches!(
+            env("TERM_PROGRAM").as_deref(),
+            Some("iTerm.app" | "WezTerm")
+        ) || env("LC_TERMINAL").as_deref() == Some("iTerm2");
+        iterm2.then_some(Self::Iterm2)
+    }
+
+    /// 指定したMIMEタイプの画像を表示できるか（kittyはPNGのみ直接表示できる）
+    fn supports(self, mime_type: &str) -> bool {
+        match self {
+            Self::Iterm2 => true,
+            Self::Kitty => mime_type == "image/png",
+        }
+    }
+
+    /// 画像を表示するエスケープシーケンスを生成する
+    ///
+    /// # Arguments
+    /// * `image` - 表示する画像
+    /// * `size` - デコード後の画像のバイト数
+    fn escape_sequence(self, image: &ToolImage, size: usize) -> String {
+        match self {
+            Self::Iterm2 => format!(
+                "\x1b]1337;File=inline=1;size={};preserveAspectRatio=1:{}\x07",
+                size, image.data
+            ),
+            Self::Kitty => {
+                let chunks: Vec<&[u8]> = image.data.as_bytes().chunks(KITTY_CHUNK_SIZE).collect();
+                let mut sequence = String::new();
+                for (index, chunk) in chunks.iter().enumerate() {
+                    let more = u8::from(index + 1 < chunks.len());
+                    let control = if index == 0 {
+                        format!("f=100,a=T,m={}", more)
+                    } else {
+                        format!("m={}", more)
+                    };
+                    // base64はASCIIのみのため、バイト単位で分割しても文字列として正しい
+                    let chunk = std::str::from_utf8(chunk).unwrap_or_default();
+                    sequence.push_str(&format!("\x1b_G{};{}\x1b\\", control, chunk));
+                }
+                sequence
+            }
+        }
+    }
+}
+
+/// ツール結果から画像を取り出す
+///
+/// MCPのツール結果（`content` 配列）のうち、`"type": "image"` で `data` を持つ要素を返す。
+fn extract_tool_images(result: &Value) -> Vec<ToolImage> {
+    let Some(content) = result.get("content").and_then(Value::as_array) else {
+        return Vec::new();
+    };
+    content
+        .iter()
+        .filter(|item| item.get("type").and_then(Value::as_str) == Some("image"))
+        .filter_map(|item| {
+            Some(ToolImage {
+                data: item.get("data")?.as_str()?.to_string(),
+                mime_type: item
+                    .get("mimeType")
+                    .and_then(Value::as_str)
+                    .unwrap_or("application/octet-stream")
+                    .to_string(),
+            })
+        })
+        .collect()
+}
+
+/// MIMEタイプから保存するファイルの拡張子を決める
+fn file_extension(mime_type: &str) -> &str {
+    match mime_type {
+        "image/png" => "png",
+        "image/jpeg" => "jpg",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        "image/svg+xml" => "svg",
+        _ => "bin",
+    }
+}
+
+/// ツール結果に含まれる画像を表示する
+///
+/// 標準出力が端末で、画像表示に対応している場合はインラインで描画し、
+/// それ以外の場合は一時ファイルに保存してパスを表示する。
+/// 画像の表示に失敗しても会話は続けるため、エラーは警告として表示する。
+///
+/// # Arguments
+/// * `result` - ツールの実行結果
+pub fn show_tool_images(result: &Value) {
+    let images = extract_tool_images(result);
+    if images.is_empty() {
+        return;
+    }
+    let protocol = io::stdout()
+        .is_terminal()
+        .then(|| ImageProtocol::detect(|name| std::env::var(name).ok()))
+        .flatten();
+
+    for image in &images {
+        if let Err(e) = show_tool_image(image, protocol) {
+            eprintln!("   警告: 画像を表示できませんでした: {:#}", e);
+        }
+    }
+}
+
+/// 1つの画像をインラインで描画するか、一時ファイルに保存する
+fn show_tool_image(image: &ToolImage, protocol: Option<ImageProtocol>) -> Result<()> {
+    let bytes = aws_smithy_types::base64::decode(&image.data)
+        .context("画像データのbase64デコードに失敗しました")?;
+
+    match protocol.filter(|protocol| protocol.supports(&image.mime_type)) {
+        Some(protocol) => {
+            println!("   🖼️  画像（{}）:", image.mime_type);
+            let mut stdout = io::stdout().lock();
+            writeln!(stdout, "{}", protocol.escape_sequence(image, bytes.len()))?;
+            stdout.flush()?;
+        }
+        None => {
+            let path = save_tool_image(&bytes, &image.mime_type)?;
+            println!(
+                "   🖼️  画像（{}）を保存しました: {}",
+                image.mime_type,
+                path.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// 画像を一時ディレクトリに保存し、保存先のパスを返す
+fn save_tool_image(bytes: &[u8], mime_type: &str) -> Result<PathBuf> {
+    let path = std::env::temp_dir().join(format!(
+        "agent-cli-image-{}-{}.{}",
+        std::process::id(),
+        SAVED_IMAGE_COUNT.fetch_add(1, Ordering::Relaxed) + 1,
+        file_extension(mime_type)
+    ));
+    std::fs::write(&path, bytes)
+        .with_context(|| format!("画像を {} に保存できませんでした", path.display()))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_extract_and_render_tool_images() {
+        let result = json!({
+            "content": [
+                { "type": "text", "text": "chart" },
+                { "type": "image", "data": "iVBORw0KGgo=", "mimeType": "image/png" },
+                { "type": "image" }
+            ]
+        });
+        let images = extract_tool_images(&result);
+        assert_eq!(
+            images,
+            vec![ToolImage {
+                data: "iVBORw0KGgo=".to_string(),
+                mime_type: "image/png".to_string(),
+            }]
+        );
+        assert!(extract_tool_images(&json!({ "a": 1 })).is_empty());
+
+        let detect = |vars: &[(&str, &str)]| {
+            let vars: HashMap<String, String> = vars
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect();
+            ImageProtocol::detect(|name| vars.get(name).cloned())
+        };
+        assert_eq!(
+            detect(&[("TERM", "xterm-kitty")]),
+            Some(ImageProtocol::Kitty)
+        );
+        assert_eq!(
+            detect(&[("TERM_PROGRAM", "iTerm.app")]),
+            Some(ImageProtocol::Iterm2)
+        );
+        assert_eq!(detect(&[("TERM", "xterm-256color")]), None);
+        assert!(!ImageProtocol::Kitty.supports("image/jpeg"));
+
+        let sequence = ImageProtocol::Iterm2.escape_sequence(&images[0], 8);
+        assert_eq!(
+            sequence,
+            "\x1b]1337;File=inline=1;size=8;preserveAspectRatio=1:iVBORw0KGgo=\x07"
+        );
+
+        // kittyでは4096文字ごとに分割し、最後の分割以外は m=1 を付ける
+        let large = ToolImage {
+            data: "A".repeat(KITTY_CHUNK_SIZE + 4),
+            mime_type: "image/png".to_string(),
+        };
+        let sequence = ImageProtocol::Kitty.escape_sequence(&large, 0);
+        assert!(sequence.starts_with("\x1b_Gf=100,a=T,m=1;AAAA"));
+        assert!(sequence.ends_with("\x1b_Gm=0;AAAA\x1b\\"));
+    }
+}
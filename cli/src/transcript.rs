@@ -0,0 +1,192 @@
+/// 保存した会話記録（トランスクリプト）の操作
+///
+/// `transcript diff` で2つの記録をターン単位で比較し、アシスタントの応答や
+/// ツール呼び出しが異なる箇所を表示する。
+use std::path::{Path, PathBuf};
+
+use agent::Transcript;
+use agent::history::{
+    TranscriptContent, TranscriptMessage, TranscriptRole, TranscriptToolResultContent,
+};
+use anyhow::{Context, Result};
+use clap::Subcommand;
+use similar::{ChangeTag, TextDiff};
+
+/// `transcript` サブコマンドの操作
+#[derive(Subcommand)]
+pub enum TranscriptCommand {
+    /// 2つのトランスクリプトをターン単位で比較します
+    Diff {
+        /// 比較元のトランスクリプト（JSON）
+        a: PathBuf,
+
+        /// 比較先のトランスクリプト（JSON）
+        b: PathBuf,
+    },
+}
+
+/// `transcript` サブコマンドを実行する
+pub fn run_transcript_command(command: TranscriptCommand) -> Result<()> {
+    match command {
+        TranscriptCommand::Diff { a, b } => show_transcript_diff(&a, &b),
+    }
+}
+
+/// トランスクリプトをファイルから読み込む
+pub(crate) fn load_transcript(path: &Path) -> Result<Transcript> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("ファイルの読み込みに失敗しました: {}", path.display()))?;
+    serde_json::from_str(&content)
+        .with_context(|| format!("トランスクリプトのパースに失敗しました: {}", path.display()))
+}
+
+/// 2つのトランスクリプトの差分を表示する
+fn show_transcript_diff(a_path: &Path, b_path: &Path) -> Result<()> {
+    let a = load_transcript(a_path)?;
+    let b = load_transcript(b_path)?;
+    print_transcript_diff(a_path, &a, b_path, &b);
+    Ok(())
+}
+
+/// 読み込み済みの2つのトランスクリプトの差分を表示する
+///
+/// # Arguments
+/// * `a_path` / `a` - 比較元のファイルパスとトランスクリプト
+/// * `b_path` / `b` - 比較先のファイルパスとトランスクリプト
+pub(crate) fn print_transcript_diff(a_path: &Path, a: &Transcript, b_path: &Path, b: &Transcript) {
+    println!("--- {}", a_path.display());
+    println!("+++ {}", b_path.display());
+    if a.model_id != b.model_id {
+        println!("モデル: {} → {}", a.model_id, b.model_id);
+    }
+    if a.system_prompt != b.system_prompt {
+        println!("システムプロンプトが異なります。");
+    }
+
+    let a_turns = a.turns();
+    let b_turns = b.turns();
+    let turn_count = a_turns.len().max(b_turns.len());
+    let mut differing = 0;
+
+    for index in 0..turn_count {
+        let diff = diff_turn(a_turns.get(index).copied(), b_turns.get(index).copied());
+        if let Some(diff) = diff {
+            differing += 1;
+            println!();
+            println!("@@ ターン {} @@", index + 1);
+            print!("{}", diff);
+        }
+    }
+
+    println!();
+    if differing == 0 {
+        println!("全 {} ターンが一致しました。", turn_count);
+    } else {
+        println!(
+            "{} ターン中 {} ターンに差分があります。",
+            turn_count, differing
+        );
+    }
+}
+
+/// 1ターン分の差分を行単位のテキストで作成する
+///
+/// # Arguments
+/// * `a` - 比較元のターン（存在しない場合は None）
+/// * `b` - 比較先のターン（存在しない場合は None）
+///
+/// # Returns
+/// 差分がある場合は、行頭に `-`/`+`/` ` を付けたターン全体のテキスト。一致する場合は None
+fn diff_turn(a: Option<&[TranscriptMessage]>, b: Option<&[TranscriptMessage]>) -> Option<String> {
+    let old = a.map(render_turn).unwrap_or_default();
+    let new = b.map(render_turn).unwrap_or_default();
+    if old == new {
+        return None;
+    }
+
+    let diff = TextDiff::from_lines(&old, &new);
+    let mut output = String::new();
+    for change in diff.iter_all_changes() {
+        let sign = match change.tag() {
+            ChangeTag::Delete => "-",
+            ChangeTag::Insert => "+",
+            ChangeTag::Equal => " ",
+        };
+        output.push_str(sign);
+        output.push_str(change.value());
+        if change.missing_newline() {
+            output.push('\n');
+        }
+    }
+    Some(output)
+}
+
+/// ターンのメッセージを比較用のテキストに変換する
+fn render_turn(messages: &[TranscriptMessage]) -> String {
+    let mut text = String::new();
+    for message in messages {
+        let role = match message.role {
+            TranscriptRole::User => "user",
+            TranscriptRole::Assistant => "assistant",
+        };
+        for content in &message.content {
+            let body = match content {
+                TranscriptContent::Text { text } => text.clone(),
+                TranscriptContent::ToolUse { name, input, .. } => {
+                    format!("[tool_use] {} {}", name, input)
+                }
+                TranscriptContent::ToolResult { content, .. } => {
+                    let results: Vec<String> = content
+                        .iter()
+                        .map(|result| match result {
+                            TranscriptToolResultContent::Text { text } => text.clone(),
+                            TranscriptToolResultContent::Json { json } => json.to_string(),
+                        })
+                        .collect();
+                    format!("[tool_result] {}", results.join("\n"))
+                }
+            };
+            text.push_str(&format!("{}: {}\n", role, body));
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text_message(role: TranscriptRole, text: &str) -> TranscriptMessage {
+        TranscriptMessage {
+            role,
+            content: vec![TranscriptContent::Text {
+                text: text.to_string(),
+            }],
+            timestamp_ms: None,
+        }
+    }
+
+    #[test]
+    fn test_diff_turn_shows_changed_response() {
+        let a = [
+            text_message(TranscriptRole::User, "Rustとは？"),
+            text_message(TranscriptRole::Assistant, "プログラミング言語です。"),
+        ];
+        let b = [
+            text_message(TranscriptRole::User, "Rustとは？"),
+            text_message(TranscriptRole::Assistant, "安全なシステム言語です。"),
+        ];
+
+        assert_eq!(diff_turn(Some(&a), Some(&a)), None);
+        assert_eq!(
+            diff_turn(Some(&a), Some(&b)).unwrap(),
+            " user: Rustとは？\n\
+             -assistant: プログラミング言語です。\n\
+             +assistant: 安全なシステム言語です。\n"
+        );
+        assert_eq!(
+            diff_turn(None, Some(&b[..1])).unwrap(),
+            "+user: Rustとは？\n"
+        );
+    }
+}
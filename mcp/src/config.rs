@@ -2,12 +2,128 @@
 ///
 /// Visual Studio Codeの`.vscode/mcp.json`仕様に準拠した
 /// MCP設定ファイルのパースと管理機能を提供します。
-use serde::{Deserialize, Serialize};
+use serde::de::{MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+use crate::mcp::McpError;
+
+/// チャットボットの設定を置くディレクトリ名（ワークスペース直下とホームディレクトリ直下）
+pub const CHATBOT_CONFIG_DIR: &str = ".chatbot";
+
+/// MCPの設定ファイル名（同じディレクトリに複数ある場合は前のものを優先する）
+const MCP_CONFIG_FILES: &[&str] = &["mcp.json", "mcp.yaml", "mcp.yml", "mcp.toml"];
+
+/// ワークスペースでMCPの設定ファイルを探すディレクトリ（優先度の高い順、空文字列はワークスペース直下）
+const WORKSPACE_CONFIG_DIRS: &[&str] = &[CHATBOT_CONFIG_DIR, ".vscode", ""];
+
+/// 値をマスクして表示する環境変数名に含まれる語
+const SECRET_ENV_KEYWORDS: &[&str] = &[
+    "TOKEN",
+    "SECRET",
+    "KEY",
+    "PASSWORD",
+    "PASSWD",
+    "CREDENTIAL",
+    "AUTH",
+    "PRIVATE",
+];
+
+/// マスクした環境変数の表示値
+const REDACTED_VALUE: &str = "********";
+
+/// ローカルでコマンドを起動し、標準入出力で通信するサーバーのタイプ
+pub const STDIO_SERVER_TYPE: &str = "stdio";
+
+/// HTTP（streamable HTTP）でリモートに接続するサーバーのタイプ
+pub const HTTP_SERVER_TYPE: &str = "http";
+
+/// Bearerトークンを送るHTTPヘッダー名
+const AUTHORIZATION_HEADER: &str = "Authorization";
+
+/// `mcp init` で作成する設定ファイルのひな形（コメント付き）
+pub const STARTER_CONFIG_TEMPLATE: &str = r#"// MCPサーバーの設定ファイル（VS Codeの mcp.json と同じ形式）
+// `//` と `/* */` のコメントを記述できます。
+{
+  // 入力プロンプトの定義
+  // サーバーの設定で `${input:<id>}` を参照すると、接続時に description を表示して値を尋ねます。
+  // 入力した値はCLIの終了まで保持され、password が true の場合は入力が表示されません。
+  "inputs": [
+    {
+      "type": "promptString",
+      "id": "github-token",
+      "description": "GitHub Personal Access Token",
+      "password": true
+    }
+  ],
+
+  // サーバー名ごとの起動設定（`mcp <サーバー名>` で接続します）
+  "servers": {
+    "git-mcp-server": {
+      // "stdio"（コマンドを起動）と "http"（URLに接続）に対応しています
+      "type": "stdio",
+      "command": "uvx",
+      "args": ["mcp-server-git"],
+      // ${workspaceFolder} はカレントディレクトリ、${env:<変数名>} は環境変数の値に置換されます
+      "cwd": "${workspaceFolder}",
+      "env": {
+        "RUST_LOG": "info"
+      }
+    }
+    // リモートのサーバーに接続する例（ビルド時に `mcp-http` フィーチャーが必要です）
+    // "remote-server": {
+    //   "type": "http",
+    //   "url": "https://example.com/mcp",
+    //   // ${env:<変数名>} は環境変数の値に置換されます
+    //   "headers": { "Authorization": "Bearer ${env:REMOTE_MCP_TOKEN}" }
+    // }
+  }
+}
+"#;
+
+/// 設定ファイルの形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    /// JSON（`//` と `/* */` のコメントを記述できる）
+    Json,
+    /// YAML（`yaml-config` フィーチャーが必要）
+    Yaml,
+    /// TOML（`toml-config` フィーチャーが必要）
+    Toml,
+}
+
+impl ConfigFormat {
+    /// ファイルの拡張子から形式を判定する
+    ///
+    /// `.yaml`・`.yml` はYAML、`.toml` はTOML、それ以外（`.json` を含む）はJSONとして扱う。
+    pub fn from_path(path: &Path) -> Self {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("yaml" | "yml") => Self::Yaml,
+            Some("toml") => Self::Toml,
+            _ => Self::Json,
+        }
+    }
+}
+
+impl std::fmt::Display for ConfigFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Json => "JSON",
+            Self::Yaml => "YAML",
+            Self::Toml => "TOML",
+        };
+        f.write_str(name)
+    }
+}
 
 /// mcp.jsonファイルのルート構造
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "RawMcpConfig")]
 pub struct McpConfig {
     /// 入力プロンプト定義（オプション）
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
@@ -15,6 +131,69 @@ pub struct McpConfig {
 
     /// MCPサーバーの設定マップ
     pub servers: HashMap<String, ServerConfig>,
+
+    /// 読み込んだ設定ファイルのパス（優先度の低い順、文字列やURLから読み込んだ場合は空）
+    #[serde(skip)]
+    pub source_paths: Vec<PathBuf>,
+
+    /// 同じファイルの中で重複して定義されたサーバー名（[`McpConfig::validate`] で報告する）
+    #[serde(skip)]
+    duplicate_servers: Vec<String>,
+}
+
+/// パース直後の mcp.json（同名のサーバーの重複を検出するため、定義順のまま保持する）
+#[derive(Deserialize)]
+struct RawMcpConfig {
+    #[serde(default)]
+    inputs: Vec<InputConfig>,
+    servers: ServerEntries,
+}
+
+/// 定義順に並べたサーバーの設定（同名のサーバーも取り除かない）
+struct ServerEntries(Vec<(String, ServerConfig)>);
+
+impl<'de> Deserialize<'de> for ServerEntries {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct EntriesVisitor;
+
+        impl<'de> Visitor<'de> for EntriesVisitor {
+            type Value = ServerEntries;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("a map of server names to server settings")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut entries = Vec::new();
+                while let Some(entry) = map.next_entry::<String, ServerConfig>()? {
+                    entries.push(entry);
+                }
+                Ok(ServerEntries(entries))
+            }
+        }
+
+        deserializer.deserialize_map(EntriesVisitor)
+    }
+}
+
+impl From<RawMcpConfig> for McpConfig {
+    fn from(raw: RawMcpConfig) -> Self {
+        let mut servers = HashMap::new();
+        let mut duplicate_servers = Vec::new();
+        for (name, server) in raw.servers.0 {
+            // 重複した場合は、JSONの一般的な解釈と同様に後の定義を使う
+            if servers.insert(name.clone(), server).is_some() && !duplicate_servers.contains(&name)
+            {
+                duplicate_servers.push(name);
+            }
+        }
+        McpConfig {
+            inputs: raw.inputs,
+            servers,
+            source_paths: Vec::new(),
+            duplicate_servers,
+        }
+    }
 }
 
 /// 入力プロンプトの設定
@@ -38,13 +217,26 @@ pub struct InputConfig {
 /// MCPサーバーの設定
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServerConfig {
-    /// サーバータイプ（現在は"stdio"のみサポート）
-    #[serde(rename = "type")]
+    /// サーバータイプ（"stdio" または "http"）
+    ///
+    /// 省略した場合は空文字列になり、[`McpConfig::validate`] で報告する。
+    #[serde(rename = "type", default)]
     pub server_type: String,
 
-    /// 実行するコマンド
+    /// 実行するコマンド（"stdio" の場合のみ）
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub command: String,
 
+    /// 接続するエンドポイントのURL（"http" の場合のみ）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+
+    /// リクエストに付けるHTTPヘッダー（"http" の場合のみ、オプション）
+    ///
+    /// 現在は `Authorization: Bearer <トークン>` のみ送信する。
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub headers: HashMap<String, String>,
+
     /// コマンドライン引数（オプション）
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub args: Vec<String>,
@@ -60,11 +252,19 @@ pub struct ServerConfig {
     /// 作業ディレクトリ（オプション）
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cwd: Option<String>,
+
+    /// 起動時に必ず接続するサーバーかどうか（オプション）
+    ///
+    /// `true` の場合、CLIは起動時にこのサーバーへ接続し、接続できなければ起動を中止する。
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub required: bool,
 }
 
 impl McpConfig {
     /// mcp.jsonファイルを読み込む
     ///
+    /// 拡張子（`.json`・`.yaml`・`.yml`・`.toml`）から形式を判定する（[`ConfigFormat::from_path`]）。
+    ///
     /// # Arguments
     /// * `path` - mcp.jsonファイルのパス
     ///
@@ -76,43 +276,248 @@ impl McpConfig {
     pub fn load_from_file(path: impl Into<PathBuf>) -> Result<Self, std::io::Error> {
         let path = path.into();
         let content = std::fs::read_to_string(&path)?;
-        let config: McpConfig = serde_json::from_str(&content).map_err(|e| {
+        let mut config = Self::from_str_with_format(&content, ConfigFormat::from_path(&path))?;
+        config.source_paths = vec![path];
+        Ok(config)
+    }
+
+    /// mcp.jsonの内容（JSON文字列）をパースする
+    ///
+    /// ファイル・URLのどちらから読み込んだ場合も、この関数で同じ検証を行う。
+    /// VS Codeの mcp.json と同様に、`//` と `/* */` のコメントを記述できる。
+    ///
+    /// # Errors
+    /// JSONとして不正な場合や、必須フィールドが欠けている場合
+    pub fn from_json_str(content: &str) -> Result<Self, std::io::Error> {
+        Self::from_str_with_format(content, ConfigFormat::Json)
+    }
+
+    /// 指定した形式で設定の内容をパースする
+    ///
+    /// # Errors
+    /// 指定した形式として不正な場合や、必須フィールドが欠けている場合。
+    /// エラーメッセージには、どの形式としてパースしたかを含める。
+    /// YAML・TOMLのサポートを有効にしていない場合もエラーとする。
+    pub fn from_str_with_format(
+        content: &str,
+        format: ConfigFormat,
+    ) -> Result<Self, std::io::Error> {
+        let result: Result<Self, String> = match format {
+            ConfigFormat::Json => {
+                serde_json::from_str(&strip_json_comments(content)).map_err(|e| e.to_string())
+            }
+            #[cfg(feature = "yaml-config")]
+            ConfigFormat::Yaml => serde_yaml::from_str(content).map_err(|e| e.to_string()),
+            #[cfg(feature = "toml-config")]
+            ConfigFormat::Toml => toml::from_str(content).map_err(|e| e.to_string()),
+            #[allow(unreachable_patterns)]
+            _ => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::Unsupported,
+                    format!(
+                        "Failed to parse mcp.json as {}: support for {} is not enabled (build with the {}-config feature)",
+                        format,
+                        format,
+                        format.to_string().to_ascii_lowercase()
+                    ),
+                ));
+            }
+        };
+        result.map_err(|e| {
             std::io::Error::new(
                 std::io::ErrorKind::InvalidData,
-                format!("Failed to parse mcp.json: {}", e),
+                format!("Failed to parse mcp.json as {}: {}", format, e),
             )
+        })
+    }
+
+    /// URLからmcp.jsonを取得して読み込む
+    ///
+    /// # Arguments
+    /// * `url` - mcp.jsonのURL
+    ///
+    /// # Returns
+    /// パースされた設定
+    ///
+    /// # Errors
+    /// 通信に失敗した場合、HTTPステータスが成功以外の場合、
+    /// またはレスポンスがmcp.jsonとしてパースできない場合
+    ///
+    /// # Note
+    /// mcp.jsonのサーバー設定は任意のコマンドを起動できるため、
+    /// 信頼できる取得元のURLのみを指定すること。
+    #[cfg(feature = "remote-config")]
+    pub async fn load_from_url(url: &str) -> Result<Self, std::io::Error> {
+        let response = reqwest::get(url).await.map_err(|e| {
+            std::io::Error::other(format!("Failed to fetch mcp.json from {}: {}", url, e))
         })?;
-        Ok(config)
+
+        let status = response.status();
+        if !status.is_success() {
+            return Err(std::io::Error::other(format!(
+                "Failed to fetch mcp.json from {}: HTTP {}",
+                url, status
+            )));
+        }
+
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or("unknown")
+            .to_string();
+        let content = response.text().await.map_err(|e| {
+            std::io::Error::other(format!("Failed to read response from {}: {}", url, e))
+        })?;
+
+        Self::from_json_str(&content).map_err(|e| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "Response from {} is not a valid mcp.json (Content-Type: {}): {}",
+                    url, content_type, e
+                ),
+            )
+        })
     }
 
     /// デフォルトの設定ファイルパスを取得
     ///
-    /// 以下の順序で検索：
-    /// 1. `.vscode/mcp.json`（VS Code規約）
-    /// 2. `mcp.json`（カレントディレクトリ）
+    /// カレントディレクトリから以下の順序で検索し、最初に見つかったパスを返す：
+    /// 1. `.chatbot/mcp.json`（チャットボット専用）
+    /// 2. `.vscode/mcp.json`（VS Code規約）
+    /// 3. `mcp.json`（カレントディレクトリ）
+    ///
+    /// 各ディレクトリでは `mcp.json`・`mcp.yaml`・`mcp.yml`・`mcp.toml` の順に探す。
     pub fn default_path() -> Option<PathBuf> {
-        let vscode_path = PathBuf::from(".vscode/mcp.json");
-        if vscode_path.exists() {
-            return Some(vscode_path);
+        let path = Self::find_workspace_config(Path::new(""));
+        if let Some(path) = &path {
+            tracing::debug!("Using workspace MCP config: {}", path.display());
+        }
+        path
+    }
+
+    /// ワークスペースの設定ファイルの候補を、優先度の高い順に取得
+    ///
+    /// # Arguments
+    /// * `workspace` - ワークスペースのディレクトリ（空のパスの場合はカレントディレクトリからの相対パス）
+    pub fn workspace_config_candidates(workspace: &Path) -> Vec<PathBuf> {
+        WORKSPACE_CONFIG_DIRS
+            .iter()
+            .flat_map(|dir| {
+                MCP_CONFIG_FILES
+                    .iter()
+                    .map(move |file| workspace.join(dir).join(file))
+            })
+            .collect()
+    }
+
+    /// ワークスペースの設定ファイルの候補のうち、最初に存在するものを取得
+    fn find_workspace_config(workspace: &Path) -> Option<PathBuf> {
+        Self::workspace_config_candidates(workspace)
+            .into_iter()
+            .find(|path| path.is_file())
+    }
+
+    /// ユーザー単位の設定ファイルのパスを取得
+    ///
+    /// `~/.chatbot/mcp.json`（または `mcp.yaml`・`mcp.yml`・`mcp.toml`）が存在する場合のみ返す。
+    pub fn user_config_path() -> Option<PathBuf> {
+        let dir = Self::user_config_location()?.parent()?.to_path_buf();
+        MCP_CONFIG_FILES
+            .iter()
+            .map(|file| dir.join(file))
+            .find(|path| path.is_file())
+    }
+
+    /// ユーザー単位の設定ファイルを置く場所（`~/.chatbot/mcp.json`）を取得
+    ///
+    /// ファイルが存在しない場合も返す。ホームディレクトリが不明な場合は None。
+    pub fn user_config_location() -> Option<PathBuf> {
+        let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"))?;
+        Some(
+            Path::new(&home)
+                .join(CHATBOT_CONFIG_DIR)
+                .join(MCP_CONFIG_FILES[0]),
+        )
+    }
+
+    /// 複数の設定ファイルを読み込んで1つの設定にまとめる
+    ///
+    /// # Arguments
+    /// * `paths` - 設定ファイルのパス（優先度の低い順）
+    ///
+    /// # Returns
+    /// マージされた設定。同名のサーバーは後のファイルの設定で上書きし、
+    /// `inputs` は和集合（同じIDは後のファイルの定義を優先）とする
+    ///
+    /// # Errors
+    /// いずれかのファイルの読み込みやパースに失敗した場合
+    pub fn load_merged(paths: &[PathBuf]) -> Result<Self, std::io::Error> {
+        let mut merged = McpConfig {
+            inputs: Vec::new(),
+            servers: HashMap::new(),
+            source_paths: Vec::new(),
+            duplicate_servers: Vec::new(),
+        };
+        for path in paths {
+            let config = Self::load_from_file(path)
+                .map_err(|e| std::io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+            for name in merged.merge(config) {
+                tracing::debug!("MCP server '{}' is overridden by {}", name, path.display());
+            }
         }
+        Ok(merged)
+    }
 
-        let current_path = PathBuf::from("mcp.json");
-        if current_path.exists() {
-            return Some(current_path);
+    /// 別の設定を優先度の高い設定としてマージする
+    ///
+    /// # Returns
+    /// `other` によって上書きされたサーバー名のリスト
+    pub fn merge(&mut self, other: McpConfig) -> Vec<String> {
+        self.source_paths.extend(other.source_paths);
+        self.duplicate_servers.extend(other.duplicate_servers);
+        for input in other.inputs {
+            match self
+                .inputs
+                .iter_mut()
+                .find(|existing| existing.id == input.id)
+            {
+                Some(existing) => *existing = input,
+                None => self.inputs.push(input),
+            }
         }
 
-        None
+        let mut overridden = Vec::new();
+        for (name, server) in other.servers {
+            if self.servers.insert(name.clone(), server).is_some() {
+                overridden.push(name);
+            }
+        }
+        overridden
     }
 
     /// デフォルトパスから設定を読み込む
     ///
+    /// ユーザー単位の設定（`~/.chatbot/mcp.json`）とワークスペースの設定
+    /// （[`McpConfig::default_path`]）の両方が存在する場合は、ワークスペースの設定を優先してマージする。
+    ///
+    /// 読み込んだファイルのパスは [`McpConfig::source_paths`] で確認できる。
+    ///
     /// # Returns
     /// 設定が見つかった場合はSome(config)、見つからない場合はNone
     pub fn load_default() -> Result<Option<Self>, std::io::Error> {
-        match Self::default_path() {
-            Some(path) => Ok(Some(Self::load_from_file(path)?)),
-            None => Ok(None),
+        let mut paths: Vec<PathBuf> = Self::user_config_path().into_iter().collect();
+        // ホームディレクトリで実行した場合、ユーザー単位の設定と `.chatbot/mcp.json` は同じファイルになる
+        if let Some(path) = Self::default_path()
+            && !paths.iter().any(|user| is_same_file(user, &path))
+        {
+            paths.push(path);
+        }
+        if paths.is_empty() {
+            return Ok(None);
         }
+        Self::load_merged(&paths).map(Some)
     }
 
     /// サーバー名のリストを取得
@@ -120,38 +525,458 @@ impl McpConfig {
         self.servers.keys().collect()
     }
 
+    /// 起動時に必ず接続するサーバー（`required: true`）の名前を名前順で取得
+    pub fn required_server_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self
+            .servers
+            .iter()
+            .filter(|(_, server)| server.required)
+            .map(|(name, _)| name.as_str())
+            .collect();
+        names.sort_unstable();
+        names
+    }
+
     /// 特定のサーバー設定を取得
     pub fn get_server(&self, name: &str) -> Option<&ServerConfig> {
         self.servers.get(name)
     }
+
+    /// 設定の内容を検証する
+    ///
+    /// パースできた設定について、サーバーごとの設定の誤りをまとめて検出する：
+    /// - `type` が空、または未対応のタイプ
+    /// - stdioのサーバーで `command` が空、httpのサーバーで `url` が空
+    /// - 同じファイルの中での同名のサーバー・入力の重複
+    /// - `inputs` に定義されていない入力（`${input:<id>}`）の参照
+    ///
+    /// # Errors
+    /// 問題が見つかった場合、問題ごとのメッセージのリスト（サーバー名順）
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut problems: Vec<String> = self
+            .duplicate_servers
+            .iter()
+            .map(|name| format!("server '{}' is defined more than once", name))
+            .collect();
+
+        let mut input_ids: Vec<&str> = Vec::new();
+        for input in &self.inputs {
+            if input_ids.contains(&input.id.as_str()) {
+                problems.push(format!("input '{}' is defined more than once", input.id));
+            }
+            input_ids.push(&input.id);
+        }
+
+        let mut servers: Vec<(&String, &ServerConfig)> = self.servers.iter().collect();
+        servers.sort_by_key(|(name, _)| *name);
+        for (name, server) in servers {
+            if server.server_type.trim().is_empty() {
+                problems.push(format!("server '{}': \"type\" is empty", name));
+            } else if server.is_stdio() {
+                if server.command.trim().is_empty() {
+                    problems.push(format!(
+                        "server '{}': \"command\" is required for {} servers",
+                        name, STDIO_SERVER_TYPE
+                    ));
+                }
+            } else if server.is_http() {
+                if server
+                    .url
+                    .as_deref()
+                    .is_none_or(|url| url.trim().is_empty())
+                {
+                    problems.push(format!(
+                        "server '{}': \"url\" is required for {} servers",
+                        name, HTTP_SERVER_TYPE
+                    ));
+                }
+            } else {
+                problems.push(format!(
+                    "server '{}': unsupported type '{}' (expected \"{}\" or \"{}\")",
+                    name, server.server_type, STDIO_SERVER_TYPE, HTTP_SERVER_TYPE
+                ));
+            }
+
+            for id in server.input_ids() {
+                if !input_ids.contains(&id.as_str()) {
+                    problems.push(format!(
+                        "server '{}': input '{}' is not defined in \"inputs\"",
+                        name, id
+                    ));
+                }
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+
+    /// 特定の入力プロンプトの定義を取得
+    pub fn get_input(&self, id: &str) -> Option<&InputConfig> {
+        self.inputs.iter().find(|input| input.id == id)
+    }
 }
 
 impl ServerConfig {
+    /// 設定値で参照されている入力（`${input:<id>}`）のIDを、最初に現れた順に重複なく取得
+    ///
+    /// `command`・`args`・`env`・`envFile`・`cwd`・`url`・`headers` の値を対象とする。
+    pub fn input_ids(&self) -> Vec<String> {
+        let mut env: Vec<(&String, &String)> = self.env.iter().collect();
+        env.sort();
+        let mut headers: Vec<(&String, &String)> = self.headers.iter().collect();
+        headers.sort();
+
+        let values = std::iter::once(&self.command)
+            .chain(&self.args)
+            .chain(env.into_iter().map(|(_, value)| value))
+            .chain(&self.env_file)
+            .chain(&self.cwd)
+            .chain(&self.url)
+            .chain(headers.into_iter().map(|(_, value)| value));
+        let mut ids: Vec<String> = Vec::new();
+        for value in values {
+            for id in input_references(value) {
+                if !ids.iter().any(|existing| existing == id) {
+                    ids.push(id.to_string());
+                }
+            }
+        }
+        ids
+    }
+
+    /// 入力の値（IDごと）で `${input:<id>}` を置き換えた設定を取得
+    ///
+    /// `values` にないIDの参照はそのまま残す。
+    pub fn with_inputs(&self, values: &HashMap<String, String>) -> ServerConfig {
+        let substitute = |value: &String| substitute_inputs(value, values);
+        ServerConfig {
+            command: substitute(&self.command),
+            args: self.args.iter().map(substitute).collect(),
+            env: self
+                .env
+                .iter()
+                .map(|(key, value)| (key.clone(), substitute(value)))
+                .collect(),
+            env_file: self.env_file.as_ref().map(substitute),
+            cwd: self.cwd.as_ref().map(substitute),
+            url: self.url.as_ref().map(substitute),
+            headers: self
+                .headers
+                .iter()
+                .map(|(key, value)| (key.clone(), substitute(value)))
+                .collect(),
+            ..self.clone()
+        }
+    }
+
+    /// ローカルでコマンドを起動するサーバー（"stdio"）かどうか
+    pub fn is_stdio(&self) -> bool {
+        self.server_type == STDIO_SERVER_TYPE
+    }
+
+    /// HTTPでリモートに接続するサーバー（"http"）かどうか
+    pub fn is_http(&self) -> bool {
+        self.server_type == HTTP_SERVER_TYPE
+    }
+
+    /// `Authorization` ヘッダーに指定されたBearerトークンを取得
+    ///
+    /// ヘッダー名の大文字・小文字は区別しない。値の `${env:<変数名>}` などの変数は展開する。
+    ///
+    /// # Returns
+    /// `Bearer ` の接頭辞を除いたトークン。ヘッダーがない場合やBearer形式でない場合は None
+    pub fn bearer_token(&self) -> Option<String> {
+        self.bearer_token_with(&VariableContext::new(None))
+    }
+
+    /// 送信できないHTTPヘッダー（`Authorization` 以外）の名前を名前順で取得
+    pub fn unsupported_header_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self
+            .headers
+            .keys()
+            .map(String::as_str)
+            .filter(|name| !name.eq_ignore_ascii_case(AUTHORIZATION_HEADER))
+            .collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// 変数の展開に使用する値を指定して、Bearerトークンを取得する
+    fn bearer_token_with(&self, ctx: &VariableContext) -> Option<String> {
+        let value = self
+            .headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(AUTHORIZATION_HEADER))
+            .map(|(_, value)| expand_variables(value, ctx))?;
+        let (scheme, token) = value.trim().split_once(' ')?;
+        let token = token.trim();
+        (scheme.eq_ignore_ascii_case("Bearer") && !token.is_empty()).then(|| token.to_string())
+    }
+
     /// ${workspaceFolder}などの変数を展開した実際のコマンドを取得
     ///
+    /// `${workspaceFolder}`・`${userHome}`・`${env:<変数名>}` などを展開する。
+    /// 以下の `resolve_*` も同様。
+    ///
     /// # Arguments
     /// * `workspace_folder` - ワークスペースフォルダのパス
     pub fn resolve_command(&self, workspace_folder: Option<&str>) -> String {
-        let mut command = self.command.clone();
-        if let Some(workspace) = workspace_folder {
-            command = command.replace("${workspaceFolder}", workspace);
-        }
-        command
+        expand_variables(&self.command, &VariableContext::new(workspace_folder))
     }
 
     /// 変数を展開した引数リストを取得
     pub fn resolve_args(&self, workspace_folder: Option<&str>) -> Vec<String> {
+        let ctx = VariableContext::new(workspace_folder);
         self.args
             .iter()
-            .map(|arg| {
-                let mut resolved = arg.clone();
-                if let Some(workspace) = workspace_folder {
-                    resolved = resolved.replace("${workspaceFolder}", workspace);
-                }
-                resolved
-            })
+            .map(|arg| expand_variables(arg, &ctx))
             .collect()
     }
+
+    /// 変数を展開した作業ディレクトリを取得
+    pub fn resolve_cwd(&self, workspace_folder: Option<&str>) -> Option<String> {
+        let ctx = VariableContext::new(workspace_folder);
+        self.cwd.as_deref().map(|cwd| expand_variables(cwd, &ctx))
+    }
+
+    /// サーバープロセスに設定する環境変数を取得
+    ///
+    /// `envFile` が指定されている場合は `.env` 形式で読み込み、`env` とマージする
+    /// （同じ変数名は `env` の値を優先する）。値とファイルのパスの変数は展開する。
+    ///
+    /// # Errors
+    /// `envFile` のファイルが存在しない場合や読み込めない場合は
+    /// [`McpError::InitializationError`]
+    pub fn resolve_env(
+        &self,
+        workspace_folder: Option<&str>,
+    ) -> Result<HashMap<String, String>, McpError> {
+        let ctx = VariableContext::new(workspace_folder);
+        let mut env = match &self.env_file {
+            Some(env_file) => {
+                let path = expand_variables(env_file, &ctx);
+                let content = std::fs::read_to_string(&path).map_err(|e| {
+                    McpError::InitializationError(format!("Failed to read envFile {}: {}", path, e))
+                })?;
+                parse_env_file(&content)
+            }
+            None => HashMap::new(),
+        };
+        env.extend(
+            self.env
+                .iter()
+                .map(|(key, value)| (key.clone(), expand_variables(value, &ctx))),
+        );
+        Ok(env)
+    }
+
+    /// 変数を展開した環境変数の一覧を、表示用に機密情報をマスクして取得
+    ///
+    /// # Returns
+    /// 変数名順に並べた（変数名, 値）のリスト。変数名にトークンやパスワードなどを
+    /// 示す語が含まれる場合、値はマスクされる
+    pub fn redacted_env(&self, workspace_folder: Option<&str>) -> Vec<(String, String)> {
+        let ctx = VariableContext::new(workspace_folder);
+        let mut env: Vec<(String, String)> = self
+            .env
+            .iter()
+            .map(|(key, value)| {
+                let value = if is_secret_env_key(key) {
+                    REDACTED_VALUE.to_string()
+                } else {
+                    expand_variables(value, &ctx)
+                };
+                (key.clone(), value)
+            })
+            .collect();
+        env.sort();
+        env
+    }
+}
+
+/// 2つのパスが同じファイルを指すか
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// 値に含まれる `${input:<id>}` のIDを順に取得
+fn input_references(value: &str) -> impl Iterator<Item = &str> {
+    value.split("${input:").skip(1).filter_map(|rest| {
+        let (id, _) = rest.split_once('}')?;
+        Some(id)
+    })
+}
+
+/// `${input:<id>}` を入力の値に置き換える（値のないIDはそのまま残す）
+fn substitute_inputs(value: &str, values: &HashMap<String, String>) -> String {
+    values.iter().fold(value.to_string(), |value, (id, input)| {
+        value.replace(&format!("${{input:{}}}", id), input)
+    })
+}
+
+/// 変数の展開に使用する値
+struct VariableContext<'a> {
+    /// ワークスペースフォルダのパス
+    workspace_folder: Option<&'a str>,
+    /// 環境変数名から値を取得する関数
+    env: &'a dyn Fn(&str) -> Option<String>,
+}
+
+impl<'a> VariableContext<'a> {
+    /// 実行中のプロセスの環境変数を使用するコンテキストを作成する
+    fn new(workspace_folder: Option<&'a str>) -> Self {
+        Self {
+            workspace_folder,
+            env: &process_env,
+        }
+    }
+
+    /// ホームディレクトリのパス（`HOME`、なければ `USERPROFILE`）
+    fn user_home(&self) -> Option<String> {
+        (self.env)("HOME").or_else(|| (self.env)("USERPROFILE"))
+    }
+}
+
+/// 実行中のプロセスの環境変数を取得する
+fn process_env(name: &str) -> Option<String> {
+    std::env::var(name).ok()
+}
+
+/// VS Codeの mcp.json と同じ形式の変数を展開する
+///
+/// 対応する変数：
+/// - `${workspaceFolder}`: ワークスペースフォルダのパス
+/// - `${workspaceFolderBasename}`: ワークスペースフォルダの名前
+/// - `${userHome}`: ホームディレクトリのパス
+/// - `${env:<変数名>}`: 環境変数の値（未定義の場合は空文字列）
+/// - `${pathSeparator}`・`${/}`: パスの区切り文字
+///
+/// 値を決められない変数（ワークスペースフォルダが不明な場合など）と、
+/// 対応していない変数（`${input:<id>}` など）はそのまま残す。
+fn expand_variables(input: &str, ctx: &VariableContext) -> String {
+    let mut output = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(start) = rest.find("${") {
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+        let variable = &rest[start..start + len + 1];
+        let name = &variable[2..variable.len() - 1];
+        let value = match name {
+            "workspaceFolder" => ctx.workspace_folder.map(String::from),
+            "workspaceFolderBasename" => ctx
+                .workspace_folder
+                .and_then(|folder| Path::new(folder).file_name())
+                .map(|name| name.to_string_lossy().into_owned()),
+            "userHome" => ctx.user_home(),
+            "pathSeparator" | "/" => Some(std::path::MAIN_SEPARATOR_STR.to_string()),
+            _ => name
+                .strip_prefix("env:")
+                .map(|env_name| (ctx.env)(env_name).unwrap_or_default()),
+        };
+        output.push_str(&rest[..start]);
+        output.push_str(value.as_deref().unwrap_or(variable));
+        rest = &rest[start + len + 1..];
+    }
+    output.push_str(rest);
+    output
+}
+
+/// `.env` 形式の内容を環境変数のマップにパースする
+///
+/// `KEY=VALUE` の行を読み込み、空行と `#` で始まるコメント行は無視する。
+/// 行頭の `export ` と、値を囲む引用符（`"` または `'`）は取り除く。
+fn parse_env_file(content: &str) -> HashMap<String, String> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let line = line.strip_prefix("export ").unwrap_or(line);
+            let (key, value) = line.split_once('=')?;
+            let key = key.trim();
+            if key.is_empty() {
+                return None;
+            }
+            let value = value.trim();
+            let value = ['"', '\'']
+                .iter()
+                .find_map(|quote| {
+                    value
+                        .strip_prefix(*quote)
+                        .and_then(|value| value.strip_suffix(*quote))
+                })
+                .unwrap_or(value);
+            Some((key.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// 環境変数名から、値が機密情報かを判定する
+fn is_secret_env_key(key: &str) -> bool {
+    let key = key.to_ascii_uppercase();
+    SECRET_ENV_KEYWORDS
+        .iter()
+        .any(|keyword| key.contains(keyword))
+}
+
+/// JSON文字列から `//` の行コメントと `/* */` のブロックコメントを取り除く
+///
+/// 文字列リテラル内の `//` などはそのまま残す。コメントは空白に置き換えるため、
+/// パースエラーの位置（行番号）は元の内容と一致する。
+fn strip_json_comments(content: &str) -> String {
+    let mut output = String::with_capacity(content.len());
+    let mut chars = content.chars().peekable();
+    let mut in_string = false;
+
+    while let Some(c) = chars.next() {
+        if in_string {
+            output.push(c);
+            match c {
+                '\\' => {
+                    if let Some(escaped) = chars.next() {
+                        output.push(escaped);
+                    }
+                }
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match (c, chars.peek()) {
+            ('"', _) => {
+                in_string = true;
+                output.push(c);
+            }
+            ('/', Some('/')) => {
+                // 改行は残し、行末までを読み飛ばす
+                while chars.next_if(|&next| next != '\n').is_some() {}
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                let mut prev = '\0';
+                for next in chars.by_ref() {
+                    if next == '\n' {
+                        output.push('\n');
+                    }
+                    if prev == '*' && next == '/' {
+                        break;
+                    }
+                    prev = next;
+                }
+            }
+            _ => output.push(c),
+        }
+    }
+    output
 }
 
 #[cfg(test)]
@@ -206,11 +1031,292 @@ mod tests {
         assert_eq!(server.env.get("RUST_LOG"), Some(&"debug".to_string()));
     }
 
+    #[test]
+    fn test_parse_required_servers() {
+        let json = r#"
+        {
+          "servers": {
+            "git": { "type": "stdio", "command": "git-mcp", "required": true },
+            "docs": { "type": "stdio", "command": "docs-mcp" }
+          }
+        }
+        "#;
+
+        let config = McpConfig::from_json_str(json).unwrap();
+        assert!(config.get_server("git").unwrap().required);
+        assert!(!config.get_server("docs").unwrap().required);
+        assert_eq!(config.required_server_names(), vec!["git"]);
+
+        // 既定値（false）はシリアライズしない
+        let serialized = serde_json::to_value(config.get_server("docs").unwrap()).unwrap();
+        assert!(serialized.get("required").is_none());
+    }
+
+    #[test]
+    fn test_config_format_from_path() {
+        assert_eq!(
+            ConfigFormat::from_path(Path::new(".chatbot/mcp.yaml")),
+            ConfigFormat::Yaml
+        );
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("mcp.YML")),
+            ConfigFormat::Yaml
+        );
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("mcp.toml")),
+            ConfigFormat::Toml
+        );
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("mcp.json")),
+            ConfigFormat::Json
+        );
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("mcp")),
+            ConfigFormat::Json
+        );
+
+        let candidates = McpConfig::workspace_config_candidates(Path::new(""));
+        assert_eq!(candidates[0], Path::new(".chatbot/mcp.json"));
+        assert_eq!(candidates[1], Path::new(".chatbot/mcp.yaml"));
+        assert_eq!(candidates.last().unwrap(), Path::new("mcp.toml"));
+    }
+
+    #[cfg(feature = "yaml-config")]
+    #[test]
+    fn test_parse_yaml_config() {
+        let yaml = r#"
+# YAMLではコメントを記述できる
+servers:
+  git:
+    type: stdio
+    command: uvx
+    args: [mcp-server-git]
+"#;
+        let config = McpConfig::from_str_with_format(yaml, ConfigFormat::Yaml).unwrap();
+        assert_eq!(
+            config.get_server("git").unwrap().args,
+            vec!["mcp-server-git"]
+        );
+
+        let err = McpConfig::from_str_with_format("servers: [", ConfigFormat::Yaml).unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("Failed to parse mcp.json as YAML")
+        );
+    }
+
+    #[cfg(feature = "toml-config")]
+    #[test]
+    fn test_parse_toml_config() {
+        let toml = r#"
+[servers.git]
+type = "stdio"
+command = "uvx"
+args = ["mcp-server-git"]
+required = true
+"#;
+        let config = McpConfig::from_str_with_format(toml, ConfigFormat::Toml).unwrap();
+        assert!(config.get_server("git").unwrap().required);
+
+        let err = McpConfig::from_str_with_format("servers =", ConfigFormat::Toml).unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("Failed to parse mcp.json as TOML")
+        );
+    }
+
+    #[test]
+    fn test_validate_collects_all_problems() {
+        let json = r#"{
+            "servers": {
+                "git": { "type": "stdio", "command": "uvx" },
+                "git": { "type": "stdio", "command": "uvx", "args": ["mcp-server-git"] },
+                "empty": { "command": "server" },
+                "remote": { "type": "http" },
+                "legacy": { "type": "sse", "url": "https://example.com/sse" },
+                "local": { "type": "stdio", "command": "", "env": { "TOKEN": "${input:token}" } }
+            }
+        }"#;
+        let config = McpConfig::from_json_str(json).unwrap();
+        // 重複した場合は後の定義を使う
+        assert_eq!(
+            config.get_server("git").unwrap().args,
+            vec!["mcp-server-git"]
+        );
+
+        let problems = config.validate().unwrap_err();
+        assert_eq!(
+            problems,
+            vec![
+                "server 'git' is defined more than once",
+                "server 'empty': \"type\" is empty",
+                "server 'legacy': unsupported type 'sse' (expected \"stdio\" or \"http\")",
+                "server 'local': \"command\" is required for stdio servers",
+                "server 'local': input 'token' is not defined in \"inputs\"",
+                "server 'remote': \"url\" is required for http servers",
+            ]
+        );
+
+        assert!(
+            McpConfig::from_json_str(STARTER_CONFIG_TEMPLATE)
+                .unwrap()
+                .validate()
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_from_json_str_rejects_invalid_content() {
+        let err = McpConfig::from_json_str("<html>Not Found</html>").unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(err.to_string().starts_with("Failed to parse mcp.json"));
+
+        // serversフィールドが欠けている場合もファイル読み込みと同様にエラーとする
+        assert!(McpConfig::from_json_str(r#"{ "inputs": [] }"#).is_err());
+    }
+
+    #[test]
+    fn test_comments_are_ignored_outside_strings() {
+        let json = r#"
+        // サーバー設定
+        {
+          "servers": {
+            /* ブロック
+               コメント */
+            "test-server": {
+              "type": "stdio",
+              "command": "http://example.com/*not-a-comment*/", // 行末コメント
+              "args": ["a\"//b"]
+            }
+          }
+        }
+        "#;
+
+        let config = McpConfig::from_json_str(json).unwrap();
+        let server = config.get_server("test-server").unwrap();
+        assert_eq!(server.command, "http://example.com/*not-a-comment*/");
+        assert_eq!(server.args, vec!["a\"//b"]);
+    }
+
+    #[test]
+    fn test_starter_template_is_valid() {
+        let config = McpConfig::from_json_str(STARTER_CONFIG_TEMPLATE).unwrap();
+        assert_eq!(config.inputs.len(), 1);
+        assert_eq!(config.get_server("git-mcp-server").unwrap().command, "uvx");
+    }
+
+    #[test]
+    fn test_merge_overrides_servers_and_unions_inputs() {
+        let mut user = McpConfig::from_json_str(
+            r#"
+            {
+              "inputs": [
+                { "type": "promptString", "id": "token", "description": "User token" }
+              ],
+              "servers": {
+                "git": { "type": "stdio", "command": "uvx", "args": ["mcp-server-git"] },
+                "personal": { "type": "stdio", "command": "personal-server" }
+              }
+            }
+            "#,
+        )
+        .unwrap();
+        let workspace = McpConfig::from_json_str(
+            r#"
+            {
+              "inputs": [
+                { "type": "promptString", "id": "token", "description": "Workspace token" },
+                { "type": "promptString", "id": "api-key", "description": "API key" }
+              ],
+              "servers": {
+                "git": { "type": "stdio", "command": "./bin/git-server" }
+              }
+            }
+            "#,
+        )
+        .unwrap();
+
+        let overridden = user.merge(workspace);
+        assert_eq!(overridden, vec!["git".to_string()]);
+        assert_eq!(user.servers.len(), 2);
+        assert_eq!(user.get_server("git").unwrap().command, "./bin/git-server");
+        assert!(user.get_server("personal").is_some());
+
+        assert_eq!(user.inputs.len(), 2);
+        assert_eq!(user.inputs[0].description, "Workspace token");
+        assert_eq!(user.inputs[1].id, "api-key");
+    }
+
+    #[test]
+    fn test_parse_http_server_with_bearer_token() {
+        let json = r#"{
+            "servers": {
+                "remote": {
+                    "type": "http",
+                    "url": "https://example.com/mcp",
+                    "headers": {
+                        "authorization": "Bearer ${env:REMOTE_TOKEN}",
+                        "X-Trace": "on"
+                    }
+                }
+            }
+        }"#;
+
+        let config = McpConfig::from_json_str(json).unwrap();
+        let server = config.get_server("remote").unwrap();
+        assert!(server.is_http());
+        assert!(!server.is_stdio());
+        assert!(server.command.is_empty());
+        assert_eq!(server.url.as_deref(), Some("https://example.com/mcp"));
+        assert_eq!(server.unsupported_header_names(), vec!["X-Trace"]);
+
+        let env = |name: &str| (name == "REMOTE_TOKEN").then(|| "secret".to_string());
+        let ctx = VariableContext {
+            workspace_folder: None,
+            env: &env,
+        };
+        assert_eq!(server.bearer_token_with(&ctx), Some("secret".to_string()));
+        // 環境変数が未設定の場合は、トークンなしとして扱う
+        let ctx = VariableContext {
+            workspace_folder: None,
+            env: &|_| None,
+        };
+        assert_eq!(server.bearer_token_with(&ctx), None);
+    }
+
+    #[test]
+    fn test_workspace_config_prefers_chatbot_dir() {
+        let workspace = std::env::temp_dir().join(format!("mcp-workspace-{}", std::process::id()));
+        std::fs::create_dir_all(workspace.join(".vscode")).unwrap();
+        let config = r#"{ "servers": {} }"#;
+
+        assert_eq!(McpConfig::find_workspace_config(&workspace), None);
+        std::fs::write(workspace.join("mcp.json"), config).unwrap();
+        std::fs::write(workspace.join(".vscode/mcp.json"), config).unwrap();
+        assert_eq!(
+            McpConfig::find_workspace_config(&workspace),
+            Some(workspace.join(".vscode/mcp.json"))
+        );
+
+        std::fs::create_dir_all(workspace.join(".chatbot")).unwrap();
+        std::fs::write(workspace.join(".chatbot/mcp.json"), config).unwrap();
+        let path = McpConfig::find_workspace_config(&workspace).unwrap();
+        assert_eq!(path, workspace.join(".chatbot/mcp.json"));
+
+        // 読み込んだファイルのパスを保持する
+        let loaded = McpConfig::load_merged(&[workspace.join("mcp.json"), path.clone()]).unwrap();
+        assert_eq!(loaded.source_paths, vec![workspace.join("mcp.json"), path]);
+
+        std::fs::remove_dir_all(&workspace).unwrap();
+    }
+
     #[test]
     fn test_resolve_workspace_folder() {
         let server = ServerConfig {
             server_type: "stdio".to_string(),
             command: "${workspaceFolder}/target/release/app".to_string(),
+            url: None,
+            headers: HashMap::new(),
             args: vec![
                 "--config".to_string(),
                 "${workspaceFolder}/config.toml".to_string(),
@@ -218,6 +1324,7 @@ mod tests {
             env: HashMap::new(),
             env_file: None,
             cwd: None,
+            required: false,
         };
 
         let resolved_command = server.resolve_command(Some("/home/user/project"));
@@ -226,4 +1333,156 @@ mod tests {
         let resolved_args = server.resolve_args(Some("/home/user/project"));
         assert_eq!(resolved_args[1], "/home/user/project/config.toml");
     }
+
+    #[test]
+    fn test_resolve_env_merges_env_file() {
+        let dir = std::env::temp_dir().join(format!("mcp-env-file-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join(".env"),
+            "# comment\nexport API_URL=\"https://example.com\"\nLOG_LEVEL='debug'\n\nINVALID\n",
+        )
+        .unwrap();
+
+        let mut server = ServerConfig {
+            server_type: "stdio".to_string(),
+            command: "server".to_string(),
+            url: None,
+            headers: HashMap::new(),
+            args: Vec::new(),
+            env: HashMap::from([
+                ("LOG_LEVEL".to_string(), "info".to_string()),
+                (
+                    "DATA_DIR".to_string(),
+                    "${workspaceFolder}/data".to_string(),
+                ),
+            ]),
+            env_file: Some("${workspaceFolder}/.env".to_string()),
+            cwd: None,
+            required: false,
+        };
+
+        // env の値を envFile より優先する
+        let workspace = dir.to_str().unwrap();
+        let env = server.resolve_env(Some(workspace)).unwrap();
+        assert_eq!(env.len(), 3);
+        assert_eq!(env["API_URL"], "https://example.com");
+        assert_eq!(env["LOG_LEVEL"], "info");
+        assert_eq!(env["DATA_DIR"], format!("{}/data", workspace));
+
+        server.env_file = Some("${workspaceFolder}/missing.env".to_string());
+        let error = server.resolve_env(Some(workspace)).unwrap_err();
+        assert!(matches!(error, McpError::InitializationError(_)));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_input_references_are_substituted() {
+        let json = r#"{
+            "inputs": [
+                { "type": "promptString", "id": "token", "description": "API token", "password": true },
+                { "type": "promptString", "id": "repo", "description": "Repository" }
+            ],
+            "servers": {
+                "github": {
+                    "type": "stdio",
+                    "command": "github-mcp",
+                    "args": ["--repo", "${input:repo}", "--mirror=${input:repo}"],
+                    "env": { "GITHUB_TOKEN": "${input:token}" }
+                }
+            }
+        }"#;
+
+        let config = McpConfig::from_json_str(json).unwrap();
+        let server = config.get_server("github").unwrap();
+        assert_eq!(server.input_ids(), vec!["repo", "token"]);
+        assert!(config.get_input("token").unwrap().password);
+        assert!(config.get_input("missing").is_none());
+
+        let values = HashMap::from([("repo".to_string(), "owner/name".to_string())]);
+        let resolved = server.with_inputs(&values);
+        assert_eq!(
+            resolved.args,
+            vec!["--repo", "owner/name", "--mirror=owner/name"]
+        );
+        // 値のない入力の参照はそのまま残す
+        assert_eq!(resolved.env["GITHUB_TOKEN"], "${input:token}");
+        assert_eq!(resolved.input_ids(), vec!["token"]);
+    }
+
+    #[test]
+    fn test_expand_variables() {
+        let env = |name: &str| match name {
+            "HOME" => Some("/home/user".to_string()),
+            "LOG_LEVEL" => Some("debug".to_string()),
+            _ => None,
+        };
+        let ctx = VariableContext {
+            workspace_folder: Some("/work/project"),
+            env: &env,
+        };
+        assert_eq!(
+            expand_variables("${userHome}/bin/${workspaceFolderBasename}", &ctx),
+            "/home/user/bin/project"
+        );
+        assert_eq!(
+            expand_variables("--log=${env:LOG_LEVEL}${env:UNDEFINED}", &ctx),
+            "--log=debug"
+        );
+        assert_eq!(
+            expand_variables("${workspaceFolder}${/}data", &ctx),
+            format!("/work/project{}data", std::path::MAIN_SEPARATOR)
+        );
+        // 対応していない変数と、閉じていない変数はそのまま残す
+        assert_eq!(
+            expand_variables("${input:token} ${env:HOME", &ctx),
+            "${input:token} ${env:HOME"
+        );
+
+        // ワークスペースフォルダが不明な場合、${workspaceFolder} はそのまま残す
+        let ctx = VariableContext {
+            workspace_folder: None,
+            env: &env,
+        };
+        assert_eq!(
+            expand_variables("${workspaceFolder}/${env:HOME}", &ctx),
+            "${workspaceFolder}//home/user"
+        );
+    }
+
+    #[test]
+    fn test_redacted_env_masks_secrets() {
+        let server = ServerConfig {
+            server_type: "stdio".to_string(),
+            command: "server".to_string(),
+            url: None,
+            headers: HashMap::new(),
+            args: Vec::new(),
+            env: HashMap::from([
+                ("GITHUB_TOKEN".to_string(), "ghp_secret".to_string()),
+                ("api_key".to_string(), "sk-secret".to_string()),
+                (
+                    "DATA_DIR".to_string(),
+                    "${workspaceFolder}/data".to_string(),
+                ),
+            ]),
+            env_file: None,
+            cwd: Some("${workspaceFolder}/sub".to_string()),
+            required: false,
+        };
+
+        assert_eq!(
+            server.redacted_env(Some("/work")),
+            vec![
+                ("DATA_DIR".to_string(), "/work/data".to_string()),
+                ("GITHUB_TOKEN".to_string(), REDACTED_VALUE.to_string()),
+                ("api_key".to_string(), REDACTED_VALUE.to_string()),
+            ]
+        );
+        assert_eq!(
+            server.resolve_cwd(Some("/work")),
+            Some("/work/sub".to_string())
+        );
+    }
 }
@@ -1,8 +1,13 @@
 pub mod config;
 pub mod mcp;
 
-pub use config::{McpConfig, ServerConfig};
-pub use mcp::{McpClient, McpError};
+pub use config::{ConfigFormat, HTTP_SERVER_TYPE, McpConfig, STDIO_SERVER_TYPE, ServerConfig};
+pub use mcp::{
+    DEFAULT_INIT_TIMEOUT, DEFAULT_REQUEST_TIMEOUT, LaunchOptions, McpClient, McpConnectionInfo,
+    McpError, ProtocolVersionMismatch, ResourceContent, ToolProgress,
+};
 
 // Re-export commonly used types from rmcp for convenience
-pub use rmcp::model::{Prompt, Resource, Tool};
+pub use rmcp::model::{
+    Prompt, PromptArgument, PromptMessageContent, ProtocolVersion, Reference, Resource, Tool,
+};
@@ -1,11 +1,60 @@
 use rmcp::{
-    RmcpError,
-    model::{CallToolRequestParam, Resource, ServerInfo, Tool},
-    service::{RoleClient, RunningService, ServiceError, ServiceExt},
+    ClientHandler, RmcpError,
+    model::{
+        ArgumentInfo, CallToolRequest, CallToolRequestParam, ClientRequest, CompleteRequestParam,
+        GetPromptRequestParam, GetPromptResult, ProgressNotificationParam, ProtocolVersion,
+        Reference, Resource, ResourceContents, ServerInfo, ServerResult, Tool,
+    },
+    service::{
+        NotificationContext, PeerRequestOptions, RoleClient, RunningService, ServiceError,
+        ServiceExt,
+    },
     transport::{ConfigureCommandExt, TokioChildProcess},
 };
 use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
 use tokio::process::Command;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+
+/// 進捗通知を中継するチャネルの容量
+const PROGRESS_CHANNEL_CAPACITY: usize = 64;
+
+/// サーバーへのリクエストの応答を待つ制限時間のデフォルト
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// ローカルサーバーの起動から初期化（ハンドシェイク）の完了までを待つ制限時間のデフォルト
+pub const DEFAULT_INIT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 保持するサーバーの標準エラー出力の行数（接続に失敗した場合のエラーメッセージに含める）
+const STDERR_TAIL_LINES: usize = 20;
+
+/// 接続に失敗した後、サーバーの標準エラー出力を読み終えるまで待つ時間
+const STDERR_DRAIN_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// サーバーの標準エラー出力の直近の行
+type StderrTail = Arc<Mutex<VecDeque<String>>>;
+
+/// サーバーの起動によく使われるコマンドと、見つからない場合のインストール方法
+const INSTALL_HINTS: &[(&str, &str)] = &[
+    (
+        "uvx",
+        "Install uv (which provides uvx): https://docs.astral.sh/uv/getting-started/installation/",
+    ),
+    (
+        "uv",
+        "Install uv: https://docs.astral.sh/uv/getting-started/installation/",
+    ),
+    (
+        "npx",
+        "Install Node.js (npm includes npx): https://nodejs.org/",
+    ),
+];
 
 /// MCPクライアントのエラー型
 #[derive(thiserror::Error, Debug)]
@@ -41,15 +90,241 @@ pub enum McpError {
     SerializationError(#[from] serde_json::Error),
 }
 
+/// ツール実行中にサーバーから通知された進捗
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolProgress {
+    /// これまでの進捗量（合計が不明な場合も単調増加する）
+    pub progress: f64,
+    /// 進捗の合計量（不明な場合は None）
+    pub total: Option<f64>,
+    /// 進捗に関する補足メッセージ
+    pub message: Option<String>,
+}
+
+impl ToolProgress {
+    /// 進捗率（0〜100）を取得する。合計が不明な場合は None
+    pub fn percent(&self) -> Option<f64> {
+        self.total
+            .filter(|total| *total > 0.0)
+            .map(|total| (self.progress / total * 100.0).clamp(0.0, 100.0))
+    }
+}
+
+impl std::fmt::Display for ToolProgress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.percent(), self.total) {
+            (Some(percent), _) => write!(f, "{:.0}%", percent)?,
+            (None, Some(total)) => write!(f, "{}/{}", self.progress, total)?,
+            (None, None) => write!(f, "{}", self.progress)?,
+        }
+        if let Some(message) = &self.message {
+            write!(f, " {}", message)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<ProgressNotificationParam> for ToolProgress {
+    fn from(param: ProgressNotificationParam) -> Self {
+        Self {
+            progress: param.progress,
+            total: param.total,
+            message: param.message,
+        }
+    }
+}
+
+/// リソースの内容（`read_resource` の結果の1項目）
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResourceContent {
+    /// テキスト
+    Text(String),
+    /// バイナリデータ（`data` はBase64エンコードされた文字列）
+    Blob { mime: Option<String>, data: String },
+    /// JSON（MIMEタイプがJSONのテキストをパースしたもの）
+    Json(Value),
+}
+
+impl ResourceContent {
+    /// 会話の文脈として使えるテキストに変換する
+    ///
+    /// JSONは整形した文字列に変換する。バイナリデータはテキストとして扱えないため None を返す。
+    pub fn to_text(&self) -> Option<String> {
+        match self {
+            Self::Text(text) => Some(text.clone()),
+            Self::Json(value) => {
+                Some(serde_json::to_string_pretty(value).unwrap_or_else(|_| value.to_string()))
+            }
+            Self::Blob { .. } => None,
+        }
+    }
+}
+
+impl From<ResourceContents> for ResourceContent {
+    fn from(contents: ResourceContents) -> Self {
+        match contents {
+            ResourceContents::TextResourceContents {
+                mime_type, text, ..
+            } => {
+                if mime_type.as_deref().is_some_and(is_json_mime_type)
+                    && let Ok(value) = serde_json::from_str(&text)
+                {
+                    return Self::Json(value);
+                }
+                Self::Text(text)
+            }
+            ResourceContents::BlobResourceContents {
+                mime_type, blob, ..
+            } => Self::Blob {
+                mime: mime_type,
+                data: blob,
+            },
+        }
+    }
+}
+
+/// MIMEタイプがJSON（`application/json` や `application/*+json`）かを判定する
+fn is_json_mime_type(mime_type: &str) -> bool {
+    let essence = mime_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    essence == "application/json" || essence.ends_with("+json")
+}
+
+/// このクライアントが認識しているプロトコルバージョン
+const KNOWN_PROTOCOL_VERSIONS: &[ProtocolVersion] = &[
+    ProtocolVersion::V_2024_11_05,
+    ProtocolVersion::V_2025_03_26,
+    ProtocolVersion::V_2025_06_18,
+];
+
+/// 接続中のMCPサーバーとのネゴシエーション結果
+#[derive(Debug, Clone, PartialEq)]
+pub struct McpConnectionInfo {
+    /// サーバーの実装名
+    pub server_name: String,
+    /// サーバーの実装バージョン
+    pub server_version: String,
+    /// サーバーが応答したプロトコルバージョン
+    pub protocol_version: ProtocolVersion,
+    /// クライアントが要求したプロトコルバージョン
+    pub client_protocol_version: ProtocolVersion,
+}
+
+/// クライアントとサーバーのプロトコルバージョンの食い違い
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProtocolVersionMismatch {
+    /// サーバーの方が古いバージョンを使用している（新しい機能が動作しない可能性がある）
+    ServerOlder,
+    /// サーバーの方が新しいバージョンを使用している（クライアントが未対応の挙動がありうる）
+    ServerNewer,
+    /// サーバーがクライアントの認識していないバージョンを応答した
+    Unknown,
+}
+
+impl McpConnectionInfo {
+    /// サーバーの情報から接続情報を作成する
+    ///
+    /// # Arguments
+    /// * `server_info` - 初期化時にサーバーが応答した情報
+    /// * `client_protocol_version` - クライアントが要求したプロトコルバージョン
+    pub fn new(server_info: &ServerInfo, client_protocol_version: ProtocolVersion) -> Self {
+        Self {
+            server_name: server_info.server_info.name.clone(),
+            server_version: server_info.server_info.version.clone(),
+            protocol_version: server_info.protocol_version.clone(),
+            client_protocol_version,
+        }
+    }
+
+    /// プロトコルバージョンの食い違いを判定する
+    ///
+    /// # Returns
+    /// バージョンが一致する場合は None
+    pub fn protocol_mismatch(&self) -> Option<ProtocolVersionMismatch> {
+        if self.protocol_version == self.client_protocol_version {
+            None
+        } else if !KNOWN_PROTOCOL_VERSIONS.contains(&self.protocol_version) {
+            Some(ProtocolVersionMismatch::Unknown)
+        } else if self.protocol_version < self.client_protocol_version {
+            Some(ProtocolVersionMismatch::ServerOlder)
+        } else {
+            Some(ProtocolVersionMismatch::ServerNewer)
+        }
+    }
+}
+
+/// サーバーからの通知を受け取るクライアント側のハンドラ
+///
+/// 進捗通知をブロードキャストチャネルへ中継し、リクエスト側で
+/// 進捗トークンごとに振り分けられるようにする。
+pub struct ClientNotificationHandler {
+    progress_tx: broadcast::Sender<ProgressNotificationParam>,
+}
+
+impl ClientHandler for ClientNotificationHandler {
+    fn on_progress(
+        &self,
+        params: ProgressNotificationParam,
+        _context: NotificationContext<RoleClient>,
+    ) -> impl Future<Output = ()> + Send + '_ {
+        // 受信者がいない場合の送信エラーは無視する
+        let _ = self.progress_tx.send(params);
+        std::future::ready(())
+    }
+}
+
+/// ローカルMCPサーバーを起動するときのオプション
+#[derive(Debug, Clone)]
+pub struct LaunchOptions {
+    /// サーバープロセスに設定する環境変数（CLIプロセスから引き継ぐ環境変数に追加する）
+    pub env: HashMap<String, String>,
+    /// サーバープロセスの作業ディレクトリ
+    ///
+    /// 相対パスはカレントディレクトリを基準に解決する。None の場合はカレントディレクトリで起動する。
+    pub cwd: Option<PathBuf>,
+    /// サーバーの標準エラー出力を、受信するたびにこのプロセスの標準エラー出力に書き出す（デバッグ用）
+    ///
+    /// false の場合も直近の出力は保持し、接続に失敗した場合のエラーメッセージに含める。
+    pub forward_stderr: bool,
+    /// 初期化（ハンドシェイク）の完了を待つ制限時間（None の場合は無制限）
+    ///
+    /// 超過した場合はサーバープロセスを終了する。デフォルトは [`DEFAULT_INIT_TIMEOUT`]。
+    pub init_timeout: Option<Duration>,
+}
+
+impl Default for LaunchOptions {
+    fn default() -> Self {
+        Self {
+            env: HashMap::new(),
+            cwd: None,
+            forward_stderr: false,
+            init_timeout: Some(DEFAULT_INIT_TIMEOUT),
+        }
+    }
+}
+
 /// ローカルMCPサーバーとの通信を管理するクライアント
 ///
 /// このクライアントは以下の機能を提供します：
 /// - ローカルプロセスとしてのMCPサーバーの起動と接続
+/// - HTTPによるリモートMCPサーバーへの接続（`http-transport` フィーチャー）
 /// - リソース情報の取得
 /// - ツール情報の取得と実行
 pub struct McpClient {
     /// MCP RPC サービスクライアント
-    client: RunningService<RoleClient, ()>,
+    client: RunningService<RoleClient, ClientNotificationHandler>,
+    /// 進捗通知の送信側（受信側の購読に使用する）
+    progress_tx: broadcast::Sender<ProgressNotificationParam>,
+    /// サーバープロセスのプロセスID（取得できない場合は None）
+    pid: Option<u32>,
+    /// リクエストの応答を待つ制限時間（None の場合は無制限）
+    request_timeout: Option<Duration>,
+    /// サーバーの標準エラー出力の直近の行（HTTPで接続した場合は空）
+    stderr_tail: StderrTail,
 }
 
 impl McpClient {
@@ -72,19 +347,183 @@ impl McpClient {
     /// # }
     /// ```
     pub async fn new(command: &str, args: Vec<&str>) -> Result<Self, McpError> {
-        let transport = TokioChildProcess::new(Command::new(command).configure(|cmd| {
-            for arg in args {
-                cmd.arg(arg);
+        Self::new_with_env(command, args, HashMap::new()).await
+    }
+
+    /// 環境変数を指定して新しい MCP クライアントを作成し、ローカルサーバーに接続する
+    ///
+    /// 指定した環境変数は、CLIプロセスから引き継ぐ環境変数に追加（同名の場合は上書き）する。
+    ///
+    /// # Arguments
+    /// * `command` - 起動するコマンド名（例: "uvx", "npx"）
+    /// * `args` - コマンド引数のベクター（例: vec!["mcp-server-git"]）
+    /// * `env` - サーバープロセスに設定する環境変数
+    ///   （mcp.jsonの設定からは [`ServerConfig::resolve_env`](crate::ServerConfig::resolve_env) で取得できる）
+    ///
+    /// # Returns
+    /// * `Ok(McpClient)` - 接続に成功した場合
+    /// * `Err(McpError)` - 接続に失敗した場合
+    pub async fn new_with_env(
+        command: &str,
+        args: Vec<&str>,
+        env: HashMap<String, String>,
+    ) -> Result<Self, McpError> {
+        let options = LaunchOptions {
+            env,
+            ..LaunchOptions::default()
+        };
+        Self::new_with_options(command, args, options).await
+    }
+
+    /// 環境変数・作業ディレクトリを指定して新しい MCP クライアントを作成し、ローカルサーバーに接続する
+    ///
+    /// # Arguments
+    /// * `command` - 起動するコマンド名（例: "uvx", "npx"）
+    /// * `args` - コマンド引数のベクター（例: vec!["mcp-server-git"]）
+    /// * `options` - 環境変数と作業ディレクトリ
+    ///
+    /// # Returns
+    /// * `Ok(McpClient)` - 接続に成功した場合
+    /// * `Err(McpError)` - 作業ディレクトリが存在しない場合（`InitializationError`）や、
+    ///   接続に失敗した場合（サーバーが標準エラー出力に書き出した直近の内容を含む `InitializationError`）。
+    ///   初期化が制限時間内に完了しない場合は、サーバープロセスを終了して
+    ///   `InitializationError("initialization timed out")` を返す
+    pub async fn new_with_options(
+        command: &str,
+        args: Vec<&str>,
+        options: LaunchOptions,
+    ) -> Result<Self, McpError> {
+        let cwd = options
+            .cwd
+            .as_deref()
+            .map(resolve_working_directory)
+            .transpose()?;
+        let (transport, stderr) =
+            TokioChildProcess::builder(Command::new(command).configure(|cmd| {
+                for arg in args {
+                    cmd.arg(arg);
+                }
+                for (key, value) in options.env {
+                    cmd.env(key, value);
+                }
+                if let Some(cwd) = cwd {
+                    cmd.current_dir(cwd);
+                }
+                // 初期化の途中でトランスポートを破棄した場合も、プロセスを終了して回収する
+                cmd.kill_on_drop(true);
+            }))
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| spawn_error(command, e))?;
+        let pid = transport.id();
+        let stderr_reader =
+            stderr.map(|stderr| spawn_stderr_reader(stderr, options.forward_stderr));
+
+        let (progress_tx, _) = broadcast::channel(PROGRESS_CHANNEL_CAPACITY);
+        let handler = ClientNotificationHandler {
+            progress_tx: progress_tx.clone(),
+        };
+
+        // 制限時間を超えた場合は、serve とともにトランスポート（子プロセス）を破棄する
+        let initialized = match options.init_timeout {
+            Some(limit) => tokio::time::timeout(limit, handler.serve(transport))
+                .await
+                .ok(),
+            None => Some(handler.serve(transport).await),
+        };
+        let client = match initialized {
+            Some(Ok(client)) => client,
+            failed => {
+                let mut message = match failed {
+                    Some(Err(e)) => e.to_string(),
+                    _ => "initialization timed out".to_string(),
+                };
+                if let Some((tail, reader)) = stderr_reader {
+                    // 終了したサーバーの出力を読み終えるまで少し待つ
+                    let _ = tokio::time::timeout(STDERR_DRAIN_TIMEOUT, reader).await;
+                    message = with_stderr_tail(message, &tail);
+                }
+                return Err(McpError::InitializationError(message));
             }
-        }))
-        .map_err(|e| McpError::TransportError(e.to_string()))?;
+        };
 
-        let client = ()
-            .serve(transport)
-            .await
-            .map_err(|e| McpError::InitializationError(e.to_string()))?;
+        Ok(Self {
+            client,
+            progress_tx,
+            pid,
+            request_timeout: Some(DEFAULT_REQUEST_TIMEOUT),
+            stderr_tail: stderr_reader.map(|(tail, _)| tail).unwrap_or_default(),
+        })
+    }
+
+    /// 新しい MCP クライアントを作成し、HTTP（streamable HTTP）でリモートサーバーに接続する
+    ///
+    /// # Arguments
+    /// * `url` - MCPサーバーのエンドポイントURL（例: "https://example.com/mcp"）
+    ///
+    /// # Returns
+    /// * `Ok(McpClient)` - 接続に成功した場合
+    /// * `Err(McpError)` - 接続に失敗した場合
+    ///
+    /// # Note
+    /// `http-transport` フィーチャーが必要です。認証が必要なサーバーには
+    /// [`McpClient::connect_http_with_token`] を使用してください。
+    #[cfg(feature = "http-transport")]
+    pub async fn connect_http(url: &str) -> Result<Self, McpError> {
+        Self::connect_http_with_token(url, None).await
+    }
+
+    /// 新しい MCP クライアントを作成し、Bearerトークンを付けてHTTPでリモートサーバーに接続する
+    ///
+    /// サーバーがSSEで応答する場合も、同じトランスポートでイベントを受信する。
+    ///
+    /// # Arguments
+    /// * `url` - MCPサーバーのエンドポイントURL
+    /// * `bearer_token` - `Authorization` ヘッダーで送るトークン（`Bearer ` の接頭辞は不要）
+    ///
+    /// # Returns
+    /// * `Ok(McpClient)` - 接続に成功した場合
+    /// * `Err(McpError)` - 接続に失敗した場合
+    #[cfg(feature = "http-transport")]
+    pub async fn connect_http_with_token(
+        url: &str,
+        bearer_token: Option<&str>,
+    ) -> Result<Self, McpError> {
+        use rmcp::transport::StreamableHttpClientTransport;
+        use rmcp::transport::streamable_http_client::StreamableHttpClientTransportConfig;
+
+        let mut config = StreamableHttpClientTransportConfig::with_uri(url);
+        if let Some(token) = bearer_token {
+            config = config.auth_header(token);
+        }
+        let transport = StreamableHttpClientTransport::from_config(config);
 
-        Ok(Self { client })
+        let (progress_tx, _) = broadcast::channel(PROGRESS_CHANNEL_CAPACITY);
+        let handler = ClientNotificationHandler {
+            progress_tx: progress_tx.clone(),
+        };
+
+        let client = handler.serve(transport).await.map_err(|e| {
+            McpError::ConnectionError(format!("Failed to connect to {}: {}", url, e))
+        })?;
+
+        // リモートサーバーのため、プロセスIDはない
+        Ok(Self {
+            client,
+            progress_tx,
+            pid: None,
+            request_timeout: Some(DEFAULT_REQUEST_TIMEOUT),
+            stderr_tail: StderrTail::default(),
+        })
+    }
+
+    /// サーバープロセスのプロセスIDを取得する
+    ///
+    /// # Returns
+    /// プロセスが既に終了している場合や、HTTPで接続したリモートサーバーの場合など、
+    /// 取得できない場合は None
+    pub fn pid(&self) -> Option<u32> {
+        self.pid
     }
 
     /// サーバーの情報を取得する
@@ -95,13 +534,46 @@ impl McpClient {
         self.client.peer_info()
     }
 
+    /// サーバーとのプロトコルバージョンのネゴシエーション結果を取得する
+    ///
+    /// # Returns
+    /// サーバーの情報が利用できない場合は None
+    pub fn connection_info(&self) -> Option<McpConnectionInfo> {
+        let client_protocol_version = self.client.service().get_info().protocol_version;
+        self.server_info()
+            .map(|info| McpConnectionInfo::new(info, client_protocol_version))
+    }
+
+    /// サーバーが標準エラー出力に書き出した直近の行を取得する（古い順、最大20行）
+    pub fn recent_stderr(&self) -> Vec<String> {
+        let tail = self.stderr_tail.lock().unwrap_or_else(|e| e.into_inner());
+        tail.iter().cloned().collect()
+    }
+
+    /// リクエストの応答を待つ制限時間を取得する（None の場合は無制限）
+    pub fn request_timeout(&self) -> Option<Duration> {
+        self.request_timeout
+    }
+
+    /// リクエストの応答を待つ制限時間を設定する
+    ///
+    /// ツールの実行・ツールやリソースの一覧の取得など、サーバーへのすべてのリクエストに適用する。
+    /// 応答しないサーバーで会話全体が止まらないよう、デフォルトは [`DEFAULT_REQUEST_TIMEOUT`]。
+    ///
+    /// # Arguments
+    /// * `timeout` - 制限時間（None の場合は無制限）
+    pub fn set_request_timeout(&mut self, timeout: Option<Duration>) {
+        self.request_timeout = timeout;
+    }
+
     /// 利用可能なツールの一覧を取得する
     ///
     /// # Returns
     /// * `Ok(Vec<Tool>)` - ツール一覧
-    /// * `Err(McpError)` - 取得に失敗した場合
+    /// * `Err(McpError)` - 取得に失敗した場合（制限時間を超えた場合は `ConnectionError`）
     pub async fn list_tools(&self) -> Result<Vec<Tool>, McpError> {
-        let response = self.client.list_tools(Default::default()).await?;
+        let request = self.client.list_tools(Default::default());
+        let response = within_request_timeout(self.request_timeout, "tools/list", request).await?;
         Ok(response.tools)
     }
 
@@ -113,22 +585,90 @@ impl McpClient {
     ///
     /// # Returns
     /// * `Ok(Value)` - ツールの実行結果
-    /// * `Err(McpError)` - 実行に失敗した場合
+    /// * `Err(McpError)` - 実行に失敗した場合（制限時間を超えた場合は `ConnectionError`）
     pub async fn call_tool(
         &self,
         tool_name: String,
         arguments: Option<serde_json::Map<String, Value>>,
     ) -> Result<Value, McpError> {
-        let result = self
-            .client
-            .call_tool(CallToolRequestParam {
+        let request = self.client.call_tool(CallToolRequestParam {
+            name: tool_name.into(),
+            arguments,
+        });
+        let result = within_request_timeout(self.request_timeout, "tool call", request).await?;
+
+        // 結果をJSON形式で返す
+        Ok(serde_json::to_value(&result)?)
+    }
+
+    /// 進捗通知を受け取りながら、指定されたツールを実行する
+    ///
+    /// # Arguments
+    /// * `tool_name` - 実行するツール名
+    /// * `arguments` - ツールに渡す引数（JSON形式）
+    /// * `timeout` - 応答を待つ制限時間（None の場合は [`Self::request_timeout`]）
+    /// * `on_progress` - このツール呼び出しの進捗通知を受け取るたびに呼ばれるコールバック
+    ///
+    /// # Returns
+    /// * `Ok(Value)` - ツールの実行結果
+    /// * `Err(McpError)` - 実行に失敗した場合（制限時間を超えた場合は `ConnectionError`）
+    ///
+    /// # Note
+    /// 制限時間を超えた場合は、サーバーにキャンセル通知を送信する。
+    pub async fn call_tool_with_progress(
+        &self,
+        tool_name: String,
+        arguments: Option<serde_json::Map<String, Value>>,
+        timeout: Option<Duration>,
+        mut on_progress: impl FnMut(ToolProgress),
+    ) -> Result<Value, McpError> {
+        // 通知の取りこぼしを防ぐため、リクエスト送信前に購読を開始する
+        let mut progress_rx = self.progress_tx.subscribe();
+
+        let request = ClientRequest::CallToolRequest(CallToolRequest {
+            method: Default::default(),
+            params: CallToolRequestParam {
                 name: tool_name.into(),
                 arguments,
-            })
+            },
+            extensions: Default::default(),
+        });
+        let handle = self
+            .client
+            .send_cancellable_request(
+                request,
+                PeerRequestOptions {
+                    timeout: timeout.or(self.request_timeout),
+                    ..PeerRequestOptions::no_options()
+                },
+            )
             .await?;
+        let progress_token = handle.progress_token.clone();
 
-        // 結果をJSON形式で返す
-        Ok(serde_json::to_value(&result)?)
+        let response = handle.await_response();
+        tokio::pin!(response);
+
+        let result = loop {
+            tokio::select! {
+                result = &mut response => break result.map_err(|e| request_error(e, "tool call"))?,
+                notification = progress_rx.recv() => match notification {
+                    Ok(param) if param.progress_token == progress_token => {
+                        on_progress(ToolProgress::from(param));
+                    }
+                    // 他のリクエストの通知や、取りこぼしは無視する
+                    Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    // 送信側が閉じた場合は結果のみを待つ
+                    Err(broadcast::error::RecvError::Closed) => {
+                        break response.await.map_err(|e| request_error(e, "tool call"))?
+                    }
+                },
+            }
+        };
+
+        match result {
+            ServerResult::CallToolResult(result) => Ok(serde_json::to_value(&result)?),
+            _ => Err(McpError::ServiceError(ServiceError::UnexpectedResponse)),
+        }
     }
 
     /// 利用可能なリソースの一覧を取得する
@@ -137,7 +677,9 @@ impl McpClient {
     /// * `Ok(Vec<Resource>)` - リソース一覧
     /// * `Err(McpError)` - 取得に失敗した場合
     pub async fn list_resources(&self) -> Result<Vec<Resource>, McpError> {
-        let response = self.client.list_resources(Default::default()).await?;
+        let request = self.client.list_resources(Default::default());
+        let response =
+            within_request_timeout(self.request_timeout, "resources/list", request).await?;
         Ok(response.resources)
     }
 
@@ -147,16 +689,20 @@ impl McpClient {
     /// * `uri` - リソースのURI
     ///
     /// # Returns
-    /// * `Ok(Value)` - リソースの内容
+    /// * `Ok(Vec<ResourceContent>)` - リソースの内容（サーバーが返した項目の順）
     /// * `Err(McpError)` - 読み込みに失敗した場合
-    pub async fn read_resource(&self, uri: String) -> Result<Value, McpError> {
-        let result = self
+    pub async fn read_resource(&self, uri: String) -> Result<Vec<ResourceContent>, McpError> {
+        let request = self
             .client
-            .read_resource(rmcp::model::ReadResourceRequestParam { uri })
-            .await?;
+            .read_resource(rmcp::model::ReadResourceRequestParam { uri });
+        let result =
+            within_request_timeout(self.request_timeout, "resources/read", request).await?;
 
-        // 結果をJSON形式で返す
-        Ok(serde_json::to_value(&result)?)
+        Ok(result
+            .contents
+            .into_iter()
+            .map(ResourceContent::from)
+            .collect())
     }
 
     /// 利用可能なプロンプトの一覧を取得する
@@ -165,10 +711,71 @@ impl McpClient {
     /// * `Ok(Vec<Prompt>)` - プロンプト一覧
     /// * `Err(McpError)` - 取得に失敗した場合
     pub async fn list_prompts(&self) -> Result<Vec<rmcp::model::Prompt>, McpError> {
-        let response = self.client.list_prompts(Default::default()).await?;
+        let request = self.client.list_prompts(Default::default());
+        let response =
+            within_request_timeout(self.request_timeout, "prompts/list", request).await?;
         Ok(response.prompts)
     }
 
+    /// 指定されたプロンプトを引数付きで取得する
+    ///
+    /// # Arguments
+    /// * `name` - プロンプト名
+    /// * `arguments` - プロンプトに渡す引数
+    ///
+    /// # Returns
+    /// * `Ok(GetPromptResult)` - 引数が埋め込まれたプロンプトのメッセージ一覧
+    /// * `Err(McpError)` - 取得に失敗した場合
+    pub async fn get_prompt(
+        &self,
+        name: String,
+        arguments: Option<serde_json::Map<String, Value>>,
+    ) -> Result<GetPromptResult, McpError> {
+        let request = self
+            .client
+            .get_prompt(GetPromptRequestParam { name, arguments });
+        within_request_timeout(self.request_timeout, "prompts/get", request).await
+    }
+
+    /// サーバーが補完（completion）機能を提供しているかを確認する
+    pub fn supports_completion(&self) -> bool {
+        self.server_info()
+            .is_some_and(|info| info.capabilities.completions.is_some())
+    }
+
+    /// プロンプト/リソースの引数の補完候補を取得する
+    ///
+    /// # Arguments
+    /// * `reference` - 補完対象のプロンプトまたはリソーステンプレート
+    /// * `argument` - 補完する引数名
+    /// * `partial` - 入力途中の値
+    ///
+    /// # Returns
+    /// * `Ok(Vec<String>)` - 補完候補。サーバーが補完機能を提供していない場合は空
+    /// * `Err(McpError)` - 取得に失敗した場合
+    pub async fn complete(
+        &self,
+        reference: Reference,
+        argument: &str,
+        partial: &str,
+    ) -> Result<Vec<String>, McpError> {
+        if !self.supports_completion() {
+            return Ok(Vec::new());
+        }
+
+        let request = self.client.complete(CompleteRequestParam {
+            r#ref: reference,
+            argument: ArgumentInfo {
+                name: argument.to_string(),
+                value: partial.to_string(),
+            },
+            context: None,
+        });
+        let result =
+            within_request_timeout(self.request_timeout, "completion/complete", request).await?;
+        Ok(result.completion.values)
+    }
+
     /// MCPサーバーとの接続を切断する
     ///
     /// # Returns
@@ -179,3 +786,333 @@ impl McpClient {
         Ok(())
     }
 }
+
+/// サーバーの標準エラー出力を読み取るタスクを開始する
+///
+/// パイプが詰まってサーバーが止まらないよう、サーバーが終了するまで読み取り続け、
+/// 直近の行を保持する。
+///
+/// # Arguments
+/// * `stderr` - サーバープロセスの標準エラー出力
+/// * `forward` - 読み取った行をこのプロセスの標準エラー出力に書き出すか
+fn spawn_stderr_reader(
+    stderr: impl AsyncRead + Unpin + Send + 'static,
+    forward: bool,
+) -> (StderrTail, JoinHandle<()>) {
+    let tail = StderrTail::default();
+    let reader_tail = tail.clone();
+    let reader = tokio::spawn(async move {
+        let mut lines = BufReader::new(stderr).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            if forward {
+                eprintln!("[mcp server] {}", line);
+            }
+            let mut tail = reader_tail.lock().unwrap_or_else(|e| e.into_inner());
+            if tail.len() == STDERR_TAIL_LINES {
+                tail.pop_front();
+            }
+            tail.push_back(line);
+        }
+    });
+    (tail, reader)
+}
+
+/// エラーメッセージに、サーバーの標準エラー出力の直近の行を付け加える
+fn with_stderr_tail(message: String, tail: &StderrTail) -> String {
+    let tail = tail.lock().unwrap_or_else(|e| e.into_inner());
+    if tail.is_empty() {
+        return message;
+    }
+    let lines: Vec<&str> = tail.iter().map(String::as_str).collect();
+    format!("{}\nServer stderr:\n  {}", message, lines.join("\n  "))
+}
+
+/// 制限時間内にサーバーへのリクエストの応答を待つ
+///
+/// # Arguments
+/// * `timeout` - 制限時間（None の場合は無制限）
+/// * `operation` - エラーメッセージに含めるリクエストの種類（例: "tool call"）
+/// * `request` - 応答を待つリクエスト
+///
+/// # Returns
+/// 制限時間を超えた場合は `ConnectionError("<operation> timed out")`
+async fn within_request_timeout<T>(
+    timeout: Option<Duration>,
+    operation: &str,
+    request: impl Future<Output = Result<T, ServiceError>>,
+) -> Result<T, McpError> {
+    let result = match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, request)
+            .await
+            .map_err(|_| timed_out_error(operation))?,
+        None => request.await,
+    };
+    result.map_err(|e| request_error(e, operation))
+}
+
+/// リクエストのエラーを変換する（制限時間の超過は `ConnectionError` にする）
+fn request_error(error: ServiceError, operation: &str) -> McpError {
+    match error {
+        ServiceError::Timeout { .. } => timed_out_error(operation),
+        other => McpError::ServiceError(other),
+    }
+}
+
+/// 制限時間を超えた場合のエラーを作成する
+fn timed_out_error(operation: &str) -> McpError {
+    McpError::ConnectionError(format!("{} timed out", operation))
+}
+
+/// サーバープロセスの起動に失敗した場合のエラーを作成する
+///
+/// コマンドが見つからない場合は、PATHを確認するよう案内し、よく使われるコマンドには
+/// インストール方法を付記する。
+fn spawn_error(command: &str, error: std::io::Error) -> McpError {
+    if error.kind() != std::io::ErrorKind::NotFound {
+        return McpError::TransportError(error.to_string());
+    }
+
+    let mut message = format!("command '{}' not found on PATH — is it installed?", command);
+    let name = std::path::Path::new(command)
+        .file_stem()
+        .and_then(|name| name.to_str())
+        .unwrap_or(command);
+    if let Some((_, hint)) = INSTALL_HINTS.iter().find(|(tool, _)| *tool == name) {
+        message.push_str(&format!(" {}", hint));
+    }
+    McpError::TransportError(message)
+}
+
+/// サーバープロセスの作業ディレクトリを絶対パスに解決し、存在することを確認する
+///
+/// 存在しないディレクトリで起動すると、コマンドが見つからない場合と同じエラーになるため、
+/// 起動前に確認して区別できるエラーにする。
+fn resolve_working_directory(cwd: &Path) -> Result<PathBuf, McpError> {
+    let path = if cwd.is_relative() {
+        std::env::current_dir()
+            .map_err(|e| {
+                McpError::InitializationError(format!("Failed to get the current directory: {}", e))
+            })?
+            .join(cwd)
+    } else {
+        cwd.to_path_buf()
+    };
+    if !path.is_dir() {
+        return Err(McpError::InitializationError(format!(
+            "working directory '{}' does not exist",
+            path.display()
+        )));
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_stderr_reader_keeps_recent_lines() {
+        let output: String = (1..=25).map(|i| format!("line {}\n", i)).collect();
+        let (tail, reader) = spawn_stderr_reader(std::io::Cursor::new(output.into_bytes()), false);
+        reader.await.unwrap();
+
+        let lines: Vec<String> = tail.lock().unwrap().iter().cloned().collect();
+        assert_eq!(lines.len(), STDERR_TAIL_LINES);
+        assert_eq!(lines.first().unwrap(), "line 6");
+        assert_eq!(lines.last().unwrap(), "line 25");
+
+        let message = with_stderr_tail("connection closed".to_string(), &tail);
+        assert!(message.starts_with("connection closed\nServer stderr:\n  line 6\n  line 7"));
+        assert_eq!(
+            with_stderr_tail("connection closed".to_string(), &StderrTail::default()),
+            "connection closed"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_new_includes_server_stderr_on_failure() {
+        let result = McpClient::new("sh", vec!["-c", "echo 'fatal: missing API key' >&2"]).await;
+        let Err(McpError::InitializationError(message)) = result else {
+            panic!("expected an initialization error");
+        };
+        assert!(message.contains("Server stderr:\n  fatal: missing API key"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_new_times_out_and_kills_unresponsive_server() {
+        // ハンドシェイクに応答せず、プロセスIDだけを標準エラー出力に書き出すサーバー
+        let options = LaunchOptions {
+            init_timeout: Some(Duration::from_millis(200)),
+            ..LaunchOptions::default()
+        };
+        let args = vec!["-c", "echo $$ >&2; exec sleep 30"];
+        let result = McpClient::new_with_options("sh", args, options).await;
+        let Err(McpError::InitializationError(message)) = result else {
+            panic!("expected an initialization error");
+        };
+        let (reason, pid) = message.split_once("\nServer stderr:\n  ").unwrap();
+        assert_eq!(reason, "initialization timed out");
+
+        // 終了したプロセスは回収され、ゾンビとして残らない
+        let proc_dir = PathBuf::from(format!("/proc/{}", pid.trim()));
+        for _ in 0..50 {
+            if !proc_dir.exists() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("server process {} was not reaped", pid);
+    }
+
+    #[tokio::test]
+    async fn test_within_request_timeout() {
+        // 制限時間内に応答しないリクエストは ConnectionError にする
+        let pending = std::future::pending::<Result<(), ServiceError>>();
+        let result = within_request_timeout(Some(Duration::from_millis(10)), "tool call", pending);
+        let Err(McpError::ConnectionError(message)) = result.await else {
+            panic!("expected a connection error");
+        };
+        assert_eq!(message, "tool call timed out");
+
+        // サーバー側で制限時間を超えた場合も同じエラーにする
+        let timeout = ServiceError::Timeout {
+            timeout: Duration::from_secs(1),
+        };
+        let result = within_request_timeout(None, "tools/list", async { Err::<(), _>(timeout) });
+        assert_eq!(
+            result.await.unwrap_err().to_string(),
+            "Server connection error: tools/list timed out"
+        );
+
+        let ready = async { Ok::<_, ServiceError>(42) };
+        assert_eq!(
+            within_request_timeout(Some(Duration::from_secs(1)), "tools/list", ready)
+                .await
+                .unwrap(),
+            42
+        );
+    }
+
+    #[tokio::test]
+    async fn test_new_with_options_reports_missing_working_directory() {
+        let options = LaunchOptions {
+            cwd: Some(PathBuf::from("bedrock-chatbot-missing-dir")),
+            ..LaunchOptions::default()
+        };
+        let result = McpClient::new_with_options("sh", vec![], options).await;
+        let Err(McpError::InitializationError(message)) = result else {
+            panic!("expected an initialization error");
+        };
+        // 相対パスはカレントディレクトリを基準に解決する
+        let expected = std::env::current_dir()
+            .unwrap()
+            .join("bedrock-chatbot-missing-dir");
+        assert_eq!(
+            message,
+            format!("working directory '{}' does not exist", expected.display())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_new_reports_missing_command() {
+        let result = McpClient::new("bedrock-chatbot-missing-command", vec![]).await;
+        let Err(McpError::TransportError(message)) = result else {
+            panic!("expected a transport error");
+        };
+        assert_eq!(
+            message,
+            "command 'bedrock-chatbot-missing-command' not found on PATH — is it installed?"
+        );
+
+        let error = spawn_error("uvx", std::io::ErrorKind::NotFound.into());
+        assert!(error.to_string().contains("not found on PATH"));
+        assert!(error.to_string().contains("https://docs.astral.sh/uv/"));
+    }
+
+    #[test]
+    fn test_tool_progress_display() {
+        let progress = ToolProgress {
+            progress: 2.0,
+            total: Some(5.0),
+            message: Some("ファイルを解析中".to_string()),
+        };
+        assert_eq!(progress.percent(), Some(40.0));
+        assert_eq!(progress.to_string(), "40% ファイルを解析中");
+
+        let unknown_total = ToolProgress {
+            progress: 3.0,
+            total: None,
+            message: None,
+        };
+        assert_eq!(unknown_total.percent(), None);
+        assert_eq!(unknown_total.to_string(), "3");
+    }
+
+    #[test]
+    fn test_resource_content_from_contents() {
+        let text = ResourceContents::text("# README", "file:///README.md");
+        assert_eq!(
+            ResourceContent::from(text),
+            ResourceContent::Text("# README".to_string())
+        );
+
+        let json = ResourceContents::TextResourceContents {
+            uri: "file:///package.json".to_string(),
+            mime_type: Some("application/json; charset=utf-8".to_string()),
+            text: r#"{"name":"demo"}"#.to_string(),
+            meta: None,
+        };
+        let json = ResourceContent::from(json);
+        assert_eq!(
+            json,
+            ResourceContent::Json(serde_json::json!({ "name": "demo" }))
+        );
+        assert_eq!(json.to_text().unwrap(), "{\n  \"name\": \"demo\"\n}");
+
+        let blob = ResourceContents::BlobResourceContents {
+            uri: "file:///logo.png".to_string(),
+            mime_type: Some("image/png".to_string()),
+            blob: "iVBORw0KGgo=".to_string(),
+            meta: None,
+        };
+        let blob = ResourceContent::from(blob);
+        assert_eq!(
+            blob,
+            ResourceContent::Blob {
+                mime: Some("image/png".to_string()),
+                data: "iVBORw0KGgo=".to_string(),
+            }
+        );
+        assert_eq!(blob.to_text(), None);
+    }
+
+    #[test]
+    fn test_protocol_mismatch() {
+        let info = |version: ProtocolVersion| McpConnectionInfo {
+            server_name: "test-server".to_string(),
+            server_version: "1.0.0".to_string(),
+            protocol_version: version,
+            client_protocol_version: ProtocolVersion::V_2025_03_26,
+        };
+
+        assert_eq!(
+            info(ProtocolVersion::V_2025_03_26).protocol_mismatch(),
+            None
+        );
+        assert_eq!(
+            info(ProtocolVersion::V_2024_11_05).protocol_mismatch(),
+            Some(ProtocolVersionMismatch::ServerOlder)
+        );
+        assert_eq!(
+            info(ProtocolVersion::V_2025_06_18).protocol_mismatch(),
+            Some(ProtocolVersionMismatch::ServerNewer)
+        );
+
+        let unknown: ProtocolVersion = serde_json::from_str("\"2099-01-01\"").unwrap();
+        assert_eq!(
+            info(unknown).protocol_mismatch(),
+            Some(ProtocolVersionMismatch::Unknown)
+        );
+    }
+}
@@ -40,10 +40,14 @@ async fn test_mcp_client_connection() {
     // 実際のMCPサーバーがある場合のみテストを実行
     // 例: uvx mcp-server-git が利用可能な場合
     match McpClient::new("bash", vec![server_path.to_str().unwrap()]).await {
-        Ok(_client) => {
+        Ok(client) => {
             // 接続成功の場合は基本的な動作を確認
             // Note: モックサーバーは簡易実装のため、完全な動作は保証されません
             eprintln!("モックサーバーへの接続に成功しました");
+            assert!(
+                client.pid().is_some(),
+                "サーバープロセスのPIDを取得できること"
+            );
 
             // 実際のプロダクション環境では、ここで以下のようなテストを行います：
             // let tools = client.list_tools().await.expect("ツール一覧の取得に失敗");